@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use imghash::{
+    average::AverageHasher, difference::DifferenceHasher, median::MedianHasher, multi::MultiHasher,
+    perceptual::PerceptualHasher, HashContext, ImageHasher,
+};
+
+const TEST_IMG: &str = "./data/img/test.png";
+
+fn hashers() -> Vec<Box<dyn ImageHasher + Send + Sync>> {
+    vec![
+        Box::new(AverageHasher::default()),
+        Box::new(DifferenceHasher::default()),
+        Box::new(MedianHasher::default()),
+        Box::new(PerceptualHasher::default()),
+    ]
+}
+
+fn bench_multi(c: &mut Criterion) {
+    let path = Path::new(TEST_IMG);
+    let individual = hashers();
+    let multi = MultiHasher::new(hashers()).unwrap();
+
+    let mut group = c.benchmark_group("four hashes from path");
+    group.sample_size(20);
+
+    group.bench_function("individual hashers", |b| {
+        b.iter(|| {
+            individual
+                .iter()
+                .map(|hasher| hasher.hash_from_path(path).unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.bench_function("multi hasher", |b| {
+        b.iter(|| multi.hash_from_path(path).unwrap())
+    });
+
+    group.finish();
+
+    // without decoding, the allocations of the intermediate images show up
+    let img = image::open(path).unwrap();
+    let mut ctx = HashContext::new();
+
+    let mut group = c.benchmark_group("four hashes from image");
+    group.sample_size(20);
+
+    group.bench_function("multi hasher", |b| {
+        b.iter(|| multi.hash_from_img(&img).unwrap())
+    });
+
+    group.bench_function("multi hasher with context", |b| {
+        b.iter(|| multi.hash_from_img_with(&mut ctx, &img).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_multi);
+criterion_main!(benches);
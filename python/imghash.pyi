@@ -0,0 +1,25 @@
+from os import PathLike
+from typing import Union
+
+from typing_extensions import Buffer
+
+Image = Union[str, PathLike[str], bytes, bytearray, Buffer]
+
+class ImageHash:
+    shape: tuple[int, int]
+
+    def __sub__(self, other: ImageHash) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def __len__(self) -> int: ...
+    def __str__(self) -> str: ...
+    def __repr__(self) -> str: ...
+
+def average_hash(image: Image, hash_size: int = 8) -> ImageHash: ...
+def median_hash(image: Image, hash_size: int = 8) -> ImageHash: ...
+def difference_hash(image: Image, hash_size: int = 8) -> ImageHash: ...
+def perceptual_hash(image: Image, hash_size: int = 8, highfreq_factor: int = 4) -> ImageHash: ...
+def hex_to_hash(hexstr: str) -> ImageHash: ...
+
+dhash = difference_hash
+phash = perceptual_hash
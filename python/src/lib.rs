@@ -0,0 +1,231 @@
+//! Python bindings for `imghash`, mirroring the functions of the Python `imagehash` package.
+//!
+//! The hashers use the `REC601` color space, which grayscales images like Pillow does, so the
+//! hashes are identical to the ones of `imagehash` for the same image.
+
+use std::borrow::Cow;
+use std::io;
+use std::path::PathBuf;
+
+use imghash::average::AverageHasher;
+use imghash::difference::DifferenceHasher;
+use imghash::median::MedianHasher;
+use imghash::perceptual::PerceptualHasher;
+use imghash::{ColorSpace, ImageHash, ImageHashError, ImageHasher, PixelLayout, RawImage};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyFileNotFoundError, PyOSError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes};
+
+/// The hash of an image.
+///
+/// Subtracting two hashes gives the number of bits in which they differ and `str()` gives
+/// the hexadecimal string of the `imagehash` package, which `hex_to_hash` turns back into
+/// a hash.
+#[pyclass(name = "ImageHash", module = "imghash", frozen, eq, hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct PyImageHash(ImageHash);
+
+#[pymethods]
+impl PyImageHash {
+    fn __sub__(&self, other: &PyImageHash) -> PyResult<usize> {
+        self.0
+            .distance(&other.0)
+            .map_err(|_| PyTypeError::new_err("ImageHashes must be of the same shape."))
+    }
+
+    fn __str__(&self) -> PyResult<String> {
+        self.0.encode().map_err(to_py_err)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("ImageHash('{}')", self.__str__()?))
+    }
+
+    /// The number of bits of the hash.
+    fn __len__(&self) -> usize {
+        let (rows, columns) = self.0.shape();
+        rows * columns
+    }
+
+    /// The shape of the hash, in (number of rows, number of columns).
+    #[getter]
+    fn shape(&self) -> (usize, usize) {
+        self.0.shape()
+    }
+}
+
+/// An image passed in from Python.
+enum Input<'a> {
+    /// The path of an image file
+    Path(PathBuf),
+
+    /// An encoded image, like the contents of a PNG file
+    Encoded(Cow<'a, [u8]>),
+
+    /// Decoded pixels, row by row
+    Pixels {
+        pixels: Vec<u8>,
+        width: u32,
+        height: u32,
+        layout: PixelLayout,
+    },
+}
+
+impl<'a> Input<'a> {
+    /// Accepts paths, bytes of an encoded image and `uint8` arrays of grayscale, RGB or RGBA
+    /// pixels, like the ones of `numpy.asarray` for a Pillow image.
+    fn extract(image: &'a Bound<'_, PyAny>) -> PyResult<Input<'a>> {
+        // bytes are checked first, because they would be accepted as a path as well
+        if let Ok(bytes) = image.cast::<PyBytes>() {
+            return Ok(Input::Encoded(Cow::Borrowed(bytes.as_bytes())));
+        }
+
+        // the contents of a bytearray may change once the GIL is released, so they are copied
+        if let Ok(bytes) = image.cast::<PyByteArray>() {
+            return Ok(Input::Encoded(Cow::Owned(bytes.to_vec())));
+        }
+
+        if let Ok(path) = image.extract::<PathBuf>() {
+            return Ok(Input::Path(path));
+        }
+
+        let invalid = || {
+            PyTypeError::new_err(
+                "expected a path, bytes or a uint8 array of shape (height, width), \
+                 (height, width, 3) or (height, width, 4)",
+            )
+        };
+
+        let buffer = PyBuffer::<u8>::get(image).map_err(|_| invalid())?;
+        let layout = match buffer.shape() {
+            [_, _] | [_, _, 1] => PixelLayout::Luma,
+            [_, _, 3] => PixelLayout::Rgb,
+            [_, _, 4] => PixelLayout::Rgba,
+            _ => return Err(invalid()),
+        };
+
+        let (height, width) = (buffer.shape()[0], buffer.shape()[1]);
+
+        Ok(Input::Pixels {
+            // copying the pixels in row-major order also takes care of strided arrays
+            pixels: buffer.to_vec(image.py())?,
+            width: width.try_into().map_err(|_| invalid())?,
+            height: height.try_into().map_err(|_| invalid())?,
+            layout,
+        })
+    }
+
+    fn hash(&self, hasher: &impl ImageHasher) -> Result<ImageHash, ImageHashError> {
+        match self {
+            Input::Path(path) => hasher.hash_from_path(path),
+            Input::Encoded(bytes) => hasher.hash_from_bytes(bytes),
+            Input::Pixels {
+                pixels,
+                width,
+                height,
+                layout,
+            } => {
+                let stride = *width as usize * layout.bytes_per_pixel();
+                hasher.hash_from_raw(&RawImage::new(pixels, *width, *height, stride, *layout)?)
+            }
+        }
+    }
+}
+
+/// Hashes an image without holding the GIL, so other Python threads keep running.
+fn hash(
+    image: &Bound<'_, PyAny>,
+    hasher: Result<impl ImageHasher + Sync, ImageHashError>,
+) -> PyResult<PyImageHash> {
+    let hasher = hasher.map_err(to_py_err)?;
+    let input = Input::extract(image)?;
+
+    image
+        .py()
+        .detach(|| input.hash(&hasher))
+        .map(PyImageHash)
+        .map_err(to_py_err)
+}
+
+fn to_py_err(err: ImageHashError) -> PyErr {
+    match &err {
+        ImageHashError::IoError { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+            PyFileNotFoundError::new_err(err.to_string())
+        }
+        ImageHashError::IoError { .. } => PyOSError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+/// Average hash, like `imagehash.average_hash`.
+#[pyfunction]
+#[pyo3(signature = (image, hash_size = 8))]
+fn average_hash(image: &Bound<'_, PyAny>, hash_size: u16) -> PyResult<PyImageHash> {
+    hash(
+        image,
+        AverageHasher::new(hash_size, hash_size, ColorSpace::REC601),
+    )
+}
+
+/// Median hash, which compares each pixel to the median instead of the mean intensity.
+#[pyfunction]
+#[pyo3(signature = (image, hash_size = 8))]
+fn median_hash(image: &Bound<'_, PyAny>, hash_size: u16) -> PyResult<PyImageHash> {
+    hash(
+        image,
+        MedianHasher::new(hash_size, hash_size, ColorSpace::REC601),
+    )
+}
+
+/// Difference hash, like `imagehash.dhash`.
+#[pyfunction]
+#[pyo3(signature = (image, hash_size = 8))]
+fn difference_hash(image: &Bound<'_, PyAny>, hash_size: u16) -> PyResult<PyImageHash> {
+    hash(
+        image,
+        DifferenceHasher::new(hash_size, hash_size, ColorSpace::REC601),
+    )
+}
+
+/// Perceptual hash, like `imagehash.phash`.
+#[pyfunction]
+#[pyo3(signature = (image, hash_size = 8, highfreq_factor = 4))]
+fn perceptual_hash(
+    image: &Bound<'_, PyAny>,
+    hash_size: u16,
+    highfreq_factor: u8,
+) -> PyResult<PyImageHash> {
+    hash(
+        image,
+        PerceptualHasher::new(hash_size, hash_size, highfreq_factor, ColorSpace::REC601),
+    )
+}
+
+/// Decodes the hexadecimal string of a square hash, like `imagehash.hex_to_hash`.
+#[pyfunction]
+fn hex_to_hash(hexstr: &str) -> PyResult<PyImageHash> {
+    // like `imagehash`, the size of the square is derived from the number of bits
+    let size = ((hexstr.len() * 4) as f64).sqrt() as u16;
+
+    ImageHash::decode(hexstr, size, size)
+        .map(PyImageHash)
+        .map_err(to_py_err)
+}
+
+#[pymodule]
+#[pyo3(name = "imghash")]
+fn imghash_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyImageHash>()?;
+    m.add_function(wrap_pyfunction!(average_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(median_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(difference_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(perceptual_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(hex_to_hash, m)?)?;
+
+    // the short names of the `imagehash` package
+    m.add("dhash", m.getattr("difference_hash")?)?;
+    m.add("phash", m.getattr("perceptual_hash")?)?;
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+import struct
+import zlib
+from pathlib import Path
+
+import pytest
+
+import imghash
+
+IMG_DIR = Path(__file__).resolve().parents[2] / "data" / "img"
+TEST_IMG = IMG_DIR / "test.png"
+LOGO_IMG = IMG_DIR / "logo-white.png"
+
+
+def png(pixels, width, height, channels):
+    """Encodes 8-bit pixels as a PNG without any dependencies."""
+
+    def chunk(kind, data):
+        return (
+            struct.pack(">I", len(data))
+            + kind
+            + data
+            + struct.pack(">I", zlib.crc32(kind + data))
+        )
+
+    color_type = {1: 0, 3: 2, 4: 6}[channels]
+    row = width * channels
+    scanlines = b"".join(
+        b"\x00" + pixels[y * row : (y + 1) * row] for y in range(height)
+    )
+
+    return (
+        b"\x89PNG\r\n\x1a\n"
+        + chunk(b"IHDR", struct.pack(">IIBBBBB", width, height, 8, color_type, 0, 0, 0))
+        + chunk(b"IDAT", zlib.compress(scanlines))
+        + chunk(b"IEND", b"")
+    )
+
+
+def gradient(width, height, channels):
+    return bytes(
+        (x * 7 + y * 3 + c * 50) % 256
+        for y in range(height)
+        for x in range(width)
+        for c in range(channels)
+    )
+
+
+def test_hashes_of_test_image():
+    # Act & Assert: the same hashes as the Rust tests
+    assert str(imghash.average_hash(TEST_IMG)) == "ffffff0e00000301"
+    assert str(imghash.median_hash(TEST_IMG)) == "ffffff1e00000301"
+    assert str(imghash.difference_hash(TEST_IMG)) == "cc99717ed9ea0627"
+    assert str(imghash.perceptual_hash(TEST_IMG)) == "acdbe86135344e3a"
+
+
+def test_short_names_of_imagehash():
+    # Act & Assert
+    assert imghash.dhash is imghash.difference_hash
+    assert imghash.phash is imghash.perceptual_hash
+
+
+def test_paths_and_bytes_give_same_hash():
+    # Arrange
+    data = TEST_IMG.read_bytes()
+
+    # Act
+    hashes = [
+        imghash.perceptual_hash(TEST_IMG, hash_size=16),
+        imghash.perceptual_hash(str(TEST_IMG), hash_size=16),
+        imghash.perceptual_hash(data, hash_size=16),
+        imghash.perceptual_hash(bytearray(data), hash_size=16),
+    ]
+
+    # Assert
+    assert all(h == hashes[0] for h in hashes)
+    assert len(hashes[0]) == 256
+
+
+@pytest.mark.parametrize("channels", [1, 3, 4])
+def test_pixel_buffers_give_same_hash_as_encoded_image(channels):
+    # Arrange
+    width, height = 40, 30
+    pixels = gradient(width, height, channels)
+    shape = (height, width) if channels == 1 else (height, width, channels)
+    array = memoryview(pixels).cast("B", shape)
+
+    # Act
+    from_pixels = imghash.average_hash(array)
+    from_png = imghash.average_hash(png(pixels, width, height, channels))
+
+    # Assert
+    assert from_pixels == from_png
+
+
+def test_numpy_arrays_give_same_hash_as_paths():
+    # Arrange
+    numpy = pytest.importorskip("numpy")
+    image = pytest.importorskip("PIL.Image")
+    array = numpy.asarray(image.open(TEST_IMG).convert("RGB"))
+
+    # Act & Assert
+    assert imghash.phash(array) == imghash.phash(TEST_IMG)
+    assert imghash.dhash(array[::2, ::2]) == imghash.dhash(
+        numpy.ascontiguousarray(array[::2, ::2])
+    )
+
+
+@pytest.mark.parametrize("hash_size", [8, 16])
+def test_hashes_match_imagehash(hash_size):
+    # Arrange
+    numpy = pytest.importorskip("numpy")
+    imagehash = pytest.importorskip("imagehash")
+    image = pytest.importorskip("PIL.Image").open(TEST_IMG)
+
+    # Act & Assert
+    assert str(imghash.average_hash(TEST_IMG, hash_size)) == str(
+        imagehash.average_hash(image, hash_size)
+    )
+    assert str(imghash.median_hash(TEST_IMG, hash_size)) == str(
+        imagehash.average_hash(image, hash_size, mean=numpy.median)
+    )
+    assert str(imghash.dhash(TEST_IMG, hash_size)) == str(
+        imagehash.dhash(image, hash_size)
+    )
+    assert str(imghash.phash(TEST_IMG, hash_size, highfreq_factor=4)) == str(
+        imagehash.phash(image, hash_size, highfreq_factor=4)
+    )
+
+
+def test_subtracting_gives_distance():
+    # Arrange
+    a = imghash.average_hash(TEST_IMG)
+    b = imghash.average_hash(LOGO_IMG)
+
+    # Act & Assert
+    assert a - a == 0
+    assert a - b == b - a
+    assert 0 < a - b <= 64
+
+
+def test_subtracting_hashes_of_different_shapes():
+    # Arrange
+    a = imghash.average_hash(TEST_IMG)
+    b = imghash.average_hash(TEST_IMG, hash_size=16)
+
+    # Act & Assert
+    with pytest.raises(TypeError):
+        a - b
+
+
+def test_hex_to_hash_round_trips():
+    # Arrange
+    original = imghash.perceptual_hash(TEST_IMG, hash_size=16)
+
+    # Act
+    decoded = imghash.hex_to_hash(str(original))
+
+    # Assert
+    assert decoded == original
+    assert hash(decoded) == hash(original)
+    assert decoded.shape == (16, 16)
+    assert repr(decoded) == f"ImageHash('{original}')"
+
+
+def test_hex_to_hash_with_invalid_characters():
+    # Act & Assert
+    with pytest.raises(ValueError):
+        imghash.hex_to_hash("ffffff0e0000030g")
+
+
+def test_invalid_images():
+    # Act & Assert
+    with pytest.raises(FileNotFoundError):
+        imghash.average_hash(IMG_DIR / "missing.png")
+
+    with pytest.raises(ValueError):
+        imghash.average_hash(b"not an image")
+
+    with pytest.raises(TypeError):
+        imghash.average_hash(42)
+
+    with pytest.raises(TypeError):
+        imghash.average_hash(memoryview(bytes(8)).cast("B", (2, 2, 2)))
+
+
+def test_invalid_hash_size():
+    # Act & Assert
+    with pytest.raises(ValueError):
+        imghash.average_hash(TEST_IMG, hash_size=0)
@@ -1,66 +1,207 @@
-use crate::{imageops::convert, ColorSpace, ImageHash, ImageHashError, ImageHasher};
+use crate::imghash::{dimension, invalid_dimension};
+use crate::{
+    AlphaPolicy, ColorSpace, HashAlgorithm, HashContext, HasherSpec, ImageHash, ImageHashError,
+    ImageHasher, Normalization, ResizeTarget, ToneMapping,
+};
+
+#[cfg(feature = "image")]
+use crate::{
+    imageops::{self, ConvertBuffers},
+    BitMargins,
+};
 
 #[derive(Debug, Clone)]
 pub struct AverageHasher {
     /// The target width of the matrix
-    width: u8,
+    width: u16,
 
     /// The target height of the matrix
-    height: u8,
+    height: u16,
 
     /// The color space which will be used for grayscaling.
     /// Default is Rec. 601
     color_space: ColorSpace,
+
+    /// The way transparent pixels are treated during grayscaling.
+    /// Default is to ignore the alpha channel
+    alpha: AlphaPolicy,
+
+    /// The tone mapping for high bit depth and high dynamic range images.
+    /// Default is no tone mapping
+    tone_mapping: ToneMapping,
+
+    /// The contrast normalization that is applied after grayscaling.
+    /// Default is no normalization
+    normalization: Normalization,
 }
 
 impl AverageHasher {
-    pub fn new(width: u8, height: u8, color_space: ColorSpace) -> Result<Self, ImageHashError> {
+    pub fn new(
+        width: impl TryInto<u16>,
+        height: impl TryInto<u16>,
+        color_space: ColorSpace,
+    ) -> Result<Self, ImageHashError> {
+        let (width, height) = (dimension(width, "width")?, dimension(height, "height")?);
+
         if width == 0 || height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        Ok(Self {
+        color_space.check()?;
+
+        let hasher = Self {
             width,
             height,
             color_space,
-        })
+            alpha: AlphaPolicy::default(),
+            tone_mapping: ToneMapping::default(),
+            normalization: Normalization::default(),
+        };
+
+        // the resized image must stay addressable, even on 16-bit and 32-bit targets
+        hasher.target().checked_len()?;
+
+        Ok(hasher)
+    }
+
+    /// Creates a builder for a [`AverageHasher`], starting from the default configuration.
+    ///
+    /// Unlike [`AverageHasher::new`], the builder reports which parameter is invalid.
+    pub fn builder() -> AverageHasherBuilder {
+        AverageHasherBuilder::default()
     }
 
-    pub fn width(&self) -> u8 {
+    pub fn width(&self) -> u16 {
         self.width
     }
 
-    pub fn height(&self) -> u8 {
+    pub fn height(&self) -> u16 {
         self.height
     }
 
     pub fn color_space(&self) -> ColorSpace {
         self.color_space
     }
+
+    pub fn alpha(&self) -> AlphaPolicy {
+        self.alpha
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// The grayscaled and resized image the hash is computed on.
+    fn target(&self) -> ResizeTarget {
+        ResizeTarget {
+            width: self.width as u32,
+            height: self.height as u32,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+        }
+    }
+
+    /// Generates the hash of an image along with the margin of each bit, which is the
+    /// distance of its pixel to the mean relative to the full luma range.
+    ///
+    /// # Arguments
+    /// * `img`: The image to hash
+    ///
+    /// # Returns
+    /// * The same hash as [`ImageHasher::hash_from_img`] and the [`BitMargins`] of its bits
+    #[cfg(feature = "image")]
+    pub fn hash_with_confidence(
+        &self,
+        img: &image::DynamicImage,
+    ) -> Result<(ImageHash, BitMargins), ImageHashError> {
+        let mut buffers = ConvertBuffers::default();
+        let mut values = Vec::new();
+        imageops::convert_target(img, &self.target(), &mut buffers).extend_f64(&mut values);
+
+        if values.is_empty() {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+
+        BitMargins::threshold(&values, mean, 255.0, self.width, self.height)
+    }
 }
 
 impl ImageHasher for AverageHasher {
+    #[cfg(feature = "image")]
     fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_img_with(&mut HashContext::new(), img)
+    }
+
+    fn resize_target(&self) -> Option<ResizeTarget> {
+        Some(self.target())
+    }
+
+    fn hash_from_resized(
+        &self,
+        _ctx: &mut HashContext,
+        pixels: &[u8],
+    ) -> Result<ImageHash, ImageHashError> {
         if self.width == 0 || self.height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        let width = self.width as u32;
-        let height = self.height as u32;
+        let target = self.target();
+        target.check(pixels)?;
 
-        let converted = convert(img, width, height, self.color_space);
-        let mean = converted
-            .as_bytes()
-            .iter()
-            .fold(0, |acc, x| acc + *x as usize)
-            / (width as usize * height as usize);
+        let mean = pixels.iter().fold(0, |acc, x| acc + *x as usize) / target.len();
 
         ImageHash::from_bool_iter(
-            converted.as_bytes().iter().map(|&p| p as usize > mean),
+            pixels.iter().map(|&p| p as usize > mean),
             self.width,
             self.height,
         )
     }
+
+    fn hash_from_resized_f32(
+        &self,
+        _ctx: &mut HashContext,
+        pixels: &[f32],
+    ) -> Result<ImageHash, ImageHashError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        let target = self.target();
+        target.check(pixels)?;
+
+        let mean = pixels.iter().map(|&p| p as f64).sum::<f64>() / target.len() as f64;
+
+        ImageHash::from_bool_iter(
+            pixels.iter().map(|&p| p as f64 > mean),
+            self.width,
+            self.height,
+        )
+    }
+
+    fn name(&self) -> &str {
+        "ahash"
+    }
+
+    fn spec(&self) -> Option<HasherSpec> {
+        Some(HasherSpec {
+            algorithm: HashAlgorithm::Average,
+            width: self.width,
+            height: self.height,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+            ..HasherSpec::new(HashAlgorithm::Average)
+        })
+    }
 }
 
 impl Default for AverageHasher {
@@ -69,20 +210,124 @@ impl Default for AverageHasher {
             width: 8,
             height: 8,
             color_space: ColorSpace::REC601,
+            alpha: AlphaPolicy::Ignore,
+            tone_mapping: ToneMapping::None,
+            normalization: Normalization::None,
         }
     }
 }
 
-#[cfg(test)]
+/// A builder for a [`AverageHasher`], created by [`AverageHasher::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct AverageHasherBuilder {
+    /// The configuration that is validated on [`AverageHasherBuilder::build`]
+    hasher: AverageHasher,
+
+    /// The first dimension that does not fit into `u16`, reported on [`AverageHasherBuilder::build`]
+    invalid: Option<&'static str>,
+}
+
+impl AverageHasherBuilder {
+    /// Sets the target width and height of the matrix.
+    pub fn size(self, width: impl TryInto<u16>, height: impl TryInto<u16>) -> Self {
+        self.width(width).height(height)
+    }
+
+    /// Sets the target width of the matrix.
+    pub fn width(mut self, width: impl TryInto<u16>) -> Self {
+        match width.try_into() {
+            Ok(width) => self.hasher.width = width,
+            Err(_) => self.invalid = self.invalid.or(Some("width")),
+        }
+        self
+    }
+
+    /// Sets the target height of the matrix.
+    pub fn height(mut self, height: impl TryInto<u16>) -> Self {
+        match height.try_into() {
+            Ok(height) => self.hasher.height = height,
+            Err(_) => self.invalid = self.invalid.or(Some("height")),
+        }
+        self
+    }
+
+    /// Sets the color space which will be used for grayscaling.
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.hasher.color_space = color_space;
+        self
+    }
+
+    /// Sets the way transparent pixels are treated during grayscaling.
+    pub fn alpha(mut self, alpha: AlphaPolicy) -> Self {
+        self.hasher.alpha = alpha;
+        self
+    }
+
+    /// Sets the tone mapping for high bit depth and high dynamic range images.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.hasher.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Sets the contrast normalization that is applied after grayscaling.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.hasher.normalization = normalization;
+        self
+    }
+
+    /// Validates the configuration and creates the [`AverageHasher`].
+    ///
+    /// # Returns
+    /// * The new [`AverageHasher`]
+    /// * An [`ImageHashError::InvalidParameter`] naming the first invalid parameter,
+    ///   including custom color space coefficients that are not finite and CLAHE
+    ///   parameters of zero
+    /// * An [`ImageHashError::DimensionOverflow`] if the resized image would not be addressable
+    pub fn build(&self) -> Result<AverageHasher, ImageHashError> {
+        if let Some(name) = self.invalid {
+            return Err(invalid_dimension(name));
+        }
+
+        if self.hasher.width == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "width",
+                reason: "must be greater than zero",
+            });
+        }
+
+        if self.hasher.height == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "height",
+                reason: "must be greater than zero",
+            });
+        }
+
+        self.hasher.color_space.check()?;
+        self.hasher.normalization.check()?;
+        self.hasher.target().checked_len()?;
+
+        Ok(self.hasher.clone())
+    }
+}
+
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use std::path::Path;
 
-    use image::ImageReader;
+    use image::{DynamicImage, ImageBuffer, ImageReader, Luma};
 
     use super::*;
+    use crate::{DecodeOptions, Normalization, Orientation, PixelLayout, RawImage};
 
     const TEST_IMG: &str = "./data/img/test.png";
     const TXT_FILE: &str = "./data/misc/test.txt";
+    const GRAY_IMG: &str = "./data/img/gray-601.png";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+    const DARK_IMG: &str = "./data/img/test-dark.png";
+    const LOW_CONTRAST_IMG: &str = "./data/img/test-low-contrast.png";
+    const LOGO_IMG: &str = "./data/img/logo-alpha.png";
+    const LOGO_WHITE_IMG: &str = "./data/img/logo-white.png";
+    const LOGO_BLACK_IMG: &str = "./data/img/logo-black.png";
 
     const REC_601_HASH: &str = "ffffff0e00000301";
     const REC_709_HASH: &str = "ffffff0e00000301";
@@ -109,6 +354,110 @@ mod tests {
         assert_eq!(hasher.color_space(), ColorSpace::REC601);
     }
 
+    #[test]
+    fn test_new_with_u8_dimensions() {
+        // Arrange: dimensions coming from code written against the former u8 API
+        let (width, height): (u8, u8) = (16, 12);
+
+        // Act
+        let hasher = AverageHasher::new(width, height, ColorSpace::REC601).unwrap();
+
+        // Assert
+        assert_eq!((hasher.width(), hasher.height()), (16, 12));
+    }
+
+    #[test]
+    fn test_new_with_dimension_beyond_u16() {
+        // Act
+        let result = AverageHasher::new(8, 70_000, ColorSpace::REC601);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "height", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_defaults() {
+        // Act
+        let hasher = AverageHasher::builder().build().unwrap();
+
+        // Assert
+        let default = AverageHasher::default();
+        assert_eq!(hasher.width(), default.width());
+        assert_eq!(hasher.height(), default.height());
+        assert_eq!(hasher.color_space(), default.color_space());
+    }
+
+    #[test]
+    fn test_builder_with_custom_configuration() {
+        // Act
+        let hasher = AverageHasher::builder()
+            .size(16, 12)
+            .color_space(ColorSpace::REC709)
+            .build()
+            .unwrap();
+
+        // Assert
+        assert_eq!(hasher.width(), 16);
+        assert_eq!(hasher.height(), 12);
+        assert_eq!(hasher.color_space(), ColorSpace::REC709);
+    }
+
+    #[test]
+    fn test_builder_with_zero_width() {
+        // Act
+        let result = AverageHasher::builder().width(0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "width", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_negative_width() {
+        // Act: the later valid size doesn't hide the invalid width
+        let result = AverageHasher::builder().width(-1).size(8, 8).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "width", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_zero_height() {
+        // Act
+        let result = AverageHasher::builder().size(8, 0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "height", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_invalid_custom_color_space() {
+        // Act
+        let result = AverageHasher::builder()
+            .color_space(ColorSpace::Custom([0.5, f64::INFINITY, 0.5]))
+            .build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter {
+                name: "color_space",
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_average_hash_from_img() {
         // Arrange
@@ -145,6 +494,26 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_709_HASH)
     }
 
+    #[test]
+    fn test_average_hash_from_img_with_reused_context() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = AverageHasher::default();
+        let mut ctx = HashContext::new();
+
+        // Act
+        let first = hasher.hash_from_img_with(&mut ctx, &img);
+        let second = hasher.hash_from_img_with(&mut ctx, &img);
+
+        // Assert
+        assert_eq!(first.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_eq!(second.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
     #[test]
     fn test_average_hash_from_path() {
         // Arrange
@@ -158,6 +527,109 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
     }
 
+    #[test]
+    fn test_average_hash_from_bytes() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+        let hasher = AverageHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes(&bytes);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_average_hash_from_reader_with_format() {
+        // Arrange
+        let file = std::fs::File::open(TEST_IMG).unwrap();
+        let hasher = AverageHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_reader_with_format(std::io::BufReader::new(file), image::ImageFormat::Png);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_average_hash_from_luma() {
+        // Arrange
+        let img = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = AverageHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_luma(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize,
+        );
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_average_hash_from_luma_with_normalization() {
+        // Arrange
+        let img = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = AverageHasher::builder()
+            .normalization(Normalization::Equalize)
+            .build()
+            .unwrap();
+
+        // Act
+        let hash = hasher.hash_from_luma(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize,
+        );
+
+        // Assert: the unpadded luma plane is normalized like a decoded image
+        assert_eq!(hash.unwrap(), hasher.hash_from_img(&img).unwrap());
+    }
+
+    #[test]
+    fn test_average_hash_from_raw_rgb() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let raw = RawImage::new(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize * 3,
+            PixelLayout::Rgb,
+        )
+        .unwrap();
+        let hasher = AverageHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_raw(&raw);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
     #[test]
     fn test_average_hash_from_img_with_non_default_size() {
         // Arrange
@@ -177,6 +649,169 @@ mod tests {
         assert_eq!(hash.shape(), (16, 16));
     }
 
+    #[test]
+    fn test_average_hash_from_img_with_width_larger_than_u8() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = AverageHasher::new(300, 2, ColorSpace::REC601).unwrap();
+
+        // Act
+        let hash = hasher.hash_from_img(&img);
+
+        // Assert
+        assert_eq!(hash.unwrap().shape(), (2, 300));
+    }
+
+    #[test]
+    fn test_average_hash_with_alpha_composited_over_background() {
+        for (background, flattened) in [([255; 3], LOGO_WHITE_IMG), ([0; 3], LOGO_BLACK_IMG)] {
+            // Arrange
+            let hasher = AverageHasher::builder()
+                .alpha(AlphaPolicy::CompositeOver(background))
+                .build()
+                .unwrap();
+
+            // Act
+            let hash = hasher.hash_from_path(Path::new(LOGO_IMG)).unwrap();
+
+            // Assert: the transparent logo hashes like the logo shown on the background
+            let expected = AverageHasher::default()
+                .hash_from_path(Path::new(flattened))
+                .unwrap();
+            assert_eq!(hash, expected);
+        }
+    }
+
+    #[test]
+    fn test_average_hash_with_alpha_ignored() {
+        // Arrange
+        let over_black = AverageHasher::builder()
+            .alpha(AlphaPolicy::CompositeOver([0; 3]))
+            .build()
+            .unwrap();
+
+        // Act
+        let ignored = AverageHasher::default()
+            .hash_from_path(Path::new(LOGO_IMG))
+            .unwrap();
+        let composited = over_black.hash_from_path(Path::new(LOGO_IMG)).unwrap();
+
+        // Assert: ignoring the alpha hashes the colors below the transparent pixels
+        assert_ne!(ignored, composited);
+    }
+
+    #[test]
+    fn test_average_hash_from_narrow_range_16_bit_image() {
+        // Arrange: the gray image squeezed into 256 of 65536 levels, which would collapse
+        // into two values when quantized to 8 bits
+        let gray = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_luma8();
+        let narrow = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([30000 + gray.get_pixel(x, y).0[0] as u16])
+        });
+        let full = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([gray.get_pixel(x, y).0[0] as u16 * 257])
+        });
+
+        let hasher = AverageHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(narrow))
+            .unwrap();
+
+        // Assert
+        let expected = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(full))
+            .unwrap();
+        assert_eq!(hash, expected);
+    }
+
+    #[test]
+    fn test_average_hash_from_path_with_orientation() {
+        // Arrange
+        let hasher = AverageHasher::default();
+
+        // Act
+        let oriented =
+            hasher.hash_from_path_with_options(Path::new(ROTATED_IMG), DecodeOptions::new());
+        let stored = hasher.hash_from_path(Path::new(ROTATED_IMG));
+
+        // Assert: the stored image is rotated, applying the orientation brings it upright
+        assert_eq!(oriented.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_ne!(stored.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
+    #[test]
+    fn test_average_hash_from_bytes_with_orientation() {
+        // Arrange
+        let bytes = std::fs::read(ROTATED_IMG).unwrap();
+        let hasher = AverageHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes_with_options(&bytes, DecodeOptions::new());
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_average_hash_with_equalization_is_robust_to_exposure() {
+        // Arrange
+        let plain = AverageHasher::default();
+        let equalized = AverageHasher::builder()
+            .normalization(Normalization::Equalize)
+            .build()
+            .unwrap();
+
+        let distance = |hasher: &AverageHasher, altered: &str| {
+            let original = hasher.hash_from_path(Path::new(TEST_IMG)).unwrap();
+            let altered = hasher.hash_from_path(Path::new(altered)).unwrap();
+            original.distance(&altered).unwrap()
+        };
+
+        for altered in [DARK_IMG, LOW_CONTRAST_IMG] {
+            // Act
+            let before = distance(&plain, altered);
+            let after = distance(&equalized, altered);
+
+            // Assert: the darkened copy flips a few bits without normalization, but at most
+            // two once the histograms are equalized
+            assert!(after <= before, "{altered}: {after} > {before}");
+            assert!(after <= 2, "{altered}: {after}");
+        }
+
+        assert!(distance(&plain, DARK_IMG) >= 3);
+    }
+
+    #[test]
+    fn test_average_builder_with_zero_clahe_tiles() {
+        // Act
+        let result = AverageHasher::builder()
+            .normalization(Normalization::Clahe {
+                tiles: 0,
+                clip_limit: 2,
+            })
+            .build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter {
+                name: "normalization",
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_average_hash_from_nonexisting_path() {
         // Arrange
@@ -200,4 +835,94 @@ mod tests {
         // Assert
         assert!(hash.is_err());
     }
+
+    #[test]
+    fn test_average_hash_orient_matches_transformed_images() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = AverageHasher::default();
+        let hash = hasher.hash_from_img(&img).unwrap();
+
+        let transformed = [
+            (Orientation::Rotate90, img.rotate90()),
+            (Orientation::Rotate180, img.rotate180()),
+            (Orientation::Rotate270, img.rotate270()),
+            (Orientation::FlipHorizontal, img.fliph()),
+            (Orientation::FlipVertical, img.flipv()),
+        ];
+
+        for (orientation, transformed) in transformed {
+            // Act
+            let expected = hasher.hash_from_img(&transformed).unwrap();
+
+            // Assert
+            assert_eq!(hash.orient(orientation), expected, "{:?}", orientation);
+            assert_eq!(
+                hash.min_distance_dihedral(&expected).unwrap(),
+                (0, orientation)
+            );
+        }
+    }
+
+    #[test]
+    fn test_average_hash_with_confidence_matches_hash() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = AverageHasher::default();
+
+        // Act
+        let (hash, margins) = hasher.hash_with_confidence(&img).unwrap();
+
+        // Assert
+        assert_eq!(hash, hasher.hash_from_img(&img).unwrap());
+        assert_eq!(margins.shape(), hash.shape());
+        assert!(margins.margins().iter().all(|m| (0.0..=1.0).contains(m)));
+        assert!(margins.margins().iter().any(|&m| m > 0.0));
+    }
+
+    #[test]
+    fn test_average_hash_with_confidence_flags_unstable_bits() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let dark = ImageReader::open(Path::new(DARK_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = AverageHasher::default();
+
+        // Act
+        let (hash, margins) = hasher.hash_with_confidence(&img).unwrap();
+        let dark_hash = hasher.hash_from_img(&dark).unwrap();
+
+        // Assert: the bits that flip all sit in the lower three quarters of the margins
+        let mut sorted = margins.margins().to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let quartile = sorted[sorted.len() * 3 / 4];
+
+        let flipped = hash
+            .iter_bool()
+            .zip(dark_hash.iter_bool())
+            .zip(margins.margins())
+            .filter(|((a, b), _)| a != b)
+            .map(|(_, &margin)| margin)
+            .collect::<Vec<_>>();
+
+        assert!(!flipped.is_empty());
+        assert!(flipped.iter().all(|&margin| margin < quartile));
+        assert_eq!(
+            hash.distance_masked(&dark_hash, &margins.mask(0.1))
+                .unwrap()
+                .0,
+            0
+        );
+    }
 }
@@ -0,0 +1,339 @@
+//! Parallel hashing of many image files.
+//!
+//! This module is only available with the `rayon` feature enabled. It decodes and hashes
+//! images on a dedicated [`rayon`] thread pool and hands the results back through an iterator.
+//!
+//! ```no_run
+//! use imghash::{batch::hash_paths, perceptual::PerceptualHasher};
+//!
+//! let paths = vec!["a.png", "b.png", "c.png"];
+//!
+//! for (path, hash) in hash_paths(PerceptualHasher::default(), paths) {
+//!     match hash {
+//!         Ok(hash) => println!("{}: {}", path.display(), hash),
+//!         Err(e) => eprintln!("{}: {}", path.display(), e),
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver};
+
+use rayon::prelude::*;
+
+use crate::{open_image, DecodeOptions, HashContext, ImageHash, ImageHashError, ImageHasher};
+
+/// A single result of a batch, consisting of the path and its hash.
+pub type BatchItem = (PathBuf, Result<ImageHash, ImageHashError>);
+
+/// Options for [`hash_paths_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Whether the results are yielded in the order of the input paths.
+    /// Default is `true`
+    ordered: bool,
+
+    /// The maximum number of images that are decoded at the same time.
+    /// Default is the available parallelism of the system
+    concurrency: usize,
+
+    /// The options for decoding the images.
+    /// Default decodes the images as they are stored, without applying the EXIF orientation
+    decode: DecodeOptions,
+}
+
+impl BatchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the results are yielded in the order of the input paths. Unordered
+    /// results are yielded as soon as they are ready, so a single huge image does not
+    /// hold back the results of all following images.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Sets the maximum number of images that are decoded at the same time.
+    /// This bounds the memory that is used for decoded images. A value of `0` is treated as `1`.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets the options for decoding the images, for example to apply their EXIF orientation.
+    pub fn decode(mut self, decode: DecodeOptions) -> Self {
+        self.decode = decode;
+        self
+    }
+
+    pub fn is_ordered(&self) -> bool {
+        self.ordered
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub fn decode_options(&self) -> DecodeOptions {
+        self.decode
+    }
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            ordered: true,
+            concurrency: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            decode: DecodeOptions::STORED,
+        }
+    }
+}
+
+/// Decodes and hashes the images at the specified paths in parallel using the default
+/// [`BatchOptions`], meaning the results are yielded in the order of the input paths.
+///
+/// # Arguments
+/// * `hasher`: The hasher that is used for every image
+/// * `paths`: The paths of the images to hash
+///
+/// # Returns
+/// * An iterator yielding each path together with its [`ImageHash`] or the
+///   [`ImageHashError`] that occurred while loading or hashing the image
+pub fn hash_paths<H, I, P>(hasher: H, paths: I) -> BatchIter
+where
+    H: ImageHasher + Send + Sync + 'static,
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    hash_paths_with(hasher, paths, BatchOptions::default())
+}
+
+/// Decodes and hashes the images at the specified paths in parallel.
+///
+/// The work is started immediately on a dedicated thread pool with
+/// [`BatchOptions::concurrency`] threads. Dropping the returned iterator stops
+/// the remaining work as soon as possible.
+///
+/// # Arguments
+/// * `hasher`: The hasher that is used for every image
+/// * `paths`: The paths of the images to hash
+/// * `options`: The options controlling ordering and concurrency
+///
+/// # Returns
+/// * An iterator yielding each path together with its [`ImageHash`] or the
+///   [`ImageHashError`] that occurred while loading or hashing the image
+pub fn hash_paths_with<H, I, P>(hasher: H, paths: I, options: BatchOptions) -> BatchIter
+where
+    H: ImageHasher + Send + Sync + 'static,
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let paths = paths.into_iter().map(Into::into).collect::<Vec<PathBuf>>();
+
+    // the channel is bounded, so the workers pause if the consumer falls behind
+    let (sender, receiver) = sync_channel(options.concurrency);
+
+    let work = move || {
+        // an error while sending means the iterator was dropped, so we stop early
+        let _ = paths.into_par_iter().enumerate().try_for_each_init(
+            HashContext::new,
+            |ctx, (index, path)| {
+                let hash = open_image(&path, options.decode)
+                    .and_then(|img| hasher.hash_from_img_with(ctx, &img));
+                sender.send((index, (path, hash)))
+            },
+        );
+    };
+
+    // if no dedicated pool can be created, we fall back to rayon's global pool
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(options.concurrency)
+        .build()
+    {
+        Ok(pool) => pool.spawn(work),
+        Err(_) => rayon::spawn(work),
+    }
+
+    BatchIter {
+        receiver,
+        ordered: options.ordered,
+        next: 0,
+        pending: BTreeMap::new(),
+    }
+}
+
+/// The iterator returned by [`hash_paths`] and [`hash_paths_with`].
+#[derive(Debug)]
+pub struct BatchIter {
+    /// The receiving end for the results of the workers
+    receiver: Receiver<(usize, BatchItem)>,
+
+    /// Whether the results are yielded in the order of the input paths
+    ordered: bool,
+
+    /// The index of the next result to yield in ordered mode
+    next: usize,
+
+    /// Results that arrived before their turn in ordered mode
+    pending: BTreeMap<usize, BatchItem>,
+}
+
+impl Iterator for BatchIter {
+    type Item = BatchItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.ordered {
+            return self.receiver.recv().ok().map(|(_, item)| item);
+        }
+
+        loop {
+            if let Some(item) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Some(item);
+            }
+
+            // once all workers are done the channel is closed and every result was yielded
+            let (index, item) = self.receiver.recv().ok()?;
+            self.pending.insert(index, item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::average::AverageHasher;
+
+    const TEST_IMG: &str = "./data/img/test.png";
+    const TXT_FILE: &str = "./data/misc/test.txt";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+    const MISSING_FILE: &str = "./does/not/exist.png";
+
+    const HASH: &str = "ffffff0e00000301";
+
+    fn paths() -> Vec<&'static str> {
+        vec![
+            TXT_FILE,
+            TEST_IMG,
+            MISSING_FILE,
+            TEST_IMG,
+            TEST_IMG,
+            TXT_FILE,
+        ]
+    }
+
+    #[test]
+    fn test_hash_paths_is_ordered() {
+        // Act
+        let results = hash_paths(AverageHasher::default(), paths()).collect::<Vec<_>>();
+
+        // Assert
+        let result_paths = results.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>();
+        let expected = paths().into_iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(result_paths, expected);
+
+        for (path, hash) in results {
+            if path == std::path::Path::new(TEST_IMG) {
+                assert_eq!(hash.unwrap().encode().unwrap(), HASH);
+            } else {
+                assert!(hash.is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_paths_with_missing_file() {
+        // Act
+        let results = hash_paths(AverageHasher::default(), [MISSING_FILE]).collect::<Vec<_>>();
+
+        // Assert
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0].1, Err(ImageHashError::IoError { .. })));
+    }
+
+    #[test]
+    fn test_hash_paths_with_unordered_results() {
+        // Arrange
+        let options = BatchOptions::new().ordered(false).concurrency(3);
+
+        // Act
+        let results =
+            hash_paths_with(AverageHasher::default(), paths(), options).collect::<Vec<_>>();
+
+        // Assert
+        let mut result_paths = results.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>();
+        let mut expected = paths().into_iter().map(PathBuf::from).collect::<Vec<_>>();
+        result_paths.sort();
+        expected.sort();
+        assert_eq!(result_paths, expected);
+
+        let hashes = results.iter().filter(|(_, h)| h.is_ok()).count();
+        assert_eq!(hashes, 3);
+    }
+
+    #[test]
+    fn test_hash_paths_with_single_thread() {
+        // Arrange
+        let options = BatchOptions::new().concurrency(1);
+
+        // Act
+        let results = hash_paths_with(AverageHasher::default(), vec![TEST_IMG; 4], options)
+            .map(|(_, hash)| hash.unwrap().encode().unwrap())
+            .collect::<Vec<_>>();
+
+        // Assert
+        assert_eq!(results, vec![HASH; 4]);
+    }
+
+    #[test]
+    fn test_hash_paths_with_orientation() {
+        // Arrange
+        let options = BatchOptions::new().decode(DecodeOptions::new());
+
+        // Act
+        let results = hash_paths_with(AverageHasher::default(), [ROTATED_IMG], options)
+            .map(|(_, hash)| hash.unwrap().encode().unwrap())
+            .collect::<Vec<_>>();
+
+        // Assert
+        assert_eq!(results, vec![HASH]);
+    }
+
+    #[test]
+    fn test_hash_paths_with_empty_input() {
+        // Act
+        let results = hash_paths(AverageHasher::default(), Vec::<PathBuf>::new());
+
+        // Assert
+        assert_eq!(results.count(), 0);
+    }
+
+    #[test]
+    fn test_hash_paths_dropped_early() {
+        // Arrange
+        let options = BatchOptions::new().concurrency(1);
+        let mut results = hash_paths_with(AverageHasher::default(), vec![TEST_IMG; 16], options);
+
+        // Act
+        let first = results.next();
+        drop(results);
+
+        // Assert
+        assert!(first.unwrap().1.is_ok());
+    }
+
+    #[test]
+    fn test_options_with_zero_concurrency() {
+        // Act
+        let options = BatchOptions::new().concurrency(0);
+
+        // Assert
+        assert_eq!(options.max_concurrency(), 1);
+        assert!(options.is_ordered());
+    }
+}
@@ -0,0 +1,242 @@
+use imghash::ImageHash;
+
+/// A BK-tree over a set of hashes, which finds the hashes near a given one without comparing it
+/// to every other hash. This relies on the Hamming distance being a metric: the children of a
+/// node are keyed by their distance to it, and the triangle inequality rules out every subtree
+/// whose key is further from the distance of the query to the node than the search radius.
+///
+/// Hashes with a different shape than the first one can't be compared, so they are left out.
+pub struct BkTree<'a> {
+    hashes: &'a [ImageHash],
+
+    // the root is the first node, if there is one
+    nodes: Vec<Node>,
+}
+
+/// A hash of the tree with its children, keyed by their distance to it.
+struct Node {
+    index: usize,
+    children: Vec<(usize, usize)>,
+}
+
+impl<'a> BkTree<'a> {
+    /// Builds the tree of a set of hashes.
+    ///
+    /// # Arguments
+    /// * `hashes`: The hashes to search, which are referred to by their index
+    ///
+    /// # Returns
+    /// * The tree
+    pub fn new(hashes: &'a [ImageHash]) -> Self {
+        let mut tree = BkTree {
+            hashes,
+            nodes: Vec::with_capacity(hashes.len()),
+        };
+
+        for index in 0..hashes.len() {
+            tree.insert(index);
+        }
+
+        tree
+    }
+
+    fn insert(&mut self, index: usize) {
+        let node = Node {
+            index,
+            children: Vec::new(),
+        };
+
+        if self.nodes.is_empty() {
+            self.nodes.push(node);
+            return;
+        }
+
+        let mut current = 0;
+        loop {
+            let Ok(distance) = self.hashes[self.nodes[current].index].distance(&self.hashes[index])
+            else {
+                return;
+            };
+
+            match self.nodes[current]
+                .children
+                .iter()
+                .find(|&&(key, _)| key == distance)
+            {
+                Some(&(_, child)) => current = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes[current].children.push((distance, child));
+                    self.nodes.push(node);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Finds the hashes within a distance of a hash.
+    ///
+    /// # Arguments
+    /// * `hash`: The hash to search for
+    /// * `max_distance`: The largest distance in bits of the found hashes
+    ///
+    /// # Returns
+    /// * The indices of the found hashes together with their distances, in no particular order
+    pub fn within(&self, hash: &ImageHash, max_distance: usize) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        let mut pending = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+
+        while let Some(current) = pending.pop() {
+            let node = &self.nodes[current];
+            let Ok(distance) = self.hashes[node.index].distance(hash) else {
+                break;
+            };
+
+            if distance <= max_distance {
+                found.push((node.index, distance));
+            }
+
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|&&(key, _)| key.abs_diff(distance) <= max_distance)
+                    .map(|&(_, child)| child),
+            );
+        }
+
+        found
+    }
+
+    /// Finds the distance of a hash of the tree to its nearest other hash.
+    ///
+    /// # Arguments
+    /// * `index`: The index of the hash
+    ///
+    /// # Returns
+    /// * The smallest distance in bits to another hash, if there is another one of the same shape
+    pub fn nearest(&self, index: usize) -> Option<usize> {
+        let hash = &self.hashes[index];
+        let mut nearest = None;
+        let mut pending = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+
+        while let Some(current) = pending.pop() {
+            let node = &self.nodes[current];
+            let Ok(distance) = self.hashes[node.index].distance(hash) else {
+                break;
+            };
+
+            if node.index != index && nearest.is_none_or(|nearest| distance < nearest) {
+                nearest = Some(distance);
+            }
+
+            // the radius shrinks while better candidates are found
+            let radius = nearest.unwrap_or(usize::MAX);
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|&&(key, _)| key.abs_diff(distance) <= radius)
+                    .map(|&(_, child)| child),
+            );
+        }
+
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(hex: &str) -> ImageHash {
+        ImageHash::decode(hex, 8, 8).unwrap()
+    }
+
+    /// Hashes with bits set in a pattern that spreads them over a range of distances.
+    fn hashes() -> Vec<ImageHash> {
+        (0..200u64)
+            .map(|i| {
+                let bits = i.wrapping_mul(0x9e37_79b9_7f4a_7c15) & !(u64::MAX << (i % 64));
+                hash(&format!("{bits:016x}"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_within_matches_linear_scan() {
+        // Arrange
+        let hashes = hashes();
+        let tree = BkTree::new(&hashes);
+
+        for max_distance in [0, 3, 10, 64] {
+            for query in &hashes[..20] {
+                // Act
+                let mut found = tree.within(query, max_distance);
+                found.sort();
+
+                // Assert
+                let expected = hashes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, hash)| (index, hash.distance(query).unwrap()))
+                    .filter(|&(_, distance)| distance <= max_distance)
+                    .collect::<Vec<_>>();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_matches_linear_scan() {
+        // Arrange
+        let hashes = hashes();
+        let tree = BkTree::new(&hashes);
+
+        for index in 0..hashes.len() {
+            // Act
+            let nearest = tree.nearest(index);
+
+            // Assert
+            let expected = hashes
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, hash)| hash.distance(&hashes[index]).unwrap())
+                .min();
+            assert_eq!(nearest, expected);
+        }
+    }
+
+    #[test]
+    fn test_hashes_of_other_shapes_are_left_out() {
+        // Arrange
+        let hashes = [
+            hash("0000000000000000"),
+            ImageHash::decode("0000000000000000", 4, 16).unwrap(),
+            hash("0000000000000000"),
+        ];
+        let tree = BkTree::new(&hashes);
+
+        // Act & Assert
+        assert_eq!(tree.within(&hashes[0], 0).len(), 2);
+        assert!(tree.within(&hashes[1], 64).is_empty());
+        assert_eq!(tree.nearest(0), Some(0));
+        assert_eq!(tree.nearest(1), None);
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        // Act
+        let tree = BkTree::new(&[]);
+
+        // Assert
+        assert!(tree.within(&hash("0000000000000000"), 64).is_empty());
+    }
+}
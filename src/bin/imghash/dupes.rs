@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image::ImageFormat;
+use imghash::ImageHash;
+
+use crate::bktree::BkTree;
+
+/// Collects the images in a directory and its subdirectories, recognized by their file
+/// extension. Symbolic links to directories are not followed. Subdirectories and entries that
+/// could not be read are reported and skipped.
+///
+/// # Arguments
+/// * `dir`: The directory to search
+///
+/// # Returns
+/// * The paths of the images, sorted, and whether anything was skipped
+/// * An [`io::Error`] if the directory itself could not be read
+pub fn find_images(dir: &Path) -> io::Result<(Vec<PathBuf>, bool)> {
+    let mut images = Vec::new();
+    let mut failed = false;
+    let mut pending = vec![dir.to_path_buf()];
+
+    let mut report = |path: &Path, err: io::Error| {
+        eprintln!("imghash: {}: {err}", path.display());
+        failed = true;
+    };
+
+    while let Some(current) = pending.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(err) if current == dir => return Err(err),
+            Err(err) => {
+                report(&current, err);
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let (path, file_type) =
+                match entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))) {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        report(&current, err);
+                        continue;
+                    }
+                };
+
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if ImageFormat::from_path(&path).is_ok() {
+                images.push(path);
+            }
+        }
+    }
+
+    images.sort();
+    Ok((images, failed))
+}
+
+/// Groups hashes that are at most `threshold` bits apart. Hashes also end up in the same group
+/// if they are only connected through other hashes of the group.
+///
+/// # Arguments
+/// * `hashes`: The hashes to group
+/// * `threshold`: The largest distance of two hashes in bits to count as duplicates
+///
+/// # Returns
+/// * The indices of the hashes of every group with at least two members, ordered by their
+///   first index
+pub fn group(hashes: &[ImageHash], threshold: usize) -> Vec<Vec<usize>> {
+    // a union-find forest, where each hash points to another hash of its group
+    let mut parents = (0..hashes.len()).collect::<Vec<_>>();
+
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+
+        index
+    }
+
+    let tree = BkTree::new(hashes);
+    for (a, hash) in hashes.iter().enumerate() {
+        for (b, _) in tree.within(hash, threshold) {
+            let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+            parents[root_a.max(root_b)] = root_a.min(root_b);
+        }
+    }
+
+    // the roots are the smallest index of their group, so the groups are ordered by them
+    let mut groups = vec![Vec::new(); hashes.len()];
+    for index in 0..hashes.len() {
+        let root = root(&mut parents, index);
+        groups[root].push(index);
+    }
+
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(hex: &str) -> ImageHash {
+        ImageHash::decode(hex, 8, 8).unwrap()
+    }
+
+    #[test]
+    fn test_group_joins_hashes_within_threshold() {
+        // Arrange: the first and third hash differ in one bit, the last one is connected
+        // to the first one only through the third one
+        let hashes = [
+            hash("0000000000000000"),
+            hash("ffffffffffffffff"),
+            hash("0000000000000001"),
+            hash("0000000000000003"),
+            hash("fffffffffffffffe"),
+        ];
+
+        // Act
+        let groups = group(&hashes, 1);
+
+        // Assert
+        assert_eq!(groups, vec![vec![0, 2, 3], vec![1, 4]]);
+    }
+
+    #[test]
+    fn test_group_without_duplicates() {
+        // Arrange
+        let hashes = [hash("0000000000000000"), hash("00000000000000ff")];
+
+        // Act
+        let groups = group(&hashes, 7);
+
+        // Assert
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn test_find_images_recurses_into_subdirectories() {
+        // Act
+        let (images, failed) = find_images(Path::new("./data")).unwrap();
+
+        // Assert
+        assert!(!failed);
+        assert!(images.contains(&PathBuf::from("./data/img/test.png")));
+        assert!(images.iter().all(|path| path.extension().unwrap() == "png"));
+        assert!(images.is_sorted());
+    }
+}
@@ -0,0 +1,665 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use clap::Subcommand;
+use imghash::{DecodeOptions, HasherSpec, ImageHash, ImageHasher, Preset};
+use serde_json::{json, Value};
+
+use crate::bktree::BkTree;
+use crate::output::{hex, write_csv, write_json, Format};
+use crate::{dupes, hash_files, max_distance, HasherArgs, EXIT_DIFFERENT, EXIT_UNREADABLE};
+
+/// The version of the index file format, which is increased for incompatible changes.
+const VERSION: u64 = 1;
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommand {
+    /// Hash the images in a directory and its subdirectories and save them to an index
+    Build {
+        /// The directory to index
+        dir: PathBuf,
+
+        /// The file to save the index to
+        #[arg(long, short)]
+        output: PathBuf,
+
+        #[command(flatten)]
+        hasher: HasherArgs,
+    },
+
+    /// Rehash the images whose size or modification time changed since the index was saved,
+    /// add new images and remove deleted ones
+    Update {
+        /// The index to update
+        index: PathBuf,
+    },
+
+    /// Print the images of an index that are most similar to an image, closest first
+    Query {
+        /// The index to search
+        index: PathBuf,
+
+        /// The image to search for
+        image: PathBuf,
+
+        /// The largest distance in bits of the printed images.
+        /// Default is the threshold of the default preset for the algorithm
+        #[arg(long, short = 'd')]
+        max_distance: Option<usize>,
+
+        /// The largest number of printed images
+        #[arg(long, short = 'n', default_value_t = 10)]
+        top: usize,
+
+        /// The output format
+        #[arg(long, short, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Print the distribution of the distances of each image to its nearest neighbor and the
+    /// number of duplicates
+    Stats {
+        /// The index to analyze
+        index: PathBuf,
+
+        /// The largest distance in bits for two images to count as duplicates.
+        /// Default is the threshold of the default preset for the algorithm
+        #[arg(long, short)]
+        threshold: Option<usize>,
+
+        /// The output format
+        #[arg(long, short, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+}
+
+/// The hashes of the images in a directory, together with the hasher that produced them.
+#[derive(Debug)]
+struct Index {
+    /// The spec of the hasher
+    spec: HasherSpec,
+
+    /// Whether the EXIF orientation was applied before hashing
+    exif: bool,
+
+    /// The indexed directory
+    root: PathBuf,
+
+    /// The indexed images, sorted by path
+    entries: Vec<Entry>,
+}
+
+/// An indexed image.
+#[derive(Debug, Clone)]
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    modified: Duration,
+    hash: ImageHash,
+}
+
+/// How an index changed while rescanning its directory.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Changes {
+    added: usize,
+    changed: usize,
+    removed: usize,
+    unchanged: usize,
+}
+
+impl Index {
+    /// Loads an index saved by [`Index::save`], naming the file in errors.
+    fn load(path: &Path) -> io::Result<Index> {
+        let read = || -> io::Result<Index> {
+            let value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+            Index::from_json(&value).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "not an index of imghash")
+            })
+        };
+
+        read().map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+
+    /// Saves the index, replacing the file only once it was written completely.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        // building the JSON first leaves nothing behind if the index can't be saved
+        let json = self.to_json()?;
+
+        let mut temporary = OsString::from(path);
+        temporary.push(".tmp");
+
+        let write = || -> io::Result<()> {
+            let mut out = BufWriter::new(File::create(&temporary)?);
+            serde_json::to_writer(&mut out, &json)?;
+            out.flush()
+        };
+
+        write()
+            .and_then(|_| fs::rename(&temporary, path))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&temporary);
+            })
+    }
+
+    fn to_json(&self) -> io::Result<Value> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| {
+                Ok(json!({
+                    "path": utf8(&entry.path)?,
+                    "size": entry.size,
+                    "modified": [entry.modified.as_secs(), entry.modified.subsec_nanos()],
+                    "hash": hex(&entry.hash),
+                }))
+            })
+            .collect::<io::Result<Value>>()?;
+
+        Ok(json!({
+            "version": VERSION,
+            "spec": self.spec.to_string(),
+            "exif": self.exif,
+            "root": utf8(&self.root)?,
+            "entries": entries,
+        }))
+    }
+
+    fn from_json(value: &Value) -> Option<Index> {
+        if value["version"].as_u64()? != VERSION {
+            return None;
+        }
+
+        let spec: HasherSpec = value["spec"].as_str()?.parse().ok()?;
+        let entries = value["entries"]
+            .as_array()?
+            .iter()
+            .map(|entry| {
+                let modified = entry["modified"].as_array()?;
+
+                Some(Entry {
+                    path: PathBuf::from(entry["path"].as_str()?),
+                    size: entry["size"].as_u64()?,
+                    modified: Duration::new(
+                        modified.first()?.as_u64()?,
+                        modified.get(1)?.as_u64()?.try_into().ok()?,
+                    ),
+                    hash: ImageHash::decode(entry["hash"].as_str()?, spec.width, spec.height)
+                        .ok()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Index {
+            spec,
+            exif: value["exif"].as_bool()?,
+            root: PathBuf::from(value["root"].as_str()?),
+            entries,
+        })
+    }
+
+    fn hasher(&self) -> io::Result<Box<dyn ImageHasher + Send + Sync>> {
+        self.spec
+            .build()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions::new().apply_orientation(self.exif)
+    }
+
+    /// Rescans the directory of the index, hashing only the images that are new or whose size
+    /// or modification time changed. Images that could not be read or hashed, or whose path
+    /// can't be saved, are reported and left out, as are subdirectories that could not be read.
+    ///
+    /// # Returns
+    /// * How the index changed, and whether some images could not be hashed
+    fn refresh(&mut self) -> io::Result<(Changes, bool)> {
+        let hasher = self.hasher()?;
+        let mut previous = self
+            .entries
+            .drain(..)
+            .map(|entry| (entry.path.clone(), entry))
+            .collect::<HashMap<_, _>>();
+
+        let mut changes = Changes::default();
+        let mut pending = Vec::new();
+        let mut stats = HashMap::new();
+        let (images, mut failed) = dupes::find_images(&self.root)?;
+
+        for path in images {
+            let (size, modified) = match utf8(&path).and_then(|_| stat(&path)) {
+                Ok(stat) => stat,
+                Err(err) => {
+                    eprintln!("imghash: {}: {err}", path.display());
+                    failed = true;
+                    continue;
+                }
+            };
+
+            match previous.remove(&path) {
+                Some(entry) if (entry.size, entry.modified) == (size, modified) => {
+                    changes.unchanged += 1;
+                    self.entries.push(entry);
+                    continue;
+                }
+                Some(_) => changes.changed += 1,
+                None => changes.added += 1,
+            }
+
+            stats.insert(path.clone(), (size, modified));
+            pending.push(path);
+        }
+
+        changes.removed = previous.len();
+
+        let (hashes, hash_failed) = hash_files(hasher, self.decode_options(), pending);
+        self.entries.extend(hashes.into_iter().map(|(path, hash)| {
+            let (size, modified) = stats[&path];
+            Entry {
+                path,
+                size,
+                modified,
+                hash,
+            }
+        }));
+        self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok((changes, failed || hash_failed))
+    }
+
+    /// The largest distance in bits for two hashes of the index to count as duplicates under
+    /// the default preset.
+    fn preset_threshold(&self, hasher: &dyn ImageHasher) -> usize {
+        let bits = self.spec.width as usize * self.spec.height as usize;
+        max_distance(hasher.threshold(Preset::Default), bits)
+    }
+}
+
+/// The path as a string, since the JSON of an index can only hold UTF-8 paths.
+fn utf8(path: &Path) -> io::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "path is not valid UTF-8"))
+}
+
+/// The size and modification time of a file.
+fn stat(path: &Path) -> io::Result<(u64, Duration)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Ok((metadata.len(), modified))
+}
+
+/// Runs an index command, returning its exit code.
+pub fn run(out: &mut impl Write, command: IndexCommand) -> io::Result<u8> {
+    match command {
+        IndexCommand::Build {
+            dir,
+            output,
+            hasher,
+        } => build(out, &dir, &output, &hasher),
+        IndexCommand::Update { index } => update(out, &index),
+        IndexCommand::Query {
+            index,
+            image,
+            max_distance,
+            top,
+            format,
+        } => query(out, &index, &image, max_distance, top, format),
+        IndexCommand::Stats {
+            index,
+            threshold,
+            format,
+        } => stats(out, &index, threshold, format),
+    }
+}
+
+fn build(out: &mut impl Write, dir: &Path, output: &Path, args: &HasherArgs) -> io::Result<u8> {
+    let (spec, _) = args.build();
+    let mut index = Index {
+        spec,
+        exif: args.exif,
+        root: fs::canonicalize(dir)?,
+        entries: Vec::new(),
+    };
+
+    if let Err(err) = utf8(&index.root) {
+        eprintln!("imghash: {}: {err}", index.root.display());
+        return Ok(EXIT_UNREADABLE);
+    }
+
+    let (_, failed) = index.refresh()?;
+    index.save(output)?;
+
+    writeln!(
+        out,
+        "indexed {} images of {}",
+        index.entries.len(),
+        index.root.display()
+    )?;
+
+    Ok(if failed { EXIT_UNREADABLE } else { 0 })
+}
+
+fn update(out: &mut impl Write, path: &Path) -> io::Result<u8> {
+    let mut index = Index::load(path)?;
+    let (changes, failed) = index.refresh()?;
+    index.save(path)?;
+
+    writeln!(
+        out,
+        "{} added, {} changed, {} removed, {} unchanged",
+        changes.added, changes.changed, changes.removed, changes.unchanged
+    )?;
+
+    Ok(if failed { EXIT_UNREADABLE } else { 0 })
+}
+
+fn query(
+    out: &mut impl Write,
+    path: &Path,
+    image: &Path,
+    max_distance: Option<usize>,
+    top: usize,
+    format: Format,
+) -> io::Result<u8> {
+    let index = Index::load(path)?;
+    let hasher = index.hasher()?;
+
+    let hash = match hasher.hash_from_path_with_options(image, index.decode_options()) {
+        Ok(hash) => hash,
+        Err(err) => {
+            crate::report(image, &err);
+            return Ok(EXIT_UNREADABLE);
+        }
+    };
+
+    let max_distance = max_distance.unwrap_or_else(|| index.preset_threshold(&*hasher));
+
+    let mut matches = index
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let distance = entry.hash.distance(&hash).ok()?;
+            (distance <= max_distance).then_some((distance, entry))
+        })
+        .collect::<Vec<_>>();
+
+    // sorting by path as well keeps the order of equally distant images stable
+    matches.sort_by(|(a, entry_a), (b, entry_b)| a.cmp(b).then(entry_a.path.cmp(&entry_b.path)));
+    matches.truncate(top);
+
+    let similarity = |entry: &Entry| entry.hash.similarity(&hash).unwrap_or(0.0);
+
+    match format {
+        Format::Text => {
+            for (distance, entry) in &matches {
+                writeln!(
+                    out,
+                    "{distance}  {}  {}",
+                    hex(&entry.hash),
+                    entry.path.display()
+                )?;
+            }
+        }
+        Format::Json => {
+            let matches = matches
+                .iter()
+                .map(|(distance, entry)| {
+                    json!({
+                        "path": entry.path.to_string_lossy(),
+                        "hash": hex(&entry.hash),
+                        "distance": distance,
+                        "similarity": similarity(entry),
+                    })
+                })
+                .collect();
+
+            write_json(out, &matches)?;
+        }
+        Format::Csv => {
+            write_csv(out, &["path", "hash", "distance", "similarity"])?;
+
+            for (distance, entry) in &matches {
+                write_csv(
+                    out,
+                    &[
+                        &entry.path.to_string_lossy(),
+                        &hex(&entry.hash),
+                        &distance.to_string(),
+                        &similarity(entry).to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+
+    // like grep, finding nothing is signaled through the exit code
+    Ok(if matches.is_empty() {
+        EXIT_DIFFERENT
+    } else {
+        0
+    })
+}
+
+fn stats(
+    out: &mut impl Write,
+    path: &Path,
+    threshold: Option<usize>,
+    format: Format,
+) -> io::Result<u8> {
+    let index = Index::load(path)?;
+    let threshold = match threshold {
+        Some(threshold) => threshold,
+        None => index.preset_threshold(&*index.hasher()?),
+    };
+
+    let hashes = index
+        .entries
+        .iter()
+        .map(|entry| entry.hash.clone())
+        .collect::<Vec<_>>();
+
+    let distinct = hashes.iter().map(hex).collect::<HashSet<_>>().len();
+    let groups = dupes::group(&hashes, threshold);
+    let duplicates = groups.iter().map(Vec::len).sum::<usize>();
+    let nearest = nearest_histogram(&hashes);
+
+    match format {
+        Format::Text => {
+            writeln!(out, "spec:        {}", index.spec)?;
+            writeln!(out, "images:      {}", hashes.len())?;
+            writeln!(out, "distinct:    {distinct}")?;
+            writeln!(
+                out,
+                "duplicates:  {duplicates} in {} groups within {threshold} bits",
+                groups.len()
+            )?;
+            writeln!(out, "distance to nearest image:")?;
+
+            for (distance, &count) in nearest.iter().enumerate() {
+                if count > 0 {
+                    writeln!(out, "  {distance:>4}  {count}")?;
+                }
+            }
+        }
+        Format::Json => {
+            let nearest = nearest
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+                .map(|(distance, count)| json!({ "distance": distance, "images": count }))
+                .collect::<Value>();
+
+            write_json(
+                out,
+                &json!({
+                    "spec": index.spec.to_string(),
+                    "images": hashes.len(),
+                    "distinct": distinct,
+                    "threshold": threshold,
+                    "duplicates": duplicates,
+                    "groups": groups.len(),
+                    "nearest": nearest,
+                }),
+            )?;
+        }
+        Format::Csv => {
+            write_csv(out, &["statistic", "value"])?;
+            write_csv(out, &["spec", &index.spec.to_string()])?;
+
+            let counts = [
+                ("images", hashes.len()),
+                ("distinct", distinct),
+                ("threshold", threshold),
+                ("duplicates", duplicates),
+                ("groups", groups.len()),
+            ];
+
+            for (statistic, value) in counts {
+                write_csv(out, &[statistic, &value.to_string()])?;
+            }
+
+            for (distance, &count) in nearest.iter().enumerate() {
+                if count > 0 {
+                    write_csv(out, &[&format!("nearest_{distance}"), &count.to_string()])?;
+                }
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+/// Counts the images by the distance in bits to their nearest other image.
+///
+/// # Arguments
+/// * `hashes`: The hashes of the images, all of the same shape
+///
+/// # Returns
+/// * The number of images for each distance, from `0` to the number of bits of the hashes
+fn nearest_histogram(hashes: &[ImageHash]) -> Vec<usize> {
+    let bits = hashes.first().map_or(0, |hash| {
+        let (rows, columns) = hash.shape();
+        rows * columns
+    });
+
+    let tree = BkTree::new(hashes);
+    let mut histogram = vec![0; bits + 1];
+    for distance in (0..hashes.len()).filter_map(|index| tree.nearest(index)) {
+        histogram[distance] += 1;
+    }
+
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(hex: &str) -> ImageHash {
+        ImageHash::decode(hex, 8, 8).unwrap()
+    }
+
+    #[test]
+    fn test_nearest_histogram() {
+        // Arrange
+        let hashes = [
+            hash("0000000000000000"),
+            hash("0000000000000001"),
+            hash("00000000000000ff"),
+        ];
+
+        // Act
+        let histogram = nearest_histogram(&hashes);
+
+        // Assert: the first two are one bit apart, the last one is seven bits from the second
+        assert_eq!(histogram.len(), 65);
+        assert_eq!(histogram[1], 2);
+        assert_eq!(histogram[7], 1);
+        assert_eq!(histogram.iter().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_nearest_histogram_of_single_image() {
+        // Act
+        let histogram = nearest_histogram(&[hash("0000000000000000")]);
+
+        // Assert
+        assert!(histogram.iter().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn test_index_round_trips_through_json() {
+        // Arrange
+        let index = Index {
+            spec: "dhash?size=16x8".parse().unwrap(),
+            exif: true,
+            root: PathBuf::from("/photos"),
+            entries: vec![Entry {
+                path: PathBuf::from("/photos/a.jpg"),
+                size: 1234,
+                modified: Duration::new(1_700_000_000, 123),
+                hash: ImageHash::decode("00ff00ff00ff00ff00ff00ff00ff00ff", 16, 8).unwrap(),
+            }],
+        };
+
+        // Act
+        let loaded = Index::from_json(&index.to_json().unwrap()).unwrap();
+
+        // Assert
+        assert_eq!(loaded.spec, index.spec);
+        assert!(loaded.exif);
+        assert_eq!(loaded.root, index.root);
+        assert_eq!(loaded.entries[0].path, index.entries[0].path);
+        assert_eq!(loaded.entries[0].size, 1234);
+        assert_eq!(loaded.entries[0].modified, index.entries[0].modified);
+        assert_eq!(loaded.entries[0].hash, index.entries[0].hash);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_index_with_non_utf8_path_is_not_saved() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // Arrange
+        let index = Index {
+            spec: "phash".parse().unwrap(),
+            exif: false,
+            root: PathBuf::from("/photos"),
+            entries: vec![Entry {
+                path: PathBuf::from(OsStr::from_bytes(b"/photos/bad\xff.jpg")),
+                size: 1234,
+                modified: Duration::ZERO,
+                hash: hash("0000000000000000"),
+            }],
+        };
+        let path = std::env::temp_dir().join("imghash-non-utf8.idx");
+
+        // Act
+        let result = index.save(&path);
+
+        // Assert: neither the index nor its temporary file are left behind
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(!path.exists());
+        assert!(!path.with_extension("idx.tmp").exists());
+    }
+
+    #[test]
+    fn test_index_from_other_json() {
+        // Act
+        let other = Index::from_json(&json!({ "version": 1 }));
+        let future = Index::from_json(&json!({ "version": 2, "spec": "phash" }));
+
+        // Assert
+        assert!(other.is_none());
+        assert!(future.is_none());
+    }
+}
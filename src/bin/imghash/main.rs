@@ -0,0 +1,416 @@
+//! The `imghash` command-line tool, built with the `cli` feature.
+
+mod bktree;
+mod dupes;
+mod index;
+mod output;
+
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use imghash::batch::{self, BatchOptions};
+use imghash::{
+    DecodeOptions, HashAlgorithm, HasherSpec, ImageHash, ImageHashError, ImageHasher, Preset,
+};
+use serde_json::{json, Value};
+
+use crate::index::IndexCommand;
+use crate::output::{hex, write_csv, write_json, Format};
+
+/// Exit code if compared hashes are further apart than the threshold, duplicates were found or
+/// a query found nothing.
+const EXIT_DIFFERENT: u8 = 1;
+
+/// Exit code if some of the images could not be read or hashed.
+const EXIT_UNREADABLE: u8 = 3;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success. The compared images are within the threshold or no duplicates were found
+  1  The compared images are further apart than the threshold, duplicates were found
+     or a query of an index found no image within the maximum distance
+  2  Invalid arguments
+  3  Some images could not be read or hashed";
+
+/// Perceptual image hashes, compatible with the Python imagehash package
+#[derive(Debug, Parser)]
+#[command(name = "imghash", version, after_help = EXIT_CODES)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the hashes of images as hexadecimal strings
+    Hash {
+        /// The images to hash
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        #[command(flatten)]
+        hasher: HasherArgs,
+
+        /// The output format
+        #[arg(long, short, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Print the number of bits in which the hashes of two images differ
+    Compare {
+        /// The first image
+        a: PathBuf,
+
+        /// The second image
+        b: PathBuf,
+
+        /// The largest distance in bits for the images to count as duplicates.
+        /// Default is the threshold of the default preset for the algorithm
+        #[arg(long, short)]
+        threshold: Option<usize>,
+
+        #[command(flatten)]
+        hasher: HasherArgs,
+
+        /// The output format
+        #[arg(long, short, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Find groups of duplicate images in a directory and its subdirectories
+    Dupes {
+        /// The directory to search
+        dir: PathBuf,
+
+        /// The largest distance in bits for two images to count as duplicates.
+        /// Default is the threshold of the default preset for the algorithm
+        #[arg(long, short)]
+        threshold: Option<usize>,
+
+        #[command(flatten)]
+        hasher: HasherArgs,
+
+        /// The output format
+        #[arg(long, short, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Save the hashes of a directory to an index file and search it
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+}
+
+/// The options for building the hasher, shared by all commands.
+#[derive(Debug, Args)]
+struct HasherArgs {
+    /// The hash algorithm: ahash, mhash, dhash or phash
+    #[arg(long, default_value = "phash")]
+    algo: HashAlgorithm,
+
+    /// The hash size, either N for a square hash or WxH
+    #[arg(long, default_value = "8")]
+    size: String,
+
+    /// The color space for grayscaling, like 601, 709, 2020 or srgb-linear
+    #[arg(long, default_value = "601")]
+    color: String,
+
+    /// A complete hasher spec like "phash?size=16&factor=8", replacing the options above
+    #[arg(long, conflicts_with_all = ["algo", "size", "color"])]
+    spec: Option<HasherSpec>,
+
+    /// Apply the EXIF orientation of the images before hashing them
+    #[arg(long)]
+    exif: bool,
+}
+
+impl HasherArgs {
+    /// Builds the hasher and returns it together with its spec, exiting with a usage error if
+    /// the options are invalid.
+    fn build(&self) -> (HasherSpec, Box<dyn ImageHasher + Send + Sync>) {
+        let spec = match self.spec {
+            Some(spec) => Ok(spec),
+            None => format!("{}?size={}&color={}", self.algo, self.size, self.color).parse(),
+        };
+
+        // parsing the spec doesn't catch every invalid parameter, building the hasher does
+        match spec.and_then(|spec| spec.build().map(|hasher| (spec, hasher))) {
+            Ok(built) => built,
+            Err(err) => Cli::command().error(ErrorKind::ValueValidation, err).exit(),
+        }
+    }
+
+    fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions::new().apply_orientation(self.exif)
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut out = BufWriter::new(io::stdout().lock());
+
+    let code = match cli.command {
+        Command::Hash {
+            files,
+            hasher,
+            format,
+        } => hash(&mut out, files, &hasher, format),
+        Command::Compare {
+            a,
+            b,
+            threshold,
+            hasher,
+            format,
+        } => compare(&mut out, &a, &b, threshold, &hasher, format),
+        Command::Dupes {
+            dir,
+            threshold,
+            hasher,
+            format,
+        } => dupes(&mut out, &dir, threshold, &hasher, format),
+        Command::Index { command } => index::run(&mut out, command),
+    };
+
+    match code.and_then(|code| out.flush().map(|_| code)) {
+        Ok(code) => ExitCode::from(code),
+        // the reader of the output went away, like `head` does, so there is nobody to tell
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("imghash: {err}");
+            ExitCode::from(EXIT_UNREADABLE)
+        }
+    }
+}
+
+fn hash(
+    out: &mut impl Write,
+    files: Vec<PathBuf>,
+    args: &HasherArgs,
+    format: Format,
+) -> io::Result<u8> {
+    let (_, hasher) = args.build();
+    let (hashes, failed) = hash_files(hasher, args.decode_options(), files);
+
+    match format {
+        Format::Text => {
+            for (path, hash) in &hashes {
+                writeln!(out, "{}  {}", hex(hash), path.display())?;
+            }
+        }
+        Format::Json => {
+            let hashes = hashes
+                .iter()
+                .map(|(path, hash)| json!({ "path": path.to_string_lossy(), "hash": hex(hash) }))
+                .collect();
+
+            write_json(out, &hashes)?;
+        }
+        Format::Csv => {
+            write_csv(out, &["path", "hash"])?;
+
+            for (path, hash) in &hashes {
+                write_csv(out, &[&path.to_string_lossy(), &hex(hash)])?;
+            }
+        }
+    }
+
+    Ok(if failed { EXIT_UNREADABLE } else { 0 })
+}
+
+fn compare(
+    out: &mut impl Write,
+    a: &Path,
+    b: &Path,
+    threshold: Option<usize>,
+    args: &HasherArgs,
+    format: Format,
+) -> io::Result<u8> {
+    let (_, hasher) = args.build();
+    let hash = |path: &Path| {
+        hasher
+            .hash_from_path_with_options(path, args.decode_options())
+            .inspect_err(|err| report(path, err))
+            .ok()
+    };
+
+    let (Some(hash_a), Some(hash_b)) = (hash(a), hash(b)) else {
+        return Ok(EXIT_UNREADABLE);
+    };
+
+    // both hashes come from the same hasher, so their shapes always match
+    let distance = hash_a.distance(&hash_b).unwrap_or(usize::MAX);
+    let similarity = hash_a.similarity(&hash_b).unwrap_or(0.0);
+    let threshold =
+        threshold.unwrap_or_else(|| max_distance(hasher.threshold(Preset::Default), bits(&hash_a)));
+
+    match format {
+        Format::Text => writeln!(out, "{distance}")?,
+        Format::Json => write_json(
+            out,
+            &json!({
+                "a": { "path": a.to_string_lossy(), "hash": hex(&hash_a) },
+                "b": { "path": b.to_string_lossy(), "hash": hex(&hash_b) },
+                "distance": distance,
+                "similarity": similarity,
+                "threshold": threshold,
+            }),
+        )?,
+        Format::Csv => {
+            write_csv(
+                out,
+                &["a", "b", "hash_a", "hash_b", "distance", "similarity"],
+            )?;
+            write_csv(
+                out,
+                &[
+                    &a.to_string_lossy(),
+                    &b.to_string_lossy(),
+                    &hex(&hash_a),
+                    &hex(&hash_b),
+                    &distance.to_string(),
+                    &similarity.to_string(),
+                ],
+            )?;
+        }
+    }
+
+    Ok(if distance <= threshold {
+        0
+    } else {
+        EXIT_DIFFERENT
+    })
+}
+
+fn dupes(
+    out: &mut impl Write,
+    dir: &Path,
+    threshold: Option<usize>,
+    args: &HasherArgs,
+    format: Format,
+) -> io::Result<u8> {
+    let (images, skipped) = match dupes::find_images(dir) {
+        Ok(found) => found,
+        Err(err) => {
+            eprintln!("imghash: {}: {err}", dir.display());
+            return Ok(EXIT_UNREADABLE);
+        }
+    };
+
+    let (_, hasher) = args.build();
+    let share = hasher.threshold(Preset::Default);
+    let (hashes, unreadable) = hash_files(hasher, args.decode_options(), images);
+    let failed = skipped || unreadable;
+    let threshold = threshold.unwrap_or_else(|| match hashes.first() {
+        Some((_, hash)) => max_distance(share, bits(hash)),
+        None => 0,
+    });
+
+    let groups = dupes::group(
+        &hashes
+            .iter()
+            .map(|(_, hash)| hash.clone())
+            .collect::<Vec<_>>(),
+        threshold,
+    );
+
+    match format {
+        Format::Text => {
+            for (number, group) in groups.iter().enumerate() {
+                if number > 0 {
+                    writeln!(out)?;
+                }
+
+                for &index in group {
+                    let (path, hash) = &hashes[index];
+                    writeln!(out, "{}  {}", hex(hash), path.display())?;
+                }
+            }
+        }
+        Format::Json => {
+            let groups = groups
+                .iter()
+                .map(|group| {
+                    group
+                        .iter()
+                        .map(|&index| {
+                            let (path, hash) = &hashes[index];
+                            json!({ "path": path.to_string_lossy(), "hash": hex(hash) })
+                        })
+                        .collect::<Value>()
+                })
+                .collect();
+
+            write_json(out, &groups)?;
+        }
+        Format::Csv => {
+            write_csv(out, &["group", "path", "hash"])?;
+
+            for (number, group) in groups.iter().enumerate() {
+                for &index in group {
+                    let (path, hash) = &hashes[index];
+                    write_csv(
+                        out,
+                        &[
+                            &(number + 1).to_string(),
+                            &path.to_string_lossy(),
+                            &hex(hash),
+                        ],
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(if failed {
+        EXIT_UNREADABLE
+    } else if !groups.is_empty() {
+        EXIT_DIFFERENT
+    } else {
+        0
+    })
+}
+
+/// Hashes the images in parallel, in the order of their paths. The images that could not be
+/// hashed are reported on stderr and left out, which is signaled by the returned flag.
+fn hash_files(
+    hasher: Box<dyn ImageHasher + Send + Sync>,
+    decode: DecodeOptions,
+    paths: Vec<PathBuf>,
+) -> (Vec<(PathBuf, ImageHash)>, bool) {
+    let options = BatchOptions::new().decode(decode);
+    let mut failed = false;
+
+    let hashes = batch::hash_paths_with(hasher, paths, options)
+        .filter_map(|(path, hash)| {
+            hash.inspect_err(|err| {
+                report(&path, err);
+                failed = true;
+            })
+            .ok()
+            .map(|hash| (path, hash))
+        })
+        .collect();
+
+    (hashes, failed)
+}
+
+/// The largest distance in bits between two hashes for a threshold given as a share of bits,
+/// like the ones of [`Preset`].
+fn max_distance(share: f64, bits: usize) -> usize {
+    (share * bits as f64).floor() as usize
+}
+
+/// The number of bits of a hash.
+fn bits(hash: &ImageHash) -> usize {
+    let (rows, columns) = hash.shape();
+    rows * columns
+}
+
+fn report(path: &Path, err: &ImageHashError) {
+    eprintln!("imghash: {}: {err}", path.display());
+}
@@ -0,0 +1,95 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use imghash::ImageHash;
+use serde_json::Value;
+
+/// The format of the results printed to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human readable lines
+    Text,
+
+    /// A single JSON document
+    Json,
+
+    /// Comma separated values with a header row
+    Csv,
+}
+
+/// Encodes a hash as the hexadecimal string of the Python `imagehash` package.
+pub fn hex(hash: &ImageHash) -> String {
+    // hashers never produce empty hashes, which are the only ones that can't be encoded
+    hash.encode().unwrap_or_default()
+}
+
+/// Writes a JSON document followed by a line break.
+///
+/// # Arguments
+/// * `out`: The writer to write to
+/// * `value`: The JSON document
+pub fn write_json(out: &mut impl Write, value: &Value) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)
+}
+
+/// Writes a row of comma separated values, quoting the fields where necessary.
+///
+/// # Arguments
+/// * `out`: The writer to write to
+/// * `fields`: The fields of the row
+pub fn write_csv(out: &mut impl Write, fields: &[&str]) -> io::Result<()> {
+    let row = fields
+        .iter()
+        .map(|field| quote_csv(field))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    writeln!(out, "{row}")
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break, doubling its quotes.
+fn quote_csv(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv_quotes_fields_where_necessary() {
+        // Arrange
+        let mut out = Vec::new();
+
+        // Act
+        write_csv(
+            &mut out,
+            &["plain.png", "a,b.png", "say \"hi\".png", "two\nlines"],
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "plain.png,\"a,b.png\",\"say \"\"hi\"\".png\",\"two\nlines\"\n"
+        );
+    }
+
+    #[test]
+    fn test_write_json_ends_with_line_break() {
+        // Arrange
+        let mut out = Vec::new();
+
+        // Act
+        write_json(&mut out, &serde_json::json!({ "distance": 3 })).unwrap();
+
+        // Assert
+        assert_eq!(String::from_utf8(out).unwrap(), "{\n  \"distance\": 3\n}\n");
+    }
+}
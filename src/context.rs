@@ -0,0 +1,143 @@
+use alloc::vec::Vec;
+
+use crate::imageops::ConvertBuffers;
+#[cfg(feature = "image")]
+use crate::multi::MultiBuffers;
+
+/// A reusable workspace for hashing many images in a row.
+///
+/// Every hash computation needs a couple of intermediate buffers (the grayscaled image,
+/// the resized image, the DCT matrix, ...). Passing the same [`HashContext`] to
+/// [`ImageHasher::hash_from_img_with`](crate::ImageHasher::hash_from_img_with) keeps
+/// these buffers around, so after the first image no further allocations are necessary
+/// apart from the returned [`ImageHash`](crate::ImageHash) itself.
+///
+/// A context is not tied to a specific hasher and can be shared between hashers of
+/// different types and sizes. The buffers simply grow to the largest size seen so far.
+///
+/// ```no_run
+/// # #[cfg(feature = "image")]
+/// # fn main() {
+/// use std::path::Path;
+/// use imghash::{perceptual::PerceptualHasher, HashContext, ImageHasher};
+///
+/// let hasher = PerceptualHasher::default();
+/// let mut ctx = HashContext::new();
+///
+/// for path in ["frame-1.png", "frame-2.png"] {
+///     let img = image::open(Path::new(path)).unwrap();
+///     let hash = hasher.hash_from_img_with(&mut ctx, &img).unwrap();
+/// }
+/// # }
+/// # #[cfg(not(feature = "image"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HashContext {
+    /// The buffers for grayscaling and resizing
+    pub(crate) convert: ConvertBuffers,
+
+    /// A copy of the resized image used for the median selection
+    pub(crate) values: Vec<u8>,
+
+    /// The resized image as floats, used as DCT input
+    pub(crate) matrix: Vec<f64>,
+
+    /// The intermediate result of the column DCT pass
+    pub(crate) dct: Vec<f64>,
+
+    /// The low-frequency DCT coefficients
+    pub(crate) coefficients: Vec<f64>,
+
+    /// A sorted copy of the DCT coefficients used for the median
+    pub(crate) sorted: Vec<f64>,
+
+    /// The intermediate images of a [`MultiHasher`](crate::multi::MultiHasher)
+    #[cfg(feature = "image")]
+    pub(crate) multi: MultiBuffers,
+}
+
+impl HashContext {
+    /// Creates a new, empty [`HashContext`]. The buffers are allocated lazily
+    /// when the context is used for the first time.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use std::path::Path;
+
+    use image::ImageReader;
+
+    use super::*;
+    use crate::{
+        average::AverageHasher, difference::DifferenceHasher, median::MedianHasher,
+        perceptual::PerceptualHasher, ImageHasher,
+    };
+
+    const TEST_IMG: &str = "./data/img/test.png";
+
+    fn hashers() -> Vec<Box<dyn ImageHasher>> {
+        vec![
+            Box::new(AverageHasher::default()),
+            Box::new(MedianHasher::default()),
+            Box::new(DifferenceHasher::default()),
+            Box::new(PerceptualHasher::default()),
+        ]
+    }
+
+    #[test]
+    fn test_context_matches_fresh_hashes() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let mut ctx = HashContext::new();
+
+        for hasher in hashers() {
+            // Act
+            let with_ctx = hasher.hash_from_img_with(&mut ctx, &img).unwrap();
+            let without_ctx = hasher.hash_from_img(&img).unwrap();
+
+            // Assert
+            assert_eq!(with_ctx, without_ctx);
+        }
+    }
+
+    #[test]
+    fn test_context_does_not_reallocate() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let mut ctx = HashContext::new();
+        for hasher in hashers() {
+            hasher.hash_from_img_with(&mut ctx, &img).unwrap();
+        }
+
+        let buffers = |ctx: &HashContext| {
+            (
+                ctx.values.as_ptr(),
+                ctx.matrix.as_ptr(),
+                ctx.dct.as_ptr(),
+                ctx.coefficients.as_ptr(),
+                ctx.sorted.as_ptr(),
+            )
+        };
+        let before = buffers(&ctx);
+
+        // Act
+        for hasher in hashers() {
+            hasher.hash_from_img_with(&mut ctx, &img).unwrap();
+        }
+
+        // Assert
+        assert_eq!(buffers(&ctx), before);
+    }
+}
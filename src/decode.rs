@@ -0,0 +1,203 @@
+use std::io::{BufRead, Seek};
+use std::path::Path;
+
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+
+use crate::ImageHashError;
+
+/// Options for decoding images before they are hashed, used by
+/// [`ImageHasher::hash_from_path_with_options`](crate::ImageHasher::hash_from_path_with_options)
+/// and the other `_with_options` entry points.
+///
+/// By default the EXIF orientation of the image is applied, so a photo that is stored
+/// rotated hashes the same as a copy with the rotation baked in. The original entry points
+/// like [`ImageHasher::hash_from_path`](crate::ImageHasher::hash_from_path) keep decoding
+/// images as they are stored, so existing hashes do not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    /// Whether the EXIF orientation is applied to the decoded image.
+    /// Default is `true`
+    apply_orientation: bool,
+
+    /// The format the image is encoded in.
+    /// Default is `None`, which guesses the format from the leading bytes or the file extension
+    format: Option<ImageFormat>,
+}
+
+impl DecodeOptions {
+    /// The options used by the original entry points, which decode images as they are stored.
+    pub(crate) const STORED: DecodeOptions = DecodeOptions {
+        apply_orientation: false,
+        format: None,
+    };
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the EXIF orientation is applied, rotating and flipping the image the way
+    /// it is displayed. Images without an orientation tag are not affected.
+    pub fn apply_orientation(mut self, apply_orientation: bool) -> Self {
+        self.apply_orientation = apply_orientation;
+        self
+    }
+
+    /// Sets the format the image is encoded in, instead of guessing it.
+    pub fn format(mut self, format: ImageFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn applies_orientation(&self) -> bool {
+        self.apply_orientation
+    }
+
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        self.format
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            apply_orientation: true,
+            format: None,
+        }
+    }
+}
+
+/// Opens and decodes the image at the specified path.
+pub(crate) fn open_image(
+    path: &Path,
+    options: DecodeOptions,
+) -> Result<DynamicImage, ImageHashError> {
+    let mut reader = ImageReader::open(path).map_err(|e| ImageHashError::IoError {
+        source: e,
+        path: path.to_path_buf(),
+    })?;
+
+    if let Some(format) = options.format {
+        reader.set_format(format);
+    }
+
+    decode(reader, options)
+}
+
+/// Decodes an image from the given reader, guessing the format if none is specified.
+pub(crate) fn read_image<R: BufRead + Seek>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<DynamicImage, ImageHashError> {
+    let reader = match options.format {
+        Some(format) => ImageReader::with_format(reader, format),
+        None => ImageReader::new(reader)
+            .with_guessed_format()
+            .map_err(ImageHashError::ReadError)?,
+    };
+
+    if reader.format().is_none() {
+        return Err(ImageHashError::UnknownFormat);
+    }
+
+    decode(reader, options)
+}
+
+/// Decodes the image of a reader whose format is known, applying the orientation if requested.
+fn decode<R: BufRead + Seek>(
+    reader: ImageReader<R>,
+    options: DecodeOptions,
+) -> Result<DynamicImage, ImageHashError> {
+    if !options.apply_orientation {
+        return Ok(reader.decode()?);
+    }
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const TEST_IMG: &str = "./data/img/test.png";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+
+    #[test]
+    fn test_open_image_applies_orientation() {
+        // Arrange
+        let expected = open_image(Path::new(TEST_IMG), DecodeOptions::STORED).unwrap();
+
+        // Act
+        let img = open_image(Path::new(ROTATED_IMG), DecodeOptions::new()).unwrap();
+
+        // Assert
+        assert_eq!(img, expected);
+    }
+
+    #[test]
+    fn test_open_image_without_orientation() {
+        // Arrange
+        let upright = open_image(Path::new(TEST_IMG), DecodeOptions::STORED).unwrap();
+
+        // Act
+        let img = open_image(
+            Path::new(ROTATED_IMG),
+            DecodeOptions::new().apply_orientation(false),
+        )
+        .unwrap();
+
+        // Assert: the image is decoded as it is stored, rotated by 90 degrees
+        assert_eq!(img.width(), upright.height());
+        assert_eq!(img.height(), upright.width());
+    }
+
+    #[test]
+    fn test_read_image_applies_orientation() {
+        // Arrange
+        let bytes = std::fs::read(ROTATED_IMG).unwrap();
+        let expected = open_image(Path::new(TEST_IMG), DecodeOptions::STORED).unwrap();
+
+        // Act
+        let img = read_image(Cursor::new(&bytes), DecodeOptions::new()).unwrap();
+
+        // Assert
+        assert_eq!(img, expected);
+    }
+
+    #[test]
+    fn test_read_image_with_format() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+
+        // Act
+        let png = read_image(
+            Cursor::new(&bytes),
+            DecodeOptions::new().format(ImageFormat::Png),
+        );
+        let jpeg = read_image(
+            Cursor::new(&bytes),
+            DecodeOptions::new().format(ImageFormat::Jpeg),
+        );
+
+        // Assert
+        assert!(png.is_ok());
+        assert!(jpeg.is_err());
+    }
+
+    #[test]
+    fn test_decode_options_defaults() {
+        // Act
+        let options = DecodeOptions::default();
+
+        // Assert
+        assert!(options.applies_orientation());
+        assert_eq!(options.image_format(), None);
+    }
+}
@@ -1,71 +1,176 @@
-use crate::{imageops::convert, ColorSpace, ImageHash, ImageHashError, ImageHasher};
+use crate::imghash::{dimension, invalid_dimension};
+use crate::{
+    AlphaPolicy, ColorSpace, HashAlgorithm, HashContext, HasherSpec, ImageHash, ImageHashError,
+    ImageHasher, Normalization, ResizeTarget, ToneMapping,
+};
 
 #[derive(Debug, Clone)]
 pub struct DifferenceHasher {
     /// The target width of the matrix
-    width: u8,
+    width: u16,
 
     /// The target height of the matrix
-    height: u8,
+    height: u16,
 
     /// The color space which will be used for grayscaling.
     /// Default is Rec. 601
     color_space: ColorSpace,
+
+    /// The way transparent pixels are treated during grayscaling.
+    /// Default is to ignore the alpha channel
+    alpha: AlphaPolicy,
+
+    /// The tone mapping for high bit depth and high dynamic range images.
+    /// Default is no tone mapping
+    tone_mapping: ToneMapping,
+
+    /// The contrast normalization that is applied after grayscaling.
+    /// Default is no normalization
+    normalization: Normalization,
 }
 
 impl DifferenceHasher {
-    pub fn new(width: u8, height: u8, color_space: ColorSpace) -> Result<Self, ImageHashError> {
+    pub fn new(
+        width: impl TryInto<u16>,
+        height: impl TryInto<u16>,
+        color_space: ColorSpace,
+    ) -> Result<Self, ImageHashError> {
+        let (width, height) = (dimension(width, "width")?, dimension(height, "height")?);
+
         if width == 0 || height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        Ok(Self {
+        color_space.check()?;
+
+        let hasher = Self {
             width,
             height,
             color_space,
-        })
+            alpha: AlphaPolicy::default(),
+            tone_mapping: ToneMapping::default(),
+            normalization: Normalization::default(),
+        };
+
+        // the resized image must stay addressable, even on 16-bit and 32-bit targets
+        hasher.target().checked_len()?;
+
+        Ok(hasher)
+    }
+
+    /// Creates a builder for a [`DifferenceHasher`], starting from the default configuration.
+    ///
+    /// Unlike [`DifferenceHasher::new`], the builder reports which parameter is invalid.
+    pub fn builder() -> DifferenceHasherBuilder {
+        DifferenceHasherBuilder::default()
     }
 
-    pub fn width(&self) -> u8 {
+    pub fn width(&self) -> u16 {
         self.width
     }
 
-    pub fn height(&self) -> u8 {
+    pub fn height(&self) -> u16 {
         self.height
     }
 
     pub fn color_space(&self) -> ColorSpace {
         self.color_space
     }
+
+    pub fn alpha(&self) -> AlphaPolicy {
+        self.alpha
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// The grayscaled and resized image the hash is computed on.
+    fn target(&self) -> ResizeTarget {
+        ResizeTarget {
+            width: self.width as u32 + 1,
+            height: self.height as u32,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+        }
+    }
 }
 
 impl ImageHasher for DifferenceHasher {
+    #[cfg(feature = "image")]
     fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_img_with(&mut HashContext::new(), img)
+    }
+
+    fn resize_target(&self) -> Option<ResizeTarget> {
+        Some(self.target())
+    }
+
+    fn hash_from_resized(
+        &self,
+        _ctx: &mut HashContext,
+        pixels: &[u8],
+    ) -> Result<ImageHash, ImageHashError> {
         if self.width == 0 || self.height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        let width = self.width as u32;
-        let height = self.height as u32;
+        let target = self.target();
+        target.check(pixels)?;
 
-        let converted = convert(img, width + 1, height, self.color_space);
+        // we will compute the differences on each row of this matrix
+        ImageHash::from_bool_iter(
+            pixels
+                .chunks(target.width as usize)
+                .flat_map(|row| row.windows(2).map(|window| window[0] < window[1])),
+            self.width,
+            self.height,
+        )
+    }
 
-        // we will compute the differences on this matrix
-        let compare_matrix: Box<[Box<[u8]>]> = converted
-            .as_bytes()
-            .chunks((width + 1) as usize)
-            .map(|x| x.to_vec().into_boxed_slice())
-            .collect::<Vec<_>>()
-            .into_boxed_slice();
+    fn hash_from_resized_f32(
+        &self,
+        _ctx: &mut HashContext,
+        pixels: &[f32],
+    ) -> Result<ImageHash, ImageHashError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        let target = self.target();
+        target.check(pixels)?;
 
         ImageHash::from_bool_iter(
-            compare_matrix
-                .iter()
+            pixels
+                .chunks(target.width as usize)
                 .flat_map(|row| row.windows(2).map(|window| window[0] < window[1])),
             self.width,
             self.height,
         )
     }
+
+    fn name(&self) -> &str {
+        "dhash"
+    }
+
+    fn spec(&self) -> Option<HasherSpec> {
+        Some(HasherSpec {
+            algorithm: HashAlgorithm::Difference,
+            width: self.width,
+            height: self.height,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+            ..HasherSpec::new(HashAlgorithm::Difference)
+        })
+    }
 }
 
 impl Default for DifferenceHasher {
@@ -74,20 +179,122 @@ impl Default for DifferenceHasher {
             width: 8,
             height: 8,
             color_space: ColorSpace::REC601,
+            alpha: AlphaPolicy::Ignore,
+            tone_mapping: ToneMapping::None,
+            normalization: Normalization::None,
+        }
+    }
+}
+
+/// A builder for a [`DifferenceHasher`], created by [`DifferenceHasher::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct DifferenceHasherBuilder {
+    /// The configuration that is validated on [`DifferenceHasherBuilder::build`]
+    hasher: DifferenceHasher,
+
+    /// The first dimension that does not fit into `u16`, reported on [`DifferenceHasherBuilder::build`]
+    invalid: Option<&'static str>,
+}
+
+impl DifferenceHasherBuilder {
+    /// Sets the target width and height of the matrix.
+    pub fn size(self, width: impl TryInto<u16>, height: impl TryInto<u16>) -> Self {
+        self.width(width).height(height)
+    }
+
+    /// Sets the target width of the matrix.
+    pub fn width(mut self, width: impl TryInto<u16>) -> Self {
+        match width.try_into() {
+            Ok(width) => self.hasher.width = width,
+            Err(_) => self.invalid = self.invalid.or(Some("width")),
+        }
+        self
+    }
+
+    /// Sets the target height of the matrix.
+    pub fn height(mut self, height: impl TryInto<u16>) -> Self {
+        match height.try_into() {
+            Ok(height) => self.hasher.height = height,
+            Err(_) => self.invalid = self.invalid.or(Some("height")),
+        }
+        self
+    }
+
+    /// Sets the color space which will be used for grayscaling.
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.hasher.color_space = color_space;
+        self
+    }
+
+    /// Sets the way transparent pixels are treated during grayscaling.
+    pub fn alpha(mut self, alpha: AlphaPolicy) -> Self {
+        self.hasher.alpha = alpha;
+        self
+    }
+
+    /// Sets the tone mapping for high bit depth and high dynamic range images.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.hasher.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Sets the contrast normalization that is applied after grayscaling.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.hasher.normalization = normalization;
+        self
+    }
+
+    /// Validates the configuration and creates the [`DifferenceHasher`].
+    ///
+    /// # Returns
+    /// * The new [`DifferenceHasher`]
+    /// * An [`ImageHashError::InvalidParameter`] naming the first invalid parameter,
+    ///   including custom color space coefficients that are not finite and CLAHE
+    ///   parameters of zero
+    /// * An [`ImageHashError::DimensionOverflow`] if the resized image would not be addressable
+    pub fn build(&self) -> Result<DifferenceHasher, ImageHashError> {
+        if let Some(name) = self.invalid {
+            return Err(invalid_dimension(name));
+        }
+
+        if self.hasher.width == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "width",
+                reason: "must be greater than zero",
+            });
         }
+
+        if self.hasher.height == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "height",
+                reason: "must be greater than zero",
+            });
+        }
+
+        self.hasher.color_space.check()?;
+        self.hasher.normalization.check()?;
+        self.hasher.target().checked_len()?;
+
+        Ok(self.hasher.clone())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use std::path::Path;
 
-    use image::ImageReader;
+    use image::{DynamicImage, ImageBuffer, ImageReader, Luma};
 
     use super::*;
+    use crate::{DecodeOptions, PixelLayout, RawImage};
 
     const TEST_IMG: &str = "./data/img/test.png";
     const TXT_FILE: &str = "./data/misc/test.txt";
+    const GRAY_IMG: &str = "./data/img/gray-601.png";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+    const LOGO_IMG: &str = "./data/img/logo-alpha.png";
+    const LOGO_WHITE_IMG: &str = "./data/img/logo-white.png";
+    const LOGO_BLACK_IMG: &str = "./data/img/logo-black.png";
 
     const REC_601_HASH: &str = "cc99717ed9ea0627";
     const REC_709_HASH: &str = "c499717ed9ea0627";
@@ -114,6 +321,57 @@ mod tests {
         assert_eq!(hasher.color_space(), ColorSpace::REC601);
     }
 
+    #[test]
+    fn test_builder_with_defaults() {
+        // Act
+        let hasher = DifferenceHasher::builder().build().unwrap();
+
+        // Assert
+        let default = DifferenceHasher::default();
+        assert_eq!(hasher.width(), default.width());
+        assert_eq!(hasher.height(), default.height());
+        assert_eq!(hasher.color_space(), default.color_space());
+    }
+
+    #[test]
+    fn test_builder_with_custom_configuration() {
+        // Act
+        let hasher = DifferenceHasher::builder()
+            .size(16, 12)
+            .color_space(ColorSpace::REC709)
+            .build()
+            .unwrap();
+
+        // Assert
+        assert_eq!(hasher.width(), 16);
+        assert_eq!(hasher.height(), 12);
+        assert_eq!(hasher.color_space(), ColorSpace::REC709);
+    }
+
+    #[test]
+    fn test_builder_with_zero_width() {
+        // Act
+        let result = DifferenceHasher::builder().width(0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "width", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_zero_height() {
+        // Act
+        let result = DifferenceHasher::builder().size(8, 0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "height", .. })
+        ));
+    }
+
     #[test]
     fn test_difference_hash_from_img() {
         // Arrange
@@ -150,6 +408,26 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_709_HASH)
     }
 
+    #[test]
+    fn test_difference_hash_from_img_with_reused_context() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = DifferenceHasher::default();
+        let mut ctx = HashContext::new();
+
+        // Act
+        let first = hasher.hash_from_img_with(&mut ctx, &img);
+        let second = hasher.hash_from_img_with(&mut ctx, &img);
+
+        // Assert
+        assert_eq!(first.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_eq!(second.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
     #[test]
     fn test_difference_hash_from_path() {
         // Arrange
@@ -163,6 +441,84 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
     }
 
+    #[test]
+    fn test_difference_hash_from_bytes() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes(&bytes);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_difference_hash_from_reader_with_format() {
+        // Arrange
+        let file = std::fs::File::open(TEST_IMG).unwrap();
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_reader_with_format(std::io::BufReader::new(file), image::ImageFormat::Png);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_difference_hash_from_luma() {
+        // Arrange
+        let img = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_luma(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize,
+        );
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_difference_hash_from_raw_rgb() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let raw = RawImage::new(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize * 3,
+            PixelLayout::Rgb,
+        )
+        .unwrap();
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_raw(&raw);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
     #[test]
     fn test_difference_hash_from_img_with_non_default_size() {
         // Arrange
@@ -182,6 +538,102 @@ mod tests {
         assert_eq!(hash.shape(), (16, 16));
     }
 
+    #[test]
+    fn test_difference_hash_from_img_with_width_larger_than_u8() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = DifferenceHasher::new(256, 1, ColorSpace::REC601).unwrap();
+
+        // Act
+        let hash = hasher.hash_from_img(&img);
+
+        // Assert
+        assert_eq!(hash.unwrap().shape(), (1, 256));
+    }
+
+    #[test]
+    fn test_difference_hash_with_alpha_composited_over_background() {
+        for (background, flattened) in [([255; 3], LOGO_WHITE_IMG), ([0; 3], LOGO_BLACK_IMG)] {
+            // Arrange
+            let hasher = DifferenceHasher::builder()
+                .alpha(AlphaPolicy::CompositeOver(background))
+                .build()
+                .unwrap();
+
+            // Act
+            let hash = hasher.hash_from_path(Path::new(LOGO_IMG)).unwrap();
+
+            // Assert: the transparent logo hashes like the logo shown on the background
+            let expected = DifferenceHasher::default()
+                .hash_from_path(Path::new(flattened))
+                .unwrap();
+            assert_eq!(hash, expected);
+        }
+    }
+
+    #[test]
+    fn test_difference_hash_from_narrow_range_16_bit_image() {
+        // Arrange: the gray image squeezed into 256 of 65536 levels, which would collapse
+        // into two values when quantized to 8 bits
+        let gray = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_luma8();
+        let narrow = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([30000 + gray.get_pixel(x, y).0[0] as u16])
+        });
+        let full = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([gray.get_pixel(x, y).0[0] as u16 * 257])
+        });
+
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(narrow))
+            .unwrap();
+
+        // Assert
+        let expected = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(full))
+            .unwrap();
+        assert_eq!(hash, expected);
+    }
+
+    #[test]
+    fn test_difference_hash_from_path_with_orientation() {
+        // Arrange
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let oriented =
+            hasher.hash_from_path_with_options(Path::new(ROTATED_IMG), DecodeOptions::new());
+        let stored = hasher.hash_from_path(Path::new(ROTATED_IMG));
+
+        // Assert: the stored image is rotated, applying the orientation brings it upright
+        assert_eq!(oriented.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_ne!(stored.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
+    #[test]
+    fn test_difference_hash_from_bytes_with_orientation() {
+        // Arrange
+        let bytes = std::fs::read(ROTATED_IMG).unwrap();
+        let hasher = DifferenceHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes_with_options(&bytes, DecodeOptions::new());
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
     #[test]
     fn test_difference_hash_from_nonexisting_path() {
         // Arrange
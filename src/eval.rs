@@ -0,0 +1,858 @@
+//! Evaluating the robustness of hashers against synthetic image transformations.
+//!
+//! [`evaluate`] hashes a set of images and transformed copies of them, like recompressed,
+//! cropped or rotated versions, with any number of hashers. For each hasher it reports how far
+//! the copies drift from their original, how close distinct images come to each other, and how
+//! well a threshold can separate the two. This helps to choose an algorithm and a threshold
+//! from data instead of gut feeling.
+//!
+//! All distances are shares of differing bits in `[0, 1]`, so hashers of different sizes can be
+//! compared directly.
+//!
+//! ```no_run
+//! use imghash::{
+//!     average::AverageHasher,
+//!     eval::{evaluate, tiles, Transform},
+//!     perceptual::PerceptualHasher,
+//!     ImageHasher,
+//! };
+//!
+//! // distinct images can be cut from a single photo
+//! let images = tiles(&image::open("path/to/photo.png").unwrap(), 3, 3);
+//!
+//! let ahash = AverageHasher::default();
+//! let phash = PerceptualHasher::default();
+//! let hashers: [&dyn ImageHasher; 2] = [&ahash, &phash];
+//!
+//! for report in evaluate(&images, &hashers, &Transform::standard()).unwrap() {
+//!     println!(
+//!         "{}: AUC {:.3}, equal error rate {:.3} at {:.3}",
+//!         report.name, report.auc, report.equal_error.rate, report.equal_error.threshold
+//!     );
+//! }
+//! ```
+
+use std::fmt;
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{interpolate_bilinear, FilterType};
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+
+use crate::{HashContext, HasherSpec, ImageHash, ImageHashError, ImageHasher};
+
+/// A synthetic transformation of an image, as it happens when images are shared and edited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// Recompresses the image as a JPEG of the given quality between `1` and `100`
+    Jpeg { quality: u8 },
+
+    /// Scales the image by the given factor
+    Scale { factor: f32 },
+
+    /// Crops the given share of the width and height from each side
+    Crop { fraction: f32 },
+
+    /// Rotates the image clockwise around its center, filling the uncovered corners with black
+    Rotate { degrees: f32 },
+
+    /// Adds the given value to each channel
+    Brightness { delta: i32 },
+
+    /// Changes the contrast by the given percentage, negative values reduce it
+    Contrast { percent: f32 },
+
+    /// Applies a gaussian blur with the given standard deviation
+    Blur { sigma: f32 },
+
+    /// Adds deterministic uniform noise of up to the given amplitude to each channel
+    Noise { amplitude: u8 },
+
+    /// Blends a striped block over the bottom right corner, like a text watermark, with the
+    /// given opacity between `0` and `1`
+    Watermark { opacity: f32 },
+
+    /// Mirrors the image left to right
+    FlipHorizontal,
+
+    /// Mirrors the image top to bottom
+    FlipVertical,
+}
+
+impl Transform {
+    /// A set of transformations covering all kinds at mild to strong settings.
+    pub fn standard() -> Vec<Transform> {
+        vec![
+            Transform::Jpeg { quality: 90 },
+            Transform::Jpeg { quality: 70 },
+            Transform::Jpeg { quality: 50 },
+            Transform::Jpeg { quality: 30 },
+            Transform::Scale { factor: 0.5 },
+            Transform::Scale { factor: 0.25 },
+            Transform::Crop { fraction: 0.02 },
+            Transform::Crop { fraction: 0.05 },
+            Transform::Crop { fraction: 0.1 },
+            Transform::Rotate { degrees: 1.0 },
+            Transform::Rotate { degrees: 3.0 },
+            Transform::Brightness { delta: 20 },
+            Transform::Brightness { delta: -20 },
+            Transform::Contrast { percent: 20.0 },
+            Transform::Contrast { percent: -20.0 },
+            Transform::Blur { sigma: 1.0 },
+            Transform::Blur { sigma: 2.0 },
+            Transform::Noise { amplitude: 10 },
+            Transform::Watermark { opacity: 0.5 },
+            Transform::FlipHorizontal,
+            Transform::FlipVertical,
+        ]
+    }
+
+    /// Applies the transformation to an image.
+    ///
+    /// # Arguments
+    /// * `img`: The image to transform
+    ///
+    /// # Returns
+    /// * The transformed image, which is never smaller than 1 x 1 pixels
+    /// * An [`ImageHashError::ImageError`] if the image could not be recompressed
+    pub fn apply(&self, img: &DynamicImage) -> Result<DynamicImage, ImageHashError> {
+        let (width, height) = img.dimensions();
+
+        let transformed = match *self {
+            Transform::Jpeg { quality } => {
+                let mut bytes = Vec::new();
+                let encoder = JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100));
+                img.to_rgb8().write_with_encoder(encoder)?;
+
+                image::load(Cursor::new(bytes), ImageFormat::Jpeg)?
+            }
+            Transform::Scale { factor } => {
+                let scaled = |side: u32| ((side as f32 * factor).round() as u32).max(1);
+                img.resize_exact(scaled(width), scaled(height), FilterType::Triangle)
+            }
+            Transform::Crop { fraction } => {
+                let fraction = fraction.clamp(0.0, 0.49);
+                let (x, y) = (
+                    (width as f32 * fraction) as u32,
+                    (height as f32 * fraction) as u32,
+                );
+
+                img.crop_imm(x, y, (width - 2 * x).max(1), (height - 2 * y).max(1))
+            }
+            Transform::Rotate { degrees } => rotate(img, degrees),
+            Transform::Brightness { delta } => img.brighten(delta),
+            Transform::Contrast { percent } => img.adjust_contrast(percent),
+            Transform::Blur { sigma } => img.blur(sigma),
+            Transform::Noise { amplitude } => noise(img, amplitude),
+            Transform::Watermark { opacity } => watermark(img, opacity.clamp(0.0, 1.0)),
+            Transform::FlipHorizontal => img.fliph(),
+            Transform::FlipVertical => img.flipv(),
+        };
+
+        Ok(transformed)
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::Jpeg { quality } => write!(f, "jpeg-{}", quality),
+            Transform::Scale { factor } => write!(f, "scale-{}", factor),
+            Transform::Crop { fraction } => write!(f, "crop-{}", fraction),
+            Transform::Rotate { degrees } => write!(f, "rotate-{}", degrees),
+            Transform::Brightness { delta } => write!(f, "brightness{:+}", delta),
+            Transform::Contrast { percent } => write!(f, "contrast{:+}", percent),
+            Transform::Blur { sigma } => write!(f, "blur-{}", sigma),
+            Transform::Noise { amplitude } => write!(f, "noise-{}", amplitude),
+            Transform::Watermark { opacity } => write!(f, "watermark-{}", opacity),
+            Transform::FlipHorizontal => write!(f, "flip-horizontal"),
+            Transform::FlipVertical => write!(f, "flip-vertical"),
+        }
+    }
+}
+
+/// Rotates the image clockwise around its center, keeping its size.
+fn rotate(img: &DynamicImage, degrees: f32) -> DynamicImage {
+    let source = img.to_rgba8();
+    let (width, height) = source.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        // map the center of the target pixel back into the source image
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let sx = cos * dx + sin * dy + cx - 0.5;
+        let sy = -sin * dx + cos * dy + cy - 0.5;
+
+        interpolate_bilinear(&source, sx, sy).unwrap_or(Rgba([0, 0, 0, 255]))
+    }))
+}
+
+/// Adds uniform noise from a fixed seed, so the result is reproducible.
+fn noise(img: &DynamicImage, amplitude: u8) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    let range = 2 * amplitude as u32 + 1;
+    let mut state = 0x2545_f491_u32;
+
+    for pixel in rgba.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let offset = (state >> 16) % range;
+            *channel = (*channel as i32 + offset as i32 - amplitude as i32).clamp(0, 255) as u8;
+        }
+    }
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Blends white diagonal stripes over a block in the bottom right corner.
+fn watermark(img: &DynamicImage, opacity: f32) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    for y in height * 85 / 100..height * 95 / 100 {
+        for x in width * 60 / 100..width * 95 / 100 {
+            if (x + y) / 3 % 2 == 1 {
+                continue;
+            }
+
+            let pixel = rgba.get_pixel_mut(x, y);
+            for channel in &mut pixel.0[..3] {
+                *channel = (*channel as f32 * (1.0 - opacity) + 255.0 * opacity).round() as u8;
+            }
+        }
+    }
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Cuts an image into a grid of tiles, to get distinct images from a few fixtures.
+///
+/// # Arguments
+/// * `img`: The image to cut
+/// * `columns`: The number of tiles per row
+/// * `rows`: The number of tiles per column
+///
+/// # Returns
+/// * The tiles row by row, where remainders at the right and bottom edges are dropped
+pub fn tiles(img: &DynamicImage, columns: u32, rows: u32) -> Vec<DynamicImage> {
+    let (width, height) = img.dimensions();
+    let (tile_width, tile_height) = (width / columns.max(1), height / rows.max(1));
+
+    if tile_width == 0 || tile_height == 0 {
+        return Vec::new();
+    }
+
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            img.crop_imm(
+                column * tile_width,
+                row * tile_height,
+                tile_width,
+                tile_height,
+            )
+        })
+        .collect()
+}
+
+/// The sorted distances of a set of image pairs, as shares of differing bits.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Distribution {
+    // The distances in ascending order.
+    distances: Vec<f64>,
+}
+
+impl Distribution {
+    fn new(mut distances: Vec<f64>) -> Self {
+        distances.sort_by(f64::total_cmp);
+        Distribution { distances }
+    }
+
+    /// The distances in ascending order.
+    pub fn distances(&self) -> &[f64] {
+        &self.distances
+    }
+
+    /// The number of image pairs.
+    pub fn len(&self) -> usize {
+        self.distances.len()
+    }
+
+    /// Whether there are no image pairs, in which case the statistics below are `None`.
+    pub fn is_empty(&self) -> bool {
+        self.distances.is_empty()
+    }
+
+    /// The smallest distance, or `None` if there are no pairs.
+    pub fn min(&self) -> Option<f64> {
+        self.distances.first().copied()
+    }
+
+    /// The largest distance, or `None` if there are no pairs.
+    pub fn max(&self) -> Option<f64> {
+        self.distances.last().copied()
+    }
+
+    /// The mean distance, or `None` if there are no pairs.
+    pub fn mean(&self) -> Option<f64> {
+        if self.distances.is_empty() {
+            return None;
+        }
+
+        Some(self.distances.iter().sum::<f64>() / self.distances.len() as f64)
+    }
+
+    /// The distance below which the share `q` of the pairs lies, using the nearest rank, or
+    /// `None` if there are no pairs.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.distances.is_empty() {
+            return None;
+        }
+
+        let rank = (q.clamp(0.0, 1.0) * (self.distances.len() - 1) as f64).round() as usize;
+        Some(self.distances[rank])
+    }
+
+    /// The share of pairs whose distance is at most the threshold, or `0` if there are no pairs.
+    pub fn share_within(&self, threshold: f64) -> f64 {
+        if self.distances.is_empty() {
+            return 0.0;
+        }
+
+        let within = self.distances.partition_point(|&d| d <= threshold);
+        within as f64 / self.distances.len() as f64
+    }
+}
+
+/// A point on the receiver operating characteristic of a hasher, where pairs at most the
+/// threshold apart are considered near duplicates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RocPoint {
+    /// The largest share of differing bits of a near duplicate
+    pub threshold: f64,
+
+    /// The share of transformed copies that are matched with their original
+    pub true_positive_rate: f64,
+
+    /// The share of distinct image pairs that are matched with each other
+    pub false_positive_rate: f64,
+}
+
+/// The threshold at which as many copies are missed as distinct images are matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqualError {
+    /// The largest share of differing bits of a near duplicate
+    pub threshold: f64,
+
+    /// The average of the false negative and the false positive rate at the threshold
+    pub rate: f64,
+}
+
+/// The results of a single hasher, as returned by [`evaluate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The name of the hasher
+    pub name: String,
+
+    /// The spec of the hasher, if it is one of the hashers of this crate
+    pub spec: Option<HasherSpec>,
+
+    /// The number of bits of the hashes
+    pub bits: usize,
+
+    /// The distances between each image and its transformed copies
+    pub intra: Distribution,
+
+    /// The distances between distinct images, including their transformed copies
+    pub inter: Distribution,
+
+    /// The distances between each image and its copies, for each transformation
+    pub transforms: Vec<(Transform, Distribution)>,
+
+    /// The receiver operating characteristic, with one point per possible distance in
+    /// ascending order
+    pub roc: Vec<RocPoint>,
+
+    /// The area under the receiver operating characteristic, which is the probability that
+    /// a copy is closer to its original than two distinct images are to each other
+    pub auc: f64,
+
+    /// The threshold with the equal error rate
+    pub equal_error: EqualError,
+}
+
+/// Evaluates hashers on a set of images and transformed copies of them.
+///
+/// Each image is compared to each of its transformed copies, which should be near duplicates,
+/// and to every other image and its copies, which should not. The images should therefore be
+/// distinct, for example the [`tiles`] of a photo.
+///
+/// # Arguments
+/// * `images`: The distinct images to evaluate on
+/// * `hashers`: The hashers to evaluate
+/// * `transforms`: The transformations applied to each image
+///
+/// # Returns
+/// * A report for each hasher, in the order of the hashers
+/// * An [`ImageHashError::InvalidParameter`] if there are fewer than two images or no
+///   transformations
+/// * The first error of a transformation or a hasher
+pub fn evaluate(
+    images: &[DynamicImage],
+    hashers: &[&dyn ImageHasher],
+    transforms: &[Transform],
+) -> Result<Vec<Report>, ImageHashError> {
+    if images.len() < 2 {
+        return Err(ImageHashError::InvalidParameter {
+            name: "images",
+            reason: "at least two distinct images are needed",
+        });
+    }
+
+    if transforms.is_empty() {
+        return Err(ImageHashError::InvalidParameter {
+            name: "transforms",
+            reason: "at least one transformation is needed",
+        });
+    }
+
+    // the copies are shared by all hashers, so they are only generated once
+    let copies = images
+        .iter()
+        .map(|img| {
+            transforms
+                .iter()
+                .map(|transform| transform.apply(img))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut ctx = HashContext::new();
+
+    hashers
+        .iter()
+        .map(|hasher| {
+            let mut hash = |img: &DynamicImage| hasher.hash_from_img_with(&mut ctx, img);
+
+            let originals = images
+                .iter()
+                .map(&mut hash)
+                .collect::<Result<Vec<_>, _>>()?;
+            let copies = copies
+                .iter()
+                .map(|copies| copies.iter().map(&mut hash).collect::<Result<Vec<_>, _>>())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            report(*hasher, transforms, &originals, &copies)
+        })
+        .collect()
+}
+
+/// Compares the hashes of the originals and their copies of a single hasher.
+fn report(
+    hasher: &dyn ImageHasher,
+    transforms: &[Transform],
+    originals: &[ImageHash],
+    copies: &[Vec<ImageHash>],
+) -> Result<Report, ImageHashError> {
+    let (rows, columns) = originals[0].shape();
+    let bits = rows * columns;
+    let distance = |a: &ImageHash, b: &ImageHash| -> Result<f64, ImageHashError> {
+        Ok(a.distance(b)? as f64 / bits as f64)
+    };
+
+    let mut per_transform = vec![Vec::new(); transforms.len()];
+    let mut intra = Vec::new();
+    let mut inter = Vec::new();
+
+    for (i, original) in originals.iter().enumerate() {
+        for (t, copy) in copies[i].iter().enumerate() {
+            let d = distance(original, copy)?;
+            per_transform[t].push(d);
+            intra.push(d);
+        }
+
+        for (k, other) in originals.iter().enumerate() {
+            if k == i {
+                continue;
+            }
+
+            // each pair of originals is only counted once
+            if k > i {
+                inter.push(distance(original, other)?);
+            }
+
+            for copy in &copies[k] {
+                inter.push(distance(original, copy)?);
+            }
+        }
+    }
+
+    let intra = Distribution::new(intra);
+    let inter = Distribution::new(inter);
+
+    let roc = (0..=bits)
+        .map(|k| {
+            let threshold = k as f64 / bits as f64;
+            RocPoint {
+                threshold,
+                true_positive_rate: intra.share_within(threshold),
+                false_positive_rate: inter.share_within(threshold),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // the curve starts at the origin, below the smallest possible distance
+    let auc = roc
+        .iter()
+        .scan((0.0, 0.0), |previous, point| {
+            let (fpr, tpr) = *previous;
+            *previous = (point.false_positive_rate, point.true_positive_rate);
+            Some((point.false_positive_rate - fpr) * (point.true_positive_rate + tpr) / 2.0)
+        })
+        .sum();
+
+    let equal_error = roc
+        .iter()
+        .map(|point| {
+            let false_negative_rate = 1.0 - point.true_positive_rate;
+            let gap = (false_negative_rate - point.false_positive_rate).abs();
+            let rate = (false_negative_rate + point.false_positive_rate) / 2.0;
+            (
+                gap,
+                EqualError {
+                    threshold: point.threshold,
+                    rate,
+                },
+            )
+        })
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, equal_error)| equal_error)
+        .ok_or(ImageHashError::EmptyMatrix)?;
+
+    Ok(Report {
+        name: hasher.name().to_string(),
+        spec: hasher.spec(),
+        bits,
+        intra,
+        inter,
+        transforms: transforms
+            .iter()
+            .copied()
+            .zip(per_transform.into_iter().map(Distribution::new))
+            .collect(),
+        roc,
+        auc,
+        equal_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::imageops::FilterType;
+
+    use super::*;
+    use crate::{
+        average::AverageHasher, difference::DifferenceHasher, median::MedianHasher,
+        perceptual::PerceptualHasher, Preset,
+    };
+
+    const TEST_IMG: &str = "./data/img/test.png";
+
+    /// The test image at half its size, which keeps the evaluations in the tests fast.
+    fn test_img() -> DynamicImage {
+        let img = image::open(TEST_IMG).unwrap();
+        img.resize_exact(img.width() / 2, img.height() / 2, FilterType::Triangle)
+    }
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 2) as u8, (y * 2) as u8, 128, 255])
+        }))
+    }
+
+    #[test]
+    fn test_transform_dimensions() {
+        // Arrange
+        let img = gradient(100, 50);
+
+        // Act & Assert
+        let scaled = Transform::Scale { factor: 0.5 }.apply(&img).unwrap();
+        assert_eq!(scaled.dimensions(), (50, 25));
+
+        let cropped = Transform::Crop { fraction: 0.1 }.apply(&img).unwrap();
+        assert_eq!(cropped.dimensions(), (80, 40));
+        assert_eq!(cropped.get_pixel(0, 0), img.get_pixel(10, 5));
+
+        let rotated = Transform::Rotate { degrees: 3.0 }.apply(&img).unwrap();
+        assert_eq!(rotated.dimensions(), (100, 50));
+
+        let tiny = Transform::Scale { factor: 0.001 }.apply(&img).unwrap();
+        assert_eq!(tiny.dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn test_transform_rotate_by_zero_degrees() {
+        // Arrange
+        let img = gradient(40, 30);
+
+        // Act
+        let rotated = Transform::Rotate { degrees: 0.0 }.apply(&img).unwrap();
+
+        // Assert
+        assert_eq!(rotated.to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn test_transform_flips() {
+        // Arrange
+        let img = gradient(40, 30);
+
+        // Act
+        let horizontal = Transform::FlipHorizontal.apply(&img).unwrap();
+        let vertical = Transform::FlipVertical.apply(&img).unwrap();
+
+        // Assert
+        assert_eq!(horizontal, img.fliph());
+        assert_eq!(vertical, img.flipv());
+    }
+
+    #[test]
+    fn test_transform_jpeg() {
+        // Arrange
+        let img = gradient(64, 64);
+
+        // Act
+        let recompressed = Transform::Jpeg { quality: 50 }.apply(&img).unwrap();
+
+        // Assert
+        assert_eq!(recompressed.dimensions(), (64, 64));
+        let difference = img
+            .to_rgb8()
+            .pixels()
+            .zip(recompressed.to_rgb8().pixels())
+            .map(|(a, b)| a.0[0].abs_diff(b.0[0]))
+            .max()
+            .unwrap();
+        assert!(difference < 32);
+    }
+
+    #[test]
+    fn test_transform_noise_is_deterministic_and_bounded() {
+        // Arrange
+        let img = gradient(40, 30);
+        let transform = Transform::Noise { amplitude: 5 };
+
+        // Act
+        let first = transform.apply(&img).unwrap();
+        let second = transform.apply(&img).unwrap();
+
+        // Assert
+        assert_eq!(first, second);
+        assert_ne!(first.to_rgba8(), img.to_rgba8());
+        assert!(first
+            .to_rgba8()
+            .pixels()
+            .zip(img.to_rgba8().pixels())
+            .all(|(a, b)| a.0.iter().zip(b.0).all(|(&a, b)| a.abs_diff(b) <= 5)));
+    }
+
+    #[test]
+    fn test_transform_watermark_only_covers_corner() {
+        // Arrange
+        let img = gradient(100, 100);
+
+        // Act
+        let watermarked = Transform::Watermark { opacity: 1.0 }.apply(&img).unwrap();
+
+        // Assert
+        assert_eq!(watermarked.get_pixel(10, 10), img.get_pixel(10, 10));
+        assert_eq!(watermarked.get_pixel(60, 86), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_transform_display() {
+        // Act & Assert
+        assert_eq!(Transform::Jpeg { quality: 70 }.to_string(), "jpeg-70");
+        assert_eq!(Transform::Crop { fraction: 0.05 }.to_string(), "crop-0.05");
+        assert_eq!(
+            Transform::Brightness { delta: -20 }.to_string(),
+            "brightness-20"
+        );
+        assert_eq!(
+            Transform::Contrast { percent: 20.0 }.to_string(),
+            "contrast+20"
+        );
+        assert_eq!(Transform::FlipVertical.to_string(), "flip-vertical");
+    }
+
+    #[test]
+    fn test_tiles() {
+        // Arrange
+        let img = gradient(100, 50);
+
+        // Act
+        let tiles = tiles(&img, 3, 2);
+
+        // Assert
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|tile| tile.dimensions() == (33, 25)));
+        assert_eq!(tiles[4].get_pixel(0, 0), img.get_pixel(33, 25));
+    }
+
+    #[test]
+    fn test_tiles_of_too_small_image() {
+        // Act
+        let tiles = tiles(&gradient(2, 2), 3, 3);
+
+        // Assert
+        assert!(tiles.is_empty());
+    }
+
+    #[test]
+    fn test_distribution() {
+        // Arrange
+        let distribution = Distribution::new(vec![0.5, 0.0, 0.25, 1.0, 0.25]);
+
+        // Act & Assert
+        assert_eq!(distribution.distances(), &[0.0, 0.25, 0.25, 0.5, 1.0]);
+        assert_eq!(distribution.len(), 5);
+        assert_eq!(distribution.min(), Some(0.0));
+        assert_eq!(distribution.max(), Some(1.0));
+        assert_eq!(distribution.mean(), Some(0.4));
+        assert_eq!(distribution.quantile(0.5), Some(0.25));
+        assert_eq!(distribution.quantile(1.0), Some(1.0));
+        assert_eq!(distribution.share_within(0.25), 0.6);
+        assert_eq!(Distribution::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        // Arrange
+        let images = tiles(&test_img(), 3, 3);
+        let transforms = [
+            Transform::Jpeg { quality: 70 },
+            Transform::Crop { fraction: 0.05 },
+            Transform::FlipHorizontal,
+        ];
+        let ahash = AverageHasher::default();
+        let phash = PerceptualHasher::builder().size(16, 16).build().unwrap();
+        let hashers: [&dyn ImageHasher; 2] = [&ahash, &phash];
+
+        // Act
+        let reports = evaluate(&images, &hashers, &transforms).unwrap();
+
+        // Assert
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].name, "ahash");
+        assert_eq!(reports[1].spec, phash.spec());
+        assert_eq!(reports[0].bits, 64);
+        assert_eq!(reports[1].bits, 256);
+
+        for report in &reports {
+            assert_eq!(report.intra.len(), 9 * 3);
+            assert_eq!(report.inter.len(), 9 * 8 / 2 + 9 * 8 * 3);
+            assert_eq!(report.transforms.len(), 3);
+            assert_eq!(report.transforms[0].0, transforms[0]);
+            assert_eq!(report.transforms[0].1.len(), 9);
+            assert_eq!(report.roc.len(), report.bits + 1);
+
+            assert!(report.roc.windows(2).all(|w| {
+                w[0].true_positive_rate <= w[1].true_positive_rate
+                    && w[0].false_positive_rate <= w[1].false_positive_rate
+            }));
+            let last = report.roc.last().unwrap();
+            assert_eq!(
+                (last.true_positive_rate, last.false_positive_rate),
+                (1.0, 1.0)
+            );
+
+            // the flipped copies are missed, but distinct tiles are still told apart
+            assert!(report.auc > 0.8 && report.auc <= 1.0);
+            assert!(report.equal_error.rate < 0.4);
+            assert!(report.transforms[0].1.max().unwrap() < report.inter.min().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_evaluate_auc_of_perfect_separation() {
+        // Arrange
+        let images = tiles(&test_img(), 2, 2);
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let reports = evaluate(&images, &[&hasher], &[Transform::Jpeg { quality: 90 }]).unwrap();
+
+        // Assert
+        let report = &reports[0];
+        assert_eq!(report.auc, 1.0);
+        assert_eq!(report.equal_error.rate, 0.0);
+        assert!(report.equal_error.threshold >= report.intra.max().unwrap());
+        assert!(report.equal_error.threshold < report.inter.min().unwrap());
+    }
+
+    #[test]
+    fn test_evaluate_with_invalid_input() {
+        // Arrange
+        let images = tiles(&gradient(100, 100), 2, 1);
+        let hasher = AverageHasher::default();
+
+        // Act
+        let single = evaluate(&images[..1], &[&hasher], &Transform::standard());
+        let without_transforms = evaluate(&images, &[&hasher], &[]);
+
+        // Assert
+        assert!(matches!(
+            single,
+            Err(ImageHashError::InvalidParameter { name: "images", .. })
+        ));
+        assert!(matches!(
+            without_transforms,
+            Err(ImageHashError::InvalidParameter {
+                name: "transforms",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_default_presets_separate_distinct_tiles() {
+        // Arrange
+        let images = tiles(&test_img(), 3, 3);
+        let transforms = [
+            Transform::Jpeg { quality: 50 },
+            Transform::Scale { factor: 0.5 },
+            Transform::Brightness { delta: 20 },
+            Transform::Blur { sigma: 1.0 },
+        ];
+        let hashers: [Box<dyn ImageHasher>; 4] = [
+            Box::new(AverageHasher::default()),
+            Box::new(MedianHasher::default()),
+            Box::new(DifferenceHasher::default()),
+            Box::new(PerceptualHasher::default()),
+        ];
+        let hashers = hashers.iter().map(|h| h.as_ref()).collect::<Vec<_>>();
+
+        // Act
+        let reports = evaluate(&images, &hashers, &transforms).unwrap();
+
+        // Assert
+        for (hasher, report) in hashers.iter().zip(&reports) {
+            let threshold = hasher.threshold(Preset::Default);
+
+            assert!(
+                report.intra.max().unwrap() <= threshold,
+                "{}: {:?}",
+                report.name,
+                report.intra.max()
+            );
+            assert!(
+                report.inter.min().unwrap() > threshold,
+                "{}: {:?}",
+                report.name,
+                report.inter.min()
+            );
+        }
+    }
+}
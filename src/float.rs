@@ -0,0 +1,66 @@
+//! Float functions for `no_std` builds.
+//!
+//! Without `std` the inherent float methods like `f64::cos` are not available, so this
+//! trait provides them through `libm` instead. With `std` enabled the trait is not
+//! compiled at all and the inherent methods are used.
+
+pub(crate) trait Float {
+    fn cos(self) -> Self;
+    fn sin(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+}
+
+impl Float for f64 {
+    fn cos(self) -> Self {
+        libm::cos(self)
+    }
+
+    fn sin(self) -> Self {
+        libm::sin(self)
+    }
+
+    fn floor(self) -> Self {
+        libm::floor(self)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceil(self)
+    }
+
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        libm::pow(self, n)
+    }
+}
+
+impl Float for f32 {
+    fn cos(self) -> Self {
+        libm::cosf(self)
+    }
+
+    fn sin(self) -> Self {
+        libm::sinf(self)
+    }
+
+    fn floor(self) -> Self {
+        libm::floorf(self)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceilf(self)
+    }
+
+    fn round(self) -> Self {
+        libm::roundf(self)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        libm::powf(self, n)
+    }
+}
@@ -1,10 +1,587 @@
-use image::{imageops::FilterType, DynamicImage, GenericImageView, GrayImage};
+use alloc::vec::Vec;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, Default)]
+#[cfg(feature = "image")]
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Primitive};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::ImageHashError;
+
+/// The way a color pixel is reduced to a single luma value.
+///
+/// More color spaces may be added in minor versions, so matches on it need a wildcard arm.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
 pub enum ColorSpace {
+    /// Rec. 601 luma coefficients, as used by the `imagehash` package for Python
     #[default]
     REC601,
+
+    /// Rec. 709 luma coefficients
     REC709,
+
+    /// Rec. 2020 luma coefficients
+    REC2020,
+
+    /// Relative luminance using the Rec. 709 coefficients on linear light. The sRGB gamma is
+    /// decoded before weighting the channels and encoded again afterwards
+    SRGBLinear,
+
+    /// Only the red channel
+    Red,
+
+    /// Only the green channel
+    Green,
+
+    /// Only the blue channel
+    Blue,
+
+    /// The L* channel of CIELAB (D65), scaled from 0 - 100 to 0 - 255
+    Lightness,
+
+    /// Custom coefficients for the red, green and blue channel. The weighted sum is
+    /// clamped to 0 - 255
+    Custom([f64; 3]),
+}
+
+impl ColorSpace {
+    /// Returns an error if the coefficients of a [`ColorSpace::Custom`] are not finite.
+    pub(crate) fn check(&self) -> Result<(), ImageHashError> {
+        match self {
+            ColorSpace::Custom(coefficients) if !coefficients.iter().all(|c| c.is_finite()) => {
+                Err(ImageHashError::InvalidParameter {
+                    name: "color_space",
+                    reason: "custom coefficients must be finite",
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// A key for comparing and hashing, which compares custom coefficients by their bits.
+    fn key(&self) -> (u8, [u64; 3]) {
+        let index = match self {
+            ColorSpace::REC601 => 0,
+            ColorSpace::REC709 => 1,
+            ColorSpace::REC2020 => 2,
+            ColorSpace::SRGBLinear => 3,
+            ColorSpace::Red => 4,
+            ColorSpace::Green => 5,
+            ColorSpace::Blue => 6,
+            ColorSpace::Lightness => 7,
+            ColorSpace::Custom(_) => 8,
+        };
+
+        match self {
+            ColorSpace::Custom(coefficients) => (index, coefficients.map(f64::to_bits)),
+            _ => (index, [0; 3]),
+        }
+    }
+}
+
+impl PartialEq for ColorSpace {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ColorSpace {}
+
+impl core::hash::Hash for ColorSpace {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// How the alpha channel of transparent images is treated during grayscaling.
+///
+/// Transparent pixels often hold arbitrary color data that is invisible when the image is
+/// displayed. Ignoring the alpha channel hashes that color data, while the other policies
+/// make the hash depend on what is actually visible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AlphaPolicy {
+    /// The alpha channel is discarded and only the color data is used
+    #[default]
+    Ignore,
+
+    /// The image is blended over an opaque background color, given as `[r, g, b]`.
+    /// For example `[255, 255, 255]` matches a logo that is shown on a white page
+    CompositeOver([u8; 3]),
+}
+
+/// How the luma of high bit depth and high dynamic range images is mapped before hashing.
+///
+/// Images with more than 8 bits per channel are grayscaled and resized without quantizing
+/// them to 8 bits first. Floating point images can hold values brighter than white, which
+/// the tone mapping brings back into range. It has no effect on 8-bit images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ToneMapping {
+    /// The luma is used as it is, values brighter than white are kept
+    #[default]
+    None,
+
+    /// Values are clamped to the range from black to white, losing all highlight details
+    Clamp,
+
+    /// The Reinhard operator `l / (1 + l)`, which smoothly compresses the highlights
+    Reinhard,
+}
+
+#[cfg(feature = "image")]
+impl ToneMapping {
+    /// Maps a luma value, where `1.0` is white.
+    fn apply(self, luma: f32) -> f32 {
+        match self {
+            ToneMapping::None => luma,
+            ToneMapping::Clamp => luma.clamp(0.0, 1.0),
+            ToneMapping::Reinhard => luma.max(0.0) / (1.0 + luma.max(0.0)),
+        }
+    }
+}
+
+/// How the contrast of the grayscaled image is normalized before it is resized.
+///
+/// Low-contrast and exposure-shifted copies of an image move many luma values across the
+/// mean or median threshold of a hash. Normalizing the contrast first makes the hashes of
+/// such copies agree again, at the cost of no longer matching hashes without normalization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Normalization {
+    /// The luma is used as it is
+    #[default]
+    None,
+
+    /// The darkest value is stretched to black and the brightest value to white
+    MinMax,
+
+    /// Global histogram equalization, spreading the luma values evenly over the whole range
+    Equalize,
+
+    /// Contrast limited adaptive histogram equalization (CLAHE). The image is split into
+    /// `tiles` x `tiles` regions which are equalized separately and blended together. The
+    /// histogram bins of each region are clipped at `clip_limit` times their average height,
+    /// which limits the amplification of noise. Common values are 8 tiles and a clip limit of 2.
+    ///
+    /// Equalizing each region separately also evens out the brightness differences between
+    /// regions, which the average and median hashes are built on. Against exposure changes of
+    /// the whole image, [`Normalization::Equalize`] is therefore usually the more robust choice
+    Clahe {
+        /// The number of regions per side
+        tiles: u8,
+
+        /// The clip limit as a multiple of the average bin height
+        clip_limit: u8,
+    },
+}
+
+impl Normalization {
+    /// Returns an error if a [`Normalization::Clahe`] has no tiles or a clip limit of zero.
+    pub(crate) fn check(&self) -> Result<(), ImageHashError> {
+        match self {
+            Normalization::Clahe { tiles: 0, .. } => Err(ImageHashError::InvalidParameter {
+                name: "normalization",
+                reason: "the number of CLAHE tiles must be greater than zero",
+            }),
+            Normalization::Clahe { clip_limit: 0, .. } => Err(ImageHashError::InvalidParameter {
+                name: "normalization",
+                reason: "the CLAHE clip limit must be greater than zero",
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The size and color space of the grayscaled and resized image a hasher operates on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
+pub struct ResizeTarget {
+    /// The width of the resized image
+    pub width: u32,
+
+    /// The height of the resized image
+    pub height: u32,
+
+    /// The color space which is used for grayscaling
+    pub color_space: ColorSpace,
+
+    /// The way transparent pixels are treated during grayscaling
+    pub alpha: AlphaPolicy,
+
+    /// The tone mapping for high bit depth and high dynamic range images
+    pub tone_mapping: ToneMapping,
+
+    /// The contrast normalization that is applied after grayscaling
+    pub normalization: Normalization,
+}
+
+impl ResizeTarget {
+    /// The number of pixels of the resized image.
+    pub fn len(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Whether the resized image has no pixels at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of pixels of the resized image, checked for overflow.
+    ///
+    /// # Returns
+    /// * An [`ImageHashError::EmptyMatrix`] if one of the dimensions is zero
+    /// * An [`ImageHashError::DimensionOverflow`] if the number of pixels does not fit into `usize`
+    pub(crate) fn checked_len(&self) -> Result<usize, ImageHashError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .ok_or(ImageHashError::DimensionOverflow {
+                width: self.width as usize,
+                height: self.height as usize,
+            })
+    }
+
+    /// Returns an error if `pixels` does not match the size of the resized image.
+    pub(crate) fn check<T>(&self, pixels: &[T]) -> Result<(), ImageHashError> {
+        let len = self.checked_len()?;
+
+        if pixels.len() != len {
+            return Err(ImageHashError::MatrixSizeMismatch {
+                expected: len,
+                actual: pixels.len(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Reusable buffers for [`convert`], so that repeated conversions do not have to allocate.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConvertBuffers {
+    /// The full-size grayscale image
+    gray: Vec<u8>,
+
+    /// The normalized filter weights for the output pixel that is currently sampled
+    weights: Vec<f32>,
+
+    /// The vertically resampled image, before the horizontal pass
+    intermediate: Vec<f32>,
+
+    /// The lookup tables of the contrast normalization
+    lookup: Vec<f32>,
+
+    /// The grayscaled and resized output image
+    resized: Vec<u8>,
+
+    /// The full-size grayscale image of high bit depth images
+    #[cfg(feature = "image")]
+    gray_f32: Vec<f32>,
+
+    /// The grayscaled and resized output image of high bit depth images
+    #[cfg(feature = "image")]
+    resized_f32: Vec<f32>,
+}
+
+/// The layout of the pixels in a [`RawImage`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
+pub enum PixelLayout {
+    /// A single 8-bit luma channel, for example the Y plane of YUV420 or NV12
+    Luma,
+    /// Three 8-bit channels in red, green, blue order
+    Rgb,
+    /// Four 8-bit channels in red, green, blue, alpha order
+    Rgba,
+    /// Four 8-bit channels in blue, green, red, alpha order
+    Bgra,
+}
+
+impl PixelLayout {
+    /// The number of bytes per pixel.
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelLayout::Luma => 1,
+            PixelLayout::Rgb => 3,
+            PixelLayout::Rgba | PixelLayout::Bgra => 4,
+        }
+    }
+}
+
+/// A borrowed buffer of raw 8-bit pixels, for hashing images that were not
+/// decoded by the `image` crate.
+#[derive(Debug, Clone, Copy)]
+pub struct RawImage<'a> {
+    /// The pixel data, row by row
+    data: &'a [u8],
+
+    /// The width of the image in pixels
+    width: u32,
+
+    /// The height of the image in pixels
+    height: u32,
+
+    /// The number of bytes between the starts of two consecutive rows
+    stride: usize,
+
+    /// The layout of each pixel
+    layout: PixelLayout,
+}
+
+impl<'a> RawImage<'a> {
+    /// Creates a new [`RawImage`] from a pixel buffer.
+    ///
+    /// # Arguments
+    /// * `data`: The pixel data, row by row
+    /// * `width`: The width of the image in pixels
+    /// * `height`: The height of the image in pixels
+    /// * `stride`: The number of bytes between the starts of two consecutive rows,
+    ///   which may be larger than the row itself if rows are padded
+    /// * `layout`: The layout of each pixel
+    ///
+    /// # Returns
+    /// * The new [`RawImage`]
+    /// * An [`ImageHashError`] if the image is empty, the stride is smaller than a row or
+    ///   the buffer is too small for the specified dimensions
+    pub fn new(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        layout: PixelLayout,
+    ) -> Result<Self, ImageHashError> {
+        if width == 0 || height == 0 {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        let row = width as usize * layout.bytes_per_pixel();
+        if stride < row {
+            return Err(ImageHashError::InvalidStride { stride, row });
+        }
+
+        let expected = stride * (height as usize - 1) + row;
+        if data.len() < expected {
+            return Err(ImageHashError::BufferTooSmall {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        Ok(Self {
+            data,
+            width,
+            height,
+            stride,
+            layout,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn layout(&self) -> PixelLayout {
+        self.layout
+    }
+
+    /// Iterates over the rows of the image, without any padding.
+    fn rows(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        let row = self.width as usize * self.layout.bytes_per_pixel();
+
+        (0..self.height as usize).map(move |y| &self.data[y * self.stride..y * self.stride + row])
+    }
+
+    /// Converts the raw image into a [`DynamicImage`].
+    #[cfg(feature = "image")]
+    pub(crate) fn to_dynamic_image(self) -> DynamicImage {
+        let mut data = Vec::with_capacity(self.width as usize * self.height as usize * 4);
+        self.rows().for_each(|row| data.extend_from_slice(row));
+
+        // Infallible: the buffer was validated against the dimensions in `RawImage::new`
+        match self.layout {
+            PixelLayout::Luma => DynamicImage::ImageLuma8(
+                image::GrayImage::from_raw(self.width, self.height, data).unwrap(),
+            ),
+            PixelLayout::Rgb => DynamicImage::ImageRgb8(
+                image::RgbImage::from_raw(self.width, self.height, data).unwrap(),
+            ),
+            PixelLayout::Rgba => DynamicImage::ImageRgba8(
+                image::RgbaImage::from_raw(self.width, self.height, data).unwrap(),
+            ),
+            PixelLayout::Bgra => {
+                data.chunks_mut(4).for_each(|pixel| pixel.swap(0, 2));
+                DynamicImage::ImageRgba8(
+                    image::RgbaImage::from_raw(self.width, self.height, data).unwrap(),
+                )
+            }
+        }
+    }
+}
+
+/// The Rec. 601 coefficients.
+const REC601: [f64; 3] = [0.299, 0.587, 0.114];
+
+/// The Rec. 709 coefficients, which also apply to linear sRGB.
+const REC709: [f64; 3] = [0.2126, 0.7152, 0.0722];
+
+/// The Rec. 2020 coefficients.
+const REC2020: [f64; 3] = [0.2627, 0.6780, 0.0593];
+
+/// Decodes an sRGB value in the range 0 - 1 to linear light.
+fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear light in the range 0 - 1 to an sRGB value.
+fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts the relative luminance in the range 0 - 1 to the CIELAB lightness L* in
+/// the range 0 - 100.
+fn lightness(luminance: f64) -> f64 {
+    const EPSILON: f64 = 216.0 / 24389.0;
+    const KAPPA: f64 = 24389.0 / 27.0;
+
+    if luminance > EPSILON {
+        116.0 * luminance.powf(1.0 / 3.0) - 16.0
+    } else {
+        KAPPA * luminance
+    }
+}
+
+/// Computes the luma of pixels using a specific [`ColorSpace`] and [`AlphaPolicy`].
+struct Luma {
+    /// The color space to use for the conversion
+    color_space: ColorSpace,
+
+    /// The way the alpha channel is treated
+    alpha: AlphaPolicy,
+
+    /// The linear light of every 8-bit sRGB value, only filled for color spaces
+    /// that operate on linear light
+    linear: [f64; 256],
+}
+
+impl Luma {
+    fn new(color_space: ColorSpace, alpha: AlphaPolicy) -> Self {
+        let mut linear = [0.0; 256];
+
+        if matches!(color_space, ColorSpace::SRGBLinear | ColorSpace::Lightness) {
+            for (i, value) in linear.iter_mut().enumerate() {
+                *value = srgb_to_linear(i as f64 / 255.0);
+            }
+        }
+
+        Self {
+            color_space,
+            alpha,
+            linear,
+        }
+    }
+
+    /// Computes the luma of a single pixel with an alpha channel.
+    fn luma_with_alpha(&self, [r, g, b, a]: [u8; 4]) -> u8 {
+        let background = match self.alpha {
+            _ if a == u8::MAX => return self.luma([r, g, b]),
+            AlphaPolicy::Ignore => return self.luma([r, g, b]),
+            AlphaPolicy::CompositeOver(background) => background,
+        };
+
+        // blend in integers, rounding to the nearest value
+        let (a, inverse) = (a as u32, (u8::MAX - a) as u32);
+        let blend = |c: u8, bg: u8| ((c as u32 * a + bg as u32 * inverse + 127) / 255) as u8;
+
+        self.luma([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    }
+
+    /// Computes the luma of a single pixel.
+    fn luma(&self, [r, g, b]: [u8; 3]) -> u8 {
+        let weighted = |coefficients: [f64; 3]| {
+            coefficients[0] * r as f64 + coefficients[1] * g as f64 + coefficients[2] * b as f64
+        };
+
+        let luminance = || {
+            REC709[0] * self.linear[r as usize]
+                + REC709[1] * self.linear[g as usize]
+                + REC709[2] * self.linear[b as usize]
+        };
+
+        let luma = match self.color_space {
+            ColorSpace::REC601 => weighted(REC601),
+            ColorSpace::REC709 => weighted(REC709),
+            ColorSpace::REC2020 => weighted(REC2020),
+            ColorSpace::SRGBLinear => linear_to_srgb(luminance()) * 255.0,
+            ColorSpace::Red => return r,
+            ColorSpace::Green => return g,
+            ColorSpace::Blue => return b,
+            ColorSpace::Lightness => lightness(luminance()) * 2.55,
+            ColorSpace::Custom(coefficients) => weighted(coefficients),
+        };
+
+        // the cast saturates, which clamps custom coefficients to the valid range
+        luma.round() as u8
+    }
+
+    /// Computes the luma of a single pixel without quantization. The channels are in the
+    /// range 0 - 1, although high dynamic range images may exceed it.
+    ///
+    /// # Returns
+    /// * The luma, where `1.0` is white
+    #[cfg(feature = "image")]
+    fn luma_f32(&self, [r, g, b, a]: [f32; 4]) -> f32 {
+        let blend = |c: f32, bg: u8| c * a + bg as f32 / 255.0 * (1.0 - a);
+
+        let [r, g, b] = match self.alpha {
+            _ if a >= 1.0 => [r, g, b],
+            AlphaPolicy::Ignore => [r, g, b],
+            AlphaPolicy::CompositeOver([br, bg, bb]) => [blend(r, br), blend(g, bg), blend(b, bb)],
+        };
+
+        let [r, g, b] = [r as f64, g as f64, b as f64];
+        let weighted = |coefficients: [f64; 3]| {
+            coefficients[0] * r + coefficients[1] * g + coefficients[2] * b
+        };
+
+        let luminance = || {
+            REC709[0] * srgb_to_linear(r)
+                + REC709[1] * srgb_to_linear(g)
+                + REC709[2] * srgb_to_linear(b)
+        };
+
+        let luma = match self.color_space {
+            ColorSpace::REC601 => weighted(REC601),
+            ColorSpace::REC709 => weighted(REC709),
+            ColorSpace::REC2020 => weighted(REC2020),
+            ColorSpace::SRGBLinear => linear_to_srgb(luminance()),
+            ColorSpace::Red => r,
+            ColorSpace::Green => g,
+            ColorSpace::Blue => b,
+            ColorSpace::Lightness => lightness(luminance()) / 100.0,
+            ColorSpace::Custom(coefficients) => weighted(coefficients),
+        };
+
+        luma as f32
+    }
 }
 
 /// Converts a given [`DynamicImage`] to grayscale using the specified [`ColorSpace`].
@@ -12,28 +589,441 @@ pub enum ColorSpace {
 /// # Arguments
 /// * `img`: A reference to the image to convert
 /// * `color_space`: The color space to use for the conversion
+/// * `alpha`: The way the alpha channel is treated
+/// * `out`: The buffer the row-major luma values are written to
+#[cfg(feature = "image")]
+pub(crate) fn grayscale(
+    img: &DynamicImage,
+    color_space: ColorSpace,
+    alpha: AlphaPolicy,
+    out: &mut Vec<u8>,
+) {
+    let luma = Luma::new(color_space, alpha);
+
+    out.clear();
+    out.extend(
+        img.pixels()
+            .map(|(_, _, pixel)| luma.luma_with_alpha(pixel.0)),
+    );
+}
+
+/// Converts a given [`RawImage`] to grayscale using the specified [`ColorSpace`].
+/// Luma images are copied as they are, without any color conversion.
+///
+/// # Arguments
+/// * `img`: A reference to the raw image to convert
+/// * `color_space`: The color space to use for the conversion
+/// * `alpha`: The way the alpha channel of RGBA and BGRA images is treated
+/// * `out`: The buffer the row-major luma values are written to
+pub(crate) fn grayscale_raw(
+    img: &RawImage,
+    color_space: ColorSpace,
+    alpha: AlphaPolicy,
+    out: &mut Vec<u8>,
+) {
+    let luma = Luma::new(color_space, alpha);
+
+    out.clear();
+
+    for row in img.rows() {
+        match img.layout {
+            PixelLayout::Luma => out.extend_from_slice(row),
+            PixelLayout::Rgb => {
+                out.extend(row.chunks_exact(3).map(|p| luma.luma([p[0], p[1], p[2]])))
+            }
+            PixelLayout::Rgba => out.extend(
+                row.chunks_exact(4)
+                    .map(|p| luma.luma_with_alpha([p[0], p[1], p[2], p[3]])),
+            ),
+            PixelLayout::Bgra => out.extend(
+                row.chunks_exact(4)
+                    .map(|p| luma.luma_with_alpha([p[2], p[1], p[0], p[3]])),
+            ),
+        }
+    }
+}
+
+/// Whether an image has more than 8 bits per channel, which are lost when it is
+/// grayscaled to 8-bit luma.
+#[cfg(feature = "image")]
+pub(crate) fn is_high_bit_depth(img: &DynamicImage) -> bool {
+    !matches!(
+        img,
+        DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+    )
+}
+
+/// Converts the pixels of an image buffer to luma without quantization.
+#[cfg(feature = "image")]
+fn grayscale_buffer<P>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    luma: &Luma,
+    tone_mapping: ToneMapping,
+    out: &mut Vec<f32>,
+) where
+    P: Pixel,
+    P::Subpixel: Into<f32>,
+{
+    let max: f32 = P::Subpixel::DEFAULT_MAX_VALUE.into();
+
+    out.extend(buffer.pixels().map(|pixel| {
+        let rgba = pixel.to_rgba().0.map(|c| c.into() / max);
+        tone_mapping.apply(luma.luma_f32(rgba)) * 255.0
+    }));
+}
+
+/// Converts a given [`DynamicImage`] to grayscale without quantizing it to 8 bits, so the
+/// full precision of high bit depth and floating point images is kept.
+///
+/// # Arguments
+/// * `img`: A reference to the image to convert
+/// * `color_space`: The color space to use for the conversion
+/// * `alpha`: The way the alpha channel is treated
+/// * `tone_mapping`: The tone mapping that is applied to the luma
+/// * `out`: The buffer the row-major luma values are written to, where `255.0` is white
+#[cfg(feature = "image")]
+pub(crate) fn grayscale_f32(
+    img: &DynamicImage,
+    color_space: ColorSpace,
+    alpha: AlphaPolicy,
+    tone_mapping: ToneMapping,
+    out: &mut Vec<f32>,
+) {
+    let luma = Luma::new(color_space, alpha);
+
+    out.clear();
+    match img {
+        DynamicImage::ImageLuma8(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageLumaA8(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageRgb8(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageRgba8(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageLuma16(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageLumaA16(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageRgb16(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageRgba16(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageRgb32F(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        DynamicImage::ImageRgba32F(buffer) => grayscale_buffer(buffer, &luma, tone_mapping, out),
+        img => grayscale_buffer(&img.to_rgba32f(), &luma, tone_mapping, out),
+    }
+}
+
+/// Normalizes the contrast of a grayscale image in place.
+///
+/// # Arguments
+/// * `pixels`: The row-major luma values of the image, where `255` is white
+/// * `width`: The width of the image
+/// * `height`: The height of the image
+/// * `normalization`: The normalization to apply
+/// * `lookup`: The buffer for the lookup tables of the histogram equalization
+/// * `finish`: Converts the normalized values back to the pixel type
+pub(crate) fn normalize<T: Copy + Into<f32>>(
+    pixels: &mut [T],
+    (width, height): (u32, u32),
+    normalization: Normalization,
+    lookup: &mut Vec<f32>,
+    finish: impl Fn(f32) -> T,
+) {
+    // values are sorted into 256 histogram bins, anything beyond white ends up in the last one
+    let bin = |value: T| value.into().clamp(0.0, 255.0) as usize;
+
+    match normalization {
+        Normalization::None => {}
+        Normalization::MinMax => {
+            let (min, max) = pixels.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| {
+                (min.min(v.into()), max.max(v.into()))
+            });
+
+            if max > min {
+                let scale = 255.0 / (max - min);
+                for pixel in pixels.iter_mut() {
+                    *pixel = finish(((*pixel).into() - min) * scale);
+                }
+            }
+        }
+        Normalization::Equalize => {
+            let mut histogram = [0u32; 256];
+            for &pixel in pixels.iter() {
+                histogram[bin(pixel)] += 1;
+            }
+
+            // the darkest value maps to black, the brightest to white
+            let total = pixels.len() as u32;
+            let first = histogram
+                .iter()
+                .copied()
+                .find(|&count| count > 0)
+                .unwrap_or(0);
+            if total == first {
+                return;
+            }
+
+            lookup.clear();
+            lookup.extend(histogram.iter().scan(0u32, |cdf, &count| {
+                *cdf += count;
+                Some(cdf.saturating_sub(first) as f32 * 255.0 / (total - first) as f32)
+            }));
+
+            for pixel in pixels.iter_mut() {
+                *pixel = finish(lookup[bin(*pixel)]);
+            }
+        }
+        Normalization::Clahe { tiles, clip_limit } => {
+            let tiles_x = (tiles as u32).min(width).max(1);
+            let tiles_y = (tiles as u32).min(height).max(1);
+
+            lookup.clear();
+            lookup.resize((tiles_x * tiles_y) as usize * 256, 0.0);
+
+            // an equalization lookup table per tile, from its clipped histogram
+            for ty in 0..tiles_y {
+                let rows = ty * height / tiles_y..(ty + 1) * height / tiles_y;
+
+                for tx in 0..tiles_x {
+                    let columns = tx * width / tiles_x..(tx + 1) * width / tiles_x;
+
+                    let mut histogram = [0u32; 256];
+                    for y in rows.clone() {
+                        let row = y as usize * width as usize;
+                        for x in columns.clone() {
+                            histogram[bin(pixels[row + x as usize])] += 1;
+                        }
+                    }
+
+                    // a single tile of a full resolution photo overflows u32 with the clip limit
+                    let total = columns.len() as u64 * rows.len() as u64;
+                    let limit = (clip_limit as u64 * total / 256).clamp(1, u32::MAX as u64) as u32;
+
+                    // the excess above the limit is spread evenly over all bins
+                    let mut excess = 0;
+                    for count in histogram.iter_mut() {
+                        excess += count.saturating_sub(limit);
+                        *count = (*count).min(limit);
+                    }
+                    for (i, count) in histogram.iter_mut().enumerate() {
+                        *count += excess / 256 + u32::from((i as u32) < excess % 256);
+                    }
+
+                    let table = ((ty * tiles_x + tx) * 256) as usize;
+                    let mut cdf = 0;
+                    for (i, &count) in histogram.iter().enumerate() {
+                        cdf += count;
+                        lookup[table + i] = cdf as f32 * 255.0 / total.max(1) as f32;
+                    }
+                }
+            }
+
+            // every pixel blends the tables of the four closest tile centers
+            let neighbors = |position: u32, size: u32, tiles: u32| {
+                let offset = (position as f32 + 0.5) * tiles as f32 / size as f32 - 0.5;
+                let first = (offset.max(0.0).floor() as u32).min(tiles - 1);
+                let second = (first + 1).min(tiles - 1);
+
+                (first, second, (offset - first as f32).clamp(0.0, 1.0))
+            };
+
+            for y in 0..height {
+                let (top, bottom, wy) = neighbors(y, height, tiles_y);
+
+                for x in 0..width {
+                    let (left, right, wx) = neighbors(x, width, tiles_x);
+
+                    let index = y as usize * width as usize + x as usize;
+                    let value = bin(pixels[index]);
+                    let table =
+                        |tx: u32, ty: u32| lookup[((ty * tiles_x + tx) * 256) as usize + value];
+
+                    let upper = table(left, top) * (1.0 - wx) + table(right, top) * wx;
+                    let lower = table(left, bottom) * (1.0 - wx) + table(right, bottom) * wx;
+                    pixels[index] = finish(upper * (1.0 - wy) + lower * wy);
+                }
+            }
+        }
+    }
+}
+
+/// The sinc function, the ideal sampling filter.
+fn sinc(t: f32) -> f32 {
+    let a = t * core::f32::consts::PI;
+
+    if t == 0.0 {
+        1.0
+    } else {
+        a.sin() / a
+    }
+}
+
+/// The Lanczos kernel with a window of 3, a windowed sinc function.
+fn lanczos3(x: f32) -> f32 {
+    if x.abs() < 3.0 {
+        sinc(x) * sinc(x / 3.0)
+    } else {
+        0.0
+    }
+}
+
+/// Computes the normalized Lanczos3 weights for a single output pixel along one axis.
+///
+/// # Arguments
+/// * `out`: The index of the output pixel
+/// * `len`: The number of input pixels along the axis
+/// * `new_len`: The number of output pixels along the axis
+/// * `weights`: The buffer the weights are written to
 ///
 /// # Returns
-/// * The converted dynamic image
-fn grayscale(img: &DynamicImage, color_space: ColorSpace) -> DynamicImage {
-    let mut buffer = GrayImage::new(img.width(), img.height());
+/// * The index of the first input pixel the weights apply to
+fn filter_weights(out: u32, len: u32, new_len: u32, weights: &mut Vec<f32>) -> u32 {
+    let ratio = len as f32 / new_len as f32;
+    let sratio = if ratio < 1.0 { 1.0 } else { ratio };
+    let support = 3.0 * sratio;
 
-    let coefficients: [f64; 3] = match color_space {
-        ColorSpace::REC709 => [0.2126, 0.7152, 0.0722],
-        ColorSpace::REC601 => [0.299, 0.587, 0.114],
-    };
+    // find the point in the input corresponding to the centre of the output pixel,
+    // the relevant input pixels are then those in [left, right)
+    let input = (out as f32 + 0.5) * ratio;
+
+    let left = ((input - support).floor() as i64).clamp(0, len as i64 - 1) as u32;
+    let right = ((input + support).ceil() as i64).clamp(left as i64 + 1, len as i64) as u32;
+
+    // go back to the left boundary of the pixel, as the kernel treats the centre of a pixel as 0
+    let input = input - 0.5;
+
+    weights.clear();
+    weights.extend((left..right).map(|i| lanczos3((i as f32 - input) / sratio)));
+
+    let sum = weights.iter().sum::<f32>();
+    weights.iter_mut().for_each(|w| *w /= sum);
+
+    left
+}
+
+/// Resizes a row-major luma matrix using a Lanczos3 filter.
+///
+/// This mirrors `image::imageops::resize` with `FilterType::Lanczos3` step by step
+/// (a vertical pass into an `f32` intermediate followed by a horizontal pass),
+/// so the results are identical, but all buffers are provided by the caller.
+///
+/// # Arguments
+/// * `src`: The row-major luma values of the source image
+/// * `width`: The width of the source image
+/// * `height`: The height of the source image
+/// * `new_width`: The width of the resized image
+/// * `new_height`: The height of the resized image
+/// * `weights`: The buffer for the filter weights
+/// * `intermediate`: The buffer for the vertically resampled image
+/// * `out`: The buffer the row-major luma values of the resized image are written to
+pub(crate) fn resize(
+    src: &[u8],
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+    weights: &mut Vec<f32>,
+    intermediate: &mut Vec<f32>,
+    out: &mut Vec<u8>,
+) {
+    resample(
+        src,
+        (width, height),
+        (new_width, new_height),
+        weights,
+        intermediate,
+        out,
+        round,
+    );
+}
+
+/// Rounds a luma value to 8 bits, clamping it to the range from black to white.
+pub(crate) fn round(value: f32) -> u8 {
+    value.clamp(0.0, 255.0).round() as u8
+}
+
+/// Resizes a grayscale image of `f32` luma values using a Lanczos3 filter, like [`resize`]
+/// but without clamping or rounding the results.
+///
+/// # Arguments
+/// * `src`: The row-major luma values of the source image
+/// * `width`: The width of the source image
+/// * `height`: The height of the source image
+/// * `new_width`: The width of the resized image
+/// * `new_height`: The height of the resized image
+/// * `weights`: The buffer for the filter weights
+/// * `intermediate`: The buffer for the vertically resampled image
+/// * `out`: The buffer the row-major luma values of the resized image are written to
+#[cfg(feature = "image")]
+pub(crate) fn resize_f32(
+    src: &[f32],
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+    weights: &mut Vec<f32>,
+    intermediate: &mut Vec<f32>,
+    out: &mut Vec<f32>,
+) {
+    resample(
+        src,
+        (width, height),
+        (new_width, new_height),
+        weights,
+        intermediate,
+        out,
+        |value| value,
+    );
+}
 
-    buffer.enumerate_pixels_mut().for_each(|(x, y, pixel)| {
-        let [r, g, b, _] = img.get_pixel(x, y).0;
+/// The Lanczos3 resampling shared by [`resize`] and [`resize_f32`], where `finish` converts
+/// the sampled values to the output type.
+fn resample<T: Copy + Default + Into<f32>>(
+    src: &[T],
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+    weights: &mut Vec<f32>,
+    intermediate: &mut Vec<f32>,
+    out: &mut Vec<T>,
+    finish: impl Fn(f32) -> T,
+) {
+    out.clear();
 
-        let luma =
-            (coefficients[0] * r as f64 + coefficients[1] * g as f64 + coefficients[2] * b as f64)
-                .round();
+    // nothing to sample from
+    if width == 0 || height == 0 {
+        out.resize(new_width as usize * new_height as usize, T::default());
+        return;
+    }
+
+    // same dimensions, so we can simply copy the image
+    if (width, height) == (new_width, new_height) {
+        out.extend_from_slice(src);
+        return;
+    }
+
+    let (width, height) = (width as usize, height as usize);
+
+    // vertical pass, sampling each column down to the new height
+    intermediate.clear();
+    intermediate.resize(width * new_height as usize, 0.0);
+
+    for y in 0..new_height {
+        let top = filter_weights(y, height as u32, new_height, weights) as usize;
+
+        let row = &mut intermediate[y as usize * width..(y as usize + 1) * width];
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = weights.iter().enumerate().fold(0.0, |acc, (i, w)| {
+                acc + src[(top + i) * width + x].into() * w
+            });
+        }
+    }
 
-        *pixel = image::Luma([luma as u8]);
-    });
+    // horizontal pass, sampling each row down to the new width
+    out.resize(new_width as usize * new_height as usize, T::default());
 
-    DynamicImage::ImageLuma8(buffer)
+    for x in 0..new_width {
+        let left = filter_weights(x, width as u32, new_width, weights) as usize;
+
+        for y in 0..new_height as usize {
+            let row = &intermediate[y * width + left..];
+            let value = weights.iter().zip(row).fold(0.0, |acc, (w, v)| acc + v * w);
+
+            out[y * new_width as usize + x as usize] = finish(value);
+        }
+    }
 }
 
 /// Converts a given [`DynamicImage`] by converting it to grayscale and then resizing it
@@ -44,29 +1034,216 @@ fn grayscale(img: &DynamicImage, color_space: ColorSpace) -> DynamicImage {
 /// * `width`: The final width of the rescaled image
 /// * `height`: The final height of the rescaled image
 /// * `color_space`: The color space to use for the conversion
+/// * `alpha`: The way the alpha channel is treated
+/// * `normalization`: The contrast normalization applied after grayscaling
+/// * `buffers`: The buffers that are reused for the conversion
+///
+/// # Returns
+/// * The row-major luma values of the converted image
+#[cfg(feature = "image")]
+pub(crate) fn convert<'a>(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    color_space: ColorSpace,
+    alpha: AlphaPolicy,
+    normalization: Normalization,
+    buffers: &'a mut ConvertBuffers,
+) -> &'a [u8] {
+    let ConvertBuffers {
+        gray,
+        weights,
+        intermediate,
+        lookup,
+        resized,
+        ..
+    } = buffers;
+
+    grayscale(img, color_space, alpha, gray);
+    normalize(gray, img.dimensions(), normalization, lookup, round);
+    resize(
+        gray,
+        (img.width(), img.height()),
+        (width, height),
+        weights,
+        intermediate,
+        resized,
+    );
+
+    resized
+}
+
+/// Converts a given [`DynamicImage`] like [`convert`], but without quantizing the luma to
+/// 8 bits, so the full precision of high bit depth images is kept.
+///
+/// # Arguments
+/// * `img`: A reference to the image to convert
+/// * `target`: The size, color space, alpha policy, tone mapping and normalization of the
+///   converted image
+/// * `buffers`: The buffers that are reused for the conversion
+///
+/// # Returns
+/// * The row-major luma values of the converted image, where `255.0` is white
+#[cfg(feature = "image")]
+pub(crate) fn convert_f32<'a>(
+    img: &DynamicImage,
+    target: &ResizeTarget,
+    buffers: &'a mut ConvertBuffers,
+) -> &'a [f32] {
+    let ConvertBuffers {
+        gray_f32,
+        weights,
+        intermediate,
+        lookup,
+        resized_f32,
+        ..
+    } = buffers;
+
+    grayscale_f32(
+        img,
+        target.color_space,
+        target.alpha,
+        target.tone_mapping,
+        gray_f32,
+    );
+    normalize(
+        gray_f32,
+        img.dimensions(),
+        target.normalization,
+        lookup,
+        |value| value,
+    );
+    resize_f32(
+        gray_f32,
+        (img.width(), img.height()),
+        (target.width, target.height),
+        weights,
+        intermediate,
+        resized_f32,
+    );
+
+    resized_f32
+}
+
+/// The grayscaled and resized pixels of an image, as returned by [`convert_target`].
+#[cfg(feature = "image")]
+pub(crate) enum Converted<'a> {
+    /// The pixels of an image with 8 bits per channel
+    Luma8(&'a [u8]),
+
+    /// The pixels of a high bit depth image, which are not quantized to 8 bits
+    LumaF32(&'a [f32]),
+}
+
+#[cfg(feature = "image")]
+impl Converted<'_> {
+    /// Appends the pixels to `out`, row by row.
+    pub(crate) fn extend_f64(&self, out: &mut Vec<f64>) {
+        match self {
+            Converted::Luma8(pixels) => out.extend(pixels.iter().map(|&p| p as f64)),
+            Converted::LumaF32(pixels) => out.extend(pixels.iter().map(|&p| p as f64)),
+        }
+    }
+}
+
+/// Converts an image for the target with [`convert`], or with [`convert_f32`] if it has a
+/// high bit depth, so no precision is lost.
+///
+/// # Arguments
+/// * `img`: A reference to the image to convert
+/// * `target`: The size, color space, alpha policy, tone mapping and normalization of the
+///   converted image
+/// * `buffers`: The buffers that are reused for the conversion
 ///
 /// # Returns
-/// * The converted dynamic image
-pub(crate) fn convert(
+/// * The row-major luma values of the converted image
+#[cfg(feature = "image")]
+pub(crate) fn convert_target<'a>(
     img: &DynamicImage,
+    target: &ResizeTarget,
+    buffers: &'a mut ConvertBuffers,
+) -> Converted<'a> {
+    if is_high_bit_depth(img) {
+        Converted::LumaF32(convert_f32(img, target, buffers))
+    } else {
+        Converted::Luma8(convert(
+            img,
+            target.width,
+            target.height,
+            target.color_space,
+            target.alpha,
+            target.normalization,
+            buffers,
+        ))
+    }
+}
+
+/// Converts a given [`RawImage`] by converting it to grayscale and then resizing it
+/// to the specified size. Unpadded luma images without normalization are resized directly
+/// without any copy.
+///
+/// # Arguments
+/// * `img`: A reference to the raw image to convert
+/// * `width`: The final width of the rescaled image
+/// * `height`: The final height of the rescaled image
+/// * `color_space`: The color space to use for the conversion
+/// * `alpha`: The way the alpha channel is treated
+/// * `normalization`: The contrast normalization applied after grayscaling
+/// * `buffers`: The buffers that are reused for the conversion
+///
+/// # Returns
+/// * The row-major luma values of the converted image
+pub(crate) fn convert_raw<'a>(
+    img: &RawImage,
     width: u32,
     height: u32,
     color_space: ColorSpace,
-) -> DynamicImage {
-    let filter = FilterType::Lanczos3;
+    alpha: AlphaPolicy,
+    normalization: Normalization,
+    buffers: &'a mut ConvertBuffers,
+) -> &'a [u8] {
+    let ConvertBuffers {
+        gray,
+        weights,
+        intermediate,
+        lookup,
+        resized,
+        ..
+    } = buffers;
+
+    let unpadded = img.layout == PixelLayout::Luma && img.stride == img.width as usize;
 
-    let grayscale_img = grayscale(img, color_space);
-    grayscale_img.resize_exact(width, height, filter)
+    let src = if unpadded && normalization == Normalization::None {
+        &img.data[..img.width as usize * img.height as usize]
+    } else {
+        grayscale_raw(img, color_space, alpha, gray);
+        normalize(gray, (img.width, img.height), normalization, lookup, round);
+        gray
+    };
+
+    resize(
+        src,
+        (img.width, img.height),
+        (width, height),
+        weights,
+        intermediate,
+        resized,
+    );
+
+    resized
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use super::*;
 
-    use image::ImageReader;
+    use image::{imageops::FilterType, GrayImage, ImageReader};
     use std::path::Path;
 
     const TEST_IMG: &str = "./data/img/test.png";
+    const LOGO_IMG: &str = "./data/img/logo-alpha.png";
+    const LOGO_WHITE_IMG: &str = "./data/img/logo-white.png";
+    const LOGO_BLACK_IMG: &str = "./data/img/logo-black.png";
 
     const REC_601_IMG: &str = "./data/img/gray-601.png";
     const REC_709_IMG: &str = "./data/img/gray-709.png";
@@ -88,10 +1265,16 @@ mod tests {
             .unwrap();
 
         // Act
-        let grayscale = grayscale(&test_img, ColorSpace::REC601);
+        let mut grayscale_buf = Vec::new();
+        grayscale(
+            &test_img,
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            &mut grayscale_buf,
+        );
 
         // Assert
-        assert_eq!(grayscale, grayscale_img);
+        assert_eq!(grayscale_buf, grayscale_img.as_bytes());
     }
 
     #[test]
@@ -108,10 +1291,19 @@ mod tests {
             .unwrap();
 
         // Act
-        let converted = convert(&test_img, 32, 32, ColorSpace::REC601);
+        let mut buffers = ConvertBuffers::default();
+        let converted = convert(
+            &test_img,
+            32,
+            32,
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            Normalization::None,
+            &mut buffers,
+        );
 
         // Assert
-        assert_eq!(converted, converted_img);
+        assert_eq!(converted, converted_img.as_bytes());
     }
 
     #[test]
@@ -128,10 +1320,16 @@ mod tests {
             .unwrap();
 
         // Act
-        let grayscale = grayscale(&test_img, ColorSpace::REC709);
+        let mut grayscale_buf = Vec::new();
+        grayscale(
+            &test_img,
+            ColorSpace::REC709,
+            AlphaPolicy::Ignore,
+            &mut grayscale_buf,
+        );
 
         // Assert
-        assert_eq!(grayscale, grayscale_img);
+        assert_eq!(grayscale_buf, grayscale_img.as_bytes());
     }
 
     #[test]
@@ -148,9 +1346,682 @@ mod tests {
             .unwrap();
 
         // Act
-        let converted = convert(&test_img, 32, 32, ColorSpace::REC709);
+        let mut buffers = ConvertBuffers::default();
+        let converted = convert(
+            &test_img,
+            32,
+            32,
+            ColorSpace::REC709,
+            AlphaPolicy::Ignore,
+            Normalization::None,
+            &mut buffers,
+        );
+
+        // Assert
+        assert_eq!(converted, converted_img.as_bytes());
+    }
+
+    #[test]
+    fn test_convert_matches_image_resize() {
+        // Arrange
+        let test_img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let mut gray = Vec::new();
+        grayscale(
+            &test_img,
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            &mut gray,
+        );
+        let gray_img = DynamicImage::ImageLuma8(
+            GrayImage::from_raw(test_img.width(), test_img.height(), gray).unwrap(),
+        );
+
+        let mut buffers = ConvertBuffers::default();
+
+        for (width, height) in [(9, 8), (17, 5), (1, 1), (test_img.width() + 3, 7)] {
+            let expected = gray_img.resize_exact(width, height, FilterType::Lanczos3);
+
+            // Act
+            let converted = convert(
+                &test_img,
+                width,
+                height,
+                ColorSpace::REC601,
+                AlphaPolicy::Ignore,
+                Normalization::None,
+                &mut buffers,
+            );
+
+            // Assert
+            assert_eq!(converted, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_convert_with_same_size() {
+        // Arrange
+        let test_img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let grayscale_img = ImageReader::open(Path::new(REC_601_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        // Act
+        let mut buffers = ConvertBuffers::default();
+        let converted = convert(
+            &test_img,
+            test_img.width(),
+            test_img.height(),
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            Normalization::None,
+            &mut buffers,
+        );
+
+        // Assert
+        assert_eq!(converted, grayscale_img.as_bytes());
+    }
+
+    fn to_bgra(rgb: &[u8]) -> Vec<u8> {
+        rgb.chunks(3)
+            .flat_map(|p| [p[2], p[1], p[0], 255])
+            .collect()
+    }
+
+    #[test]
+    fn test_grayscale_raw_matches_grayscale() {
+        // Arrange
+        let test_img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let (width, height) = (test_img.width(), test_img.height());
+        let rgb = test_img.as_bytes();
+        let rgba = test_img.to_rgba8().into_raw();
+        let bgra = to_bgra(rgb);
+
+        let mut expected = Vec::new();
+        grayscale(
+            &test_img,
+            ColorSpace::REC709,
+            AlphaPolicy::Ignore,
+            &mut expected,
+        );
+
+        let raws = [
+            RawImage::new(rgb, width, height, width as usize * 3, PixelLayout::Rgb).unwrap(),
+            RawImage::new(&rgba, width, height, width as usize * 4, PixelLayout::Rgba).unwrap(),
+            RawImage::new(&bgra, width, height, width as usize * 4, PixelLayout::Bgra).unwrap(),
+        ];
+
+        for raw in raws {
+            // Act
+            let mut gray = Vec::new();
+            grayscale_raw(&raw, ColorSpace::REC709, AlphaPolicy::Ignore, &mut gray);
+
+            // Assert
+            assert_eq!(gray, expected);
+            assert_eq!(raw.to_dynamic_image().to_rgb8().into_raw(), rgb);
+        }
+    }
+
+    /// Grayscales a single row of RGB pixels.
+    fn grayscale_pixels(pixels: &[[u8; 3]], color_space: ColorSpace) -> Vec<u8> {
+        let data = pixels.concat();
+        let width = pixels.len() as u32;
+        let raw = RawImage::new(&data, width, 1, data.len(), PixelLayout::Rgb).unwrap();
+
+        let mut gray = Vec::new();
+        grayscale_raw(&raw, color_space, AlphaPolicy::Ignore, &mut gray);
+        gray
+    }
+
+    #[test]
+    fn test_grayscale_with_rec_2020() {
+        // Act
+        let gray = grayscale_pixels(&[[0, 255, 0], [255, 255, 255]], ColorSpace::REC2020);
+
+        // Assert
+        assert_eq!(gray, vec![173, 255]);
+    }
+
+    #[test]
+    fn test_grayscale_with_srgb_linear() {
+        // Arrange
+        let pixels = [
+            [0, 0, 0],
+            [128, 128, 128],
+            [255, 255, 255],
+            [255, 0, 0],
+            [0, 0, 255],
+        ];
+
+        // Act
+        let gray = grayscale_pixels(&pixels, ColorSpace::SRGBLinear);
+
+        // Assert: grays keep their value, colors are weighted on linear light
+        assert_eq!(gray, vec![0, 128, 255, 127, 76]);
+    }
+
+    #[test]
+    fn test_grayscale_with_lightness() {
+        // Arrange
+        let pixels = [[0, 0, 0], [119, 119, 119], [255, 255, 255], [255, 0, 0]];
+
+        // Act
+        let gray = grayscale_pixels(&pixels, ColorSpace::Lightness);
+
+        // Assert: sRGB 119 is roughly the perceptual middle gray with an L* of 50
+        assert_eq!(gray, vec![0, 128, 255, 136]);
+    }
+
+    #[test]
+    fn test_grayscale_with_single_channels() {
+        // Arrange
+        let pixels = [[10, 20, 30], [200, 100, 0]];
+
+        // Act & Assert
+        assert_eq!(grayscale_pixels(&pixels, ColorSpace::Red), vec![10, 200]);
+        assert_eq!(grayscale_pixels(&pixels, ColorSpace::Green), vec![20, 100]);
+        assert_eq!(grayscale_pixels(&pixels, ColorSpace::Blue), vec![30, 0]);
+    }
+
+    #[test]
+    fn test_grayscale_with_custom_coefficients() {
+        // Arrange
+        let test_img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let mut expected = Vec::new();
+        grayscale(
+            &test_img,
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            &mut expected,
+        );
+
+        // Act
+        let mut gray = Vec::new();
+        grayscale(
+            &test_img,
+            ColorSpace::Custom([0.299, 0.587, 0.114]),
+            AlphaPolicy::Ignore,
+            &mut gray,
+        );
+
+        // Assert
+        assert_eq!(gray, expected);
+    }
+
+    #[test]
+    fn test_grayscale_with_custom_coefficients_is_clamped() {
+        // Act
+        let gray = grayscale_pixels(
+            &[[200, 0, 0], [0, 200, 0]],
+            ColorSpace::Custom([2.0, -1.0, 0.0]),
+        );
+
+        // Assert
+        assert_eq!(gray, vec![255, 0]);
+    }
+
+    #[test]
+    fn test_grayscale_with_alpha_composited_over_background() {
+        // Arrange
+        let open = |path| {
+            ImageReader::open(Path::new(path))
+                .unwrap()
+                .decode()
+                .unwrap()
+        };
+        let logo = open(LOGO_IMG);
+
+        for (alpha, flattened) in [
+            (AlphaPolicy::CompositeOver([255, 255, 255]), LOGO_WHITE_IMG),
+            (AlphaPolicy::CompositeOver([0, 0, 0]), LOGO_BLACK_IMG),
+        ] {
+            let mut expected = Vec::new();
+            grayscale(
+                &open(flattened),
+                ColorSpace::REC601,
+                AlphaPolicy::Ignore,
+                &mut expected,
+            );
+
+            // Act
+            let mut gray = Vec::new();
+            grayscale(&logo, ColorSpace::REC601, alpha, &mut gray);
+
+            // Assert
+            assert_eq!(gray, expected, "{alpha:?}");
+        }
+    }
+
+    #[test]
+    fn test_grayscale_with_alpha_ignored() {
+        // Arrange
+        let open = |path| {
+            ImageReader::open(Path::new(path))
+                .unwrap()
+                .decode()
+                .unwrap()
+        };
+
+        let mut composited = Vec::new();
+        grayscale(
+            &open(LOGO_WHITE_IMG),
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            &mut composited,
+        );
+
+        // Act
+        let mut gray = Vec::new();
+        grayscale(
+            &open(LOGO_IMG),
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            &mut gray,
+        );
+
+        // Assert: the garbage below the transparent pixels is hashed
+        assert_ne!(gray, composited);
+    }
+
+    #[test]
+    fn test_grayscale_raw_with_semi_transparent_pixels() {
+        // Arrange: opaque red, half transparent red, fully transparent garbage
+        let rgba = [255, 0, 0, 255, 255, 0, 0, 128, 12, 34, 56, 0];
+        let bgra = [0, 0, 255, 255, 0, 0, 255, 128, 56, 34, 12, 0];
+
+        let alpha = AlphaPolicy::CompositeOver([255, 255, 255]);
+
+        for (data, layout) in [(rgba, PixelLayout::Rgba), (bgra, PixelLayout::Bgra)] {
+            let raw = RawImage::new(&data, 3, 1, 12, layout).unwrap();
+
+            // Act
+            let mut gray = Vec::new();
+            grayscale_raw(&raw, ColorSpace::Red, alpha, &mut gray);
+
+            let mut over_black = Vec::new();
+            grayscale_raw(
+                &raw,
+                ColorSpace::Red,
+                AlphaPolicy::CompositeOver([0, 0, 0]),
+                &mut over_black,
+            );
+
+            // Assert
+            assert_eq!(gray, vec![255, 255, 255]);
+            assert_eq!(over_black, vec![255, 128, 0]);
+        }
+    }
+
+    #[test]
+    fn test_color_space_equality() {
+        // Act & Assert
+        assert_eq!(ColorSpace::Custom([0.5; 3]), ColorSpace::Custom([0.5; 3]));
+        assert_ne!(
+            ColorSpace::Custom([0.5; 3]),
+            ColorSpace::Custom([0.5, 0.5, 0.0])
+        );
+        assert_ne!(
+            ColorSpace::REC601,
+            ColorSpace::Custom([0.299, 0.587, 0.114])
+        );
+        assert!(ColorSpace::Custom([f64::NAN, 0.0, 0.0]).check().is_err());
+    }
+
+    #[test]
+    fn test_grayscale_f32_matches_grayscale() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let deep = DynamicImage::ImageRgb16(img.to_rgb16());
+
+        let mut expected = Vec::new();
+        grayscale(&img, ColorSpace::REC601, AlphaPolicy::Ignore, &mut expected);
+
+        for img in [img, deep] {
+            // Act
+            let mut gray = Vec::new();
+            grayscale_f32(
+                &img,
+                ColorSpace::REC601,
+                AlphaPolicy::Ignore,
+                ToneMapping::None,
+                &mut gray,
+            );
+
+            // Assert
+            assert_eq!(gray.len(), expected.len());
+            assert!(gray
+                .iter()
+                .zip(&expected)
+                .all(|(&a, &b)| (a - b as f32).abs() <= 0.5));
+        }
+    }
+
+    #[test]
+    fn test_grayscale_f32_with_tone_mapping() {
+        // Arrange
+        let img = DynamicImage::ImageRgb32F(ImageBuffer::from_fn(3, 1, |x, _| {
+            image::Rgb([[0.5, 2.0, 10.0][x as usize]; 3])
+        }));
+
+        let cases = [
+            (ToneMapping::None, [127.5, 510.0, 2550.0]),
+            (ToneMapping::Clamp, [127.5, 255.0, 255.0]),
+            (ToneMapping::Reinhard, [85.0, 170.0, 231.818]),
+        ];
+
+        for (tone_mapping, expected) in cases {
+            // Act
+            let mut gray = Vec::new();
+            grayscale_f32(
+                &img,
+                ColorSpace::REC601,
+                AlphaPolicy::Ignore,
+                tone_mapping,
+                &mut gray,
+            );
+
+            // Assert
+            assert!(
+                gray.iter()
+                    .zip(expected)
+                    .all(|(&a, b)| (a - b).abs() < 0.01),
+                "{tone_mapping:?}: {gray:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalize_with_min_max() {
+        // Arrange
+        let mut pixels = [50u8, 100, 150];
+        let mut pixels_f32 = [50.0f32, 100.0, 150.0];
+
+        // Act
+        normalize(
+            &mut pixels,
+            (3, 1),
+            Normalization::MinMax,
+            &mut Vec::new(),
+            round,
+        );
+        normalize(
+            &mut pixels_f32,
+            (3, 1),
+            Normalization::MinMax,
+            &mut Vec::new(),
+            |value| value,
+        );
 
         // Assert
-        assert_eq!(converted, converted_img);
+        assert_eq!(pixels, [0, 128, 255]);
+        assert_eq!(pixels_f32, [0.0, 127.5, 255.0]);
+    }
+
+    #[test]
+    fn test_normalize_with_equalize() {
+        // Arrange
+        let mut pixels = [10u8, 10, 20, 30];
+
+        // Act
+        normalize(
+            &mut pixels,
+            (2, 2),
+            Normalization::Equalize,
+            &mut Vec::new(),
+            round,
+        );
+
+        // Assert
+        assert_eq!(pixels, [0, 0, 128, 255]);
+    }
+
+    #[test]
+    fn test_normalize_uniform_image() {
+        for normalization in [
+            Normalization::MinMax,
+            Normalization::Equalize,
+            Normalization::Clahe {
+                tiles: 2,
+                clip_limit: 2,
+            },
+        ] {
+            // Arrange
+            let mut pixels = [77u8; 16];
+
+            // Act
+            normalize(&mut pixels, (4, 4), normalization, &mut Vec::new(), round);
+
+            // Assert: there is no contrast that could be stretched
+            let expected = if matches!(normalization, Normalization::Clahe { .. }) {
+                pixels[0]
+            } else {
+                77
+            };
+            assert!(pixels.iter().all(|&p| p == expected), "{normalization:?}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_with_clahe_stretches_local_contrast() {
+        // Arrange: a dark and a bright half, each with only little contrast of its own
+        let mut pixels = (0..32 * 32)
+            .map(|i| {
+                let (x, y) = (i % 32, i / 32);
+                let base = if x < 16 { 20 } else { 220 };
+                base + ((x + y) % 8) as u8
+            })
+            .collect::<Vec<_>>();
+
+        // Act
+        normalize(
+            &mut pixels,
+            (32, 32),
+            Normalization::Clahe {
+                tiles: 2,
+                clip_limit: 16,
+            },
+            &mut Vec::new(),
+            round,
+        );
+
+        // Assert
+        let range = |half: &dyn Fn(usize) -> bool| {
+            let values = pixels.iter().enumerate().filter(|(i, _)| half(i % 32));
+            let (min, max) = values.fold((u8::MAX, u8::MIN), |(min, max), (_, &v)| {
+                (min.min(v), max.max(v))
+            });
+            max - min
+        };
+        assert!(range(&|x| x < 8) > 100);
+        assert!(range(&|x| x >= 24) > 100);
+    }
+
+    #[test]
+    fn test_normalize_with_clahe_on_large_single_tile() {
+        // Arrange: a 24 megapixel photo, whose clipped histogram doesn't fit into u32 math
+        let (width, height) = (6000, 4000);
+        let mut pixels = (0..width * height)
+            .map(|i| if i % width < width / 2 { 100 } else { 150 })
+            .collect::<Vec<u8>>();
+
+        // Act
+        normalize(
+            &mut pixels,
+            (width, height),
+            Normalization::Clahe {
+                tiles: 1,
+                clip_limit: u8::MAX,
+            },
+            &mut Vec::new(),
+            round,
+        );
+
+        // Assert: nothing is clipped, so the single tile is equalized
+        assert_eq!(pixels[0], 128);
+        assert_eq!(pixels[pixels.len() - 1], 255);
+    }
+
+    #[test]
+    fn test_resize_f32_is_not_clamped() {
+        // Arrange
+        let src = [400.0; 16];
+        let mut out = Vec::new();
+
+        // Act
+        resize_f32(
+            &src,
+            (4, 4),
+            (2, 2),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut out,
+        );
+
+        // Assert
+        assert_eq!(out.len(), 4);
+        assert!(out.iter().all(|&v| (v - 400.0).abs() < 0.01));
+    }
+
+    #[test]
+    fn test_resize_f32_matches_resize() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let mut gray = Vec::new();
+        grayscale(&img, ColorSpace::REC601, AlphaPolicy::Ignore, &mut gray);
+        let gray_f32: Vec<f32> = gray.iter().map(|&v| v as f32).collect();
+        let size = (img.width(), img.height());
+
+        let mut expected = Vec::new();
+        resize(
+            &gray,
+            size,
+            (9, 8),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut expected,
+        );
+
+        // Act
+        let mut resized = Vec::new();
+        resize_f32(
+            &gray_f32,
+            size,
+            (9, 8),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut resized,
+        );
+
+        // Assert
+        let rounded: Vec<u8> = resized
+            .iter()
+            .map(|v| v.clamp(0.0, 255.0).round() as u8)
+            .collect();
+        assert_eq!(rounded, expected);
+    }
+
+    #[test]
+    fn test_convert_raw_with_padded_luma() {
+        // Arrange
+        let gray_img = ImageReader::open(Path::new(REC_601_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let converted_img = ImageReader::open(Path::new(REC_601_SCALED_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        // pad every row with 5 bytes of garbage
+        let (width, height) = (gray_img.width(), gray_img.height());
+        let padded = gray_img
+            .as_bytes()
+            .chunks(width as usize)
+            .flat_map(|row| row.iter().copied().chain([255; 5]))
+            .collect::<Vec<_>>();
+
+        let raw = RawImage::new(
+            &padded,
+            width,
+            height,
+            width as usize + 5,
+            PixelLayout::Luma,
+        )
+        .unwrap();
+
+        // Act
+        let mut buffers = ConvertBuffers::default();
+        let converted = convert_raw(
+            &raw,
+            32,
+            32,
+            ColorSpace::REC601,
+            AlphaPolicy::Ignore,
+            Normalization::None,
+            &mut buffers,
+        );
+
+        // Assert
+        assert_eq!(converted, converted_img.as_bytes());
+    }
+
+    #[test]
+    fn test_raw_image_with_last_row_unpadded() {
+        // the padding of the last row may be omitted
+        let result = RawImage::new(&[0; 7], 2, 2, 5, PixelLayout::Luma);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_raw_image_with_zero_size() {
+        let result = RawImage::new(&[], 0, 2, 0, PixelLayout::Luma);
+        assert!(matches!(result, Err(ImageHashError::EmptyMatrix)));
+    }
+
+    #[test]
+    fn test_raw_image_with_small_stride() {
+        let result = RawImage::new(&[0; 64], 4, 4, 11, PixelLayout::Rgb);
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidStride {
+                stride: 11,
+                row: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn test_raw_image_with_small_buffer() {
+        let result = RawImage::new(&[0; 63], 4, 4, 16, PixelLayout::Bgra);
+        assert!(matches!(
+            result,
+            Err(ImageHashError::BufferTooSmall {
+                expected: 64,
+                actual: 63
+            })
+        ));
     }
 }
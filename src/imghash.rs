@@ -1,4 +1,8 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "image")]
 use std::io;
+#[cfg(feature = "image")]
 use std::path::PathBuf;
 
 use bitvec::prelude::*;
@@ -7,9 +11,18 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum ImageHashError {
+    #[cfg(feature = "image")]
     #[error("Failed to read image from path '{}': {source}", path.display())]
     IoError { source: io::Error, path: PathBuf },
 
+    #[cfg(feature = "image")]
+    #[error("Failed to read image data: {0}")]
+    ReadError(io::Error),
+
+    #[error("Failed to determine the image format")]
+    UnknownFormat,
+
+    #[cfg(feature = "image")]
     #[error("Failed to decode image: {0}")]
     ImageError(#[from] image::ImageError),
 
@@ -29,12 +42,104 @@ pub enum ImageHashError {
     InvalidHashLength {
         expected: usize,
         actual: usize,
-        width: u8,
-        height: u8,
+        width: u16,
+        height: u16,
+    },
+
+    #[error("Invalid value for parameter '{name}': {reason}")]
+    InvalidParameter {
+        name: &'static str,
+        reason: &'static str,
     },
 
+    #[error("Dimensions {width}x{height} overflow the addressable size")]
+    DimensionOverflow { width: usize, height: usize },
+
+    #[error("Position ({row}, {column}) is out of bounds for a hash of shape {shape:?}")]
+    PositionOutOfBounds {
+        row: usize,
+        column: usize,
+        shape: (usize, usize),
+    },
+
+    #[error("The mask or weights leave no bits to compare")]
+    NothingToCompare,
+
     #[error("Invalid hexadecimal character in hash string")]
     InvalidHexCharacter,
+
+    #[error("Matrix has {actual} elements, expected {expected} (width * height)")]
+    MatrixSizeMismatch { expected: usize, actual: usize },
+
+    #[error("Row stride of {stride} bytes is smaller than a row of {row} bytes")]
+    InvalidStride { stride: usize, row: usize },
+
+    #[error("Pixel buffer has {actual} bytes, expected at least {expected}")]
+    BufferTooSmall { expected: usize, actual: usize },
+
+    #[error("Hasher does not support hashing pre-resized images")]
+    ResizedNotSupported,
+
+    #[error("Hasher name '{0}' is used more than once")]
+    DuplicateHasherName(String),
+
+    #[error("Unknown hash algorithm '{0}'")]
+    UnknownAlgorithm(String),
+
+    #[error("Invalid hasher spec: {0}")]
+    InvalidSpec(String),
+}
+
+/// One of the eight rotations and reflections of a rectangle, the dihedral group of the square.
+///
+/// Rotations are clockwise, so [`Orientation::Rotate90`] turns the top row into the right column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Orientation {
+    #[default]
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    /// Mirrors along the main diagonal, swapping rows and columns.
+    Transpose,
+    /// Mirrors along the anti-diagonal.
+    Transverse,
+}
+
+impl Orientation {
+    /// All eight orientations, starting with [`Orientation::Identity`].
+    pub const ALL: [Orientation; 8] = [
+        Orientation::Identity,
+        Orientation::Rotate90,
+        Orientation::Rotate180,
+        Orientation::Rotate270,
+        Orientation::FlipHorizontal,
+        Orientation::FlipVertical,
+        Orientation::Transpose,
+        Orientation::Transverse,
+    ];
+
+    /// Whether the orientation swaps rows and columns.
+    pub fn swaps_axes(&self) -> bool {
+        self.parts().0
+    }
+
+    /// Decomposes the orientation into an optional transpose, followed by an optional
+    /// horizontal and an optional vertical flip.
+    pub(crate) fn parts(&self) -> (bool, bool, bool) {
+        match self {
+            Orientation::Identity => (false, false, false),
+            Orientation::FlipHorizontal => (false, true, false),
+            Orientation::FlipVertical => (false, false, true),
+            Orientation::Rotate180 => (false, true, true),
+            Orientation::Transpose => (true, false, false),
+            Orientation::Rotate90 => (true, true, false),
+            Orientation::Rotate270 => (true, false, true),
+            Orientation::Transverse => (true, true, true),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -43,7 +148,45 @@ pub struct ImageHash {
     data: BitBox<u8, Lsb0>,
 
     // Number of columns.
-    width: u8,
+    width: u16,
+}
+
+/// Converts a dimension of any integer type into `u16`. Besides `u16`, this accepts the `u8`
+/// dimensions of former versions and integer literals, which default to `i32`.
+///
+/// # Returns
+/// * An [`ImageHashError::InvalidParameter`] if the value is negative or larger than `u16::MAX`
+pub(crate) fn dimension(
+    value: impl TryInto<u16>,
+    name: &'static str,
+) -> Result<u16, ImageHashError> {
+    value.try_into().map_err(|_| invalid_dimension(name))
+}
+
+/// The error for a dimension that does not fit into `u16`.
+pub(crate) fn invalid_dimension(name: &'static str) -> ImageHashError {
+    ImageHashError::InvalidParameter {
+        name,
+        reason: "must be between 0 and 65535",
+    }
+}
+
+/// The number of bits of a `width` x `height` hash.
+///
+/// # Returns
+/// * An [`ImageHashError::EmptyMatrix`] if one of the dimensions is zero
+/// * An [`ImageHashError::DimensionOverflow`] if the number of bits does not fit into `usize`
+fn checked_length(width: u16, height: u16) -> Result<usize, ImageHashError> {
+    if width == 0 || height == 0 {
+        return Err(ImageHashError::EmptyMatrix);
+    }
+
+    (width as usize)
+        .checked_mul(height as usize)
+        .ok_or(ImageHashError::DimensionOverflow {
+            width: width as usize,
+            height: height as usize,
+        })
 }
 
 impl ImageHash {
@@ -59,13 +202,11 @@ impl ImageHash {
     /// * The new [`ImageHash`].
     pub fn from_bool_iter(
         iter: impl IntoIterator<Item = bool>,
-        width: u8,
-        height: u8,
+        width: impl TryInto<u16>,
+        height: impl TryInto<u16>,
     ) -> Result<ImageHash, ImageHashError> {
-        let length = width as usize * height as usize;
-        if length == 0 {
-            return Err(ImageHashError::EmptyMatrix);
-        }
+        let (width, height) = (dimension(width, "width")?, dimension(height, "height")?);
+        let length = checked_length(width, height)?;
 
         let mut data = bitbox![u8, Lsb0; 0; length];
         let mut count = 0;
@@ -115,15 +256,60 @@ impl ImageHash {
         (self.data.len() / self.width as usize, self.width as usize)
     }
 
+    /// The bit at the specified position of the matrix.
+    ///
+    /// # Returns
+    /// * The bit, or `None` if the position is outside of the matrix
+    pub fn get(&self, row: usize, column: usize) -> Option<bool> {
+        let index = self.index(row, column)?;
+        Some(self.data[index])
+    }
+
+    /// Sets the bit at the specified position of the matrix.
+    ///
+    /// # Arguments
+    /// * `row`: The row of the bit
+    /// * `column`: The column of the bit
+    /// * `bit`: The new value of the bit
+    ///
+    /// # Returns
+    /// * An [`ImageHashError::PositionOutOfBounds`] if the position is outside of the matrix
+    pub fn set(&mut self, row: usize, column: usize, bit: bool) -> Result<(), ImageHashError> {
+        let index = self
+            .index(row, column)
+            .ok_or(ImageHashError::PositionOutOfBounds {
+                row,
+                column,
+                shape: self.shape(),
+            })?;
+
+        self.data.set(index, bit);
+        Ok(())
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        self.data.count_ones()
+    }
+
+    /// Create an iterator over the rows of the matrix, each yielding the bits of the row from
+    /// left to right.
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = impl Iterator<Item = bool> + '_> + '_ {
+        self.data
+            .chunks_exact(self.width as usize)
+            .map(|row| row.iter().by_vals())
+    }
+
+    /// The index of a position in the flattened matrix, if it is inside the matrix.
+    fn index(&self, row: usize, column: usize) -> Option<usize> {
+        let (rows, columns) = self.shape();
+        (row < rows && column < columns).then_some(row * columns + column)
+    }
+
     /// The hamming distance between this hash and the other hash.
     /// The hamming distance is the number of bits that differ between the two hashes.
     pub fn distance(&self, other: &ImageHash) -> Result<usize, ImageHashError> {
-        if self.shape() != other.shape() {
-            return Err(ImageHashError::ShapeMismatch {
-                self_shape: self.shape(),
-                other_shape: other.shape(),
-            });
-        }
+        self.check_shape(other)?;
 
         Ok(self
             .data
@@ -134,16 +320,233 @@ impl ImageHash {
             .sum())
     }
 
+    /// The share of bits that are equal between this hash and the other hash, from `0` for
+    /// inverted hashes to `1` for identical hashes. Unlike the distance, it is comparable
+    /// across hash sizes.
+    pub fn similarity(&self, other: &ImageHash) -> Result<f64, ImageHashError> {
+        let distance = self.distance(other)?;
+        Ok(1.0 - distance as f64 / self.data.len() as f64)
+    }
+
+    /// The hamming distance between this hash and the other hash, only counting the bits that
+    /// are set in the mask. Bits that are known to be unreliable, for example in a region
+    /// covered by a watermark, can be left out of the comparison this way.
+    ///
+    /// # Arguments
+    /// * `other`: The hash to compare against
+    /// * `mask`: A hash of the same shape, whose set bits are compared
+    ///
+    /// # Returns
+    /// * The number of compared bits that differ, and the similarity in `[0, 1]`, which is the
+    ///   share of the compared bits that are equal
+    /// * An [`ImageHashError::ShapeMismatch`] if the other hash or the mask have a different shape
+    /// * An [`ImageHashError::NothingToCompare`] if no bit is set in the mask
+    pub fn distance_masked(
+        &self,
+        other: &ImageHash,
+        mask: &ImageHash,
+    ) -> Result<(usize, f64), ImageHashError> {
+        self.check_shape(other)?;
+        self.check_shape(mask)?;
+
+        let compared = mask.count_ones();
+        if compared == 0 {
+            return Err(ImageHashError::NothingToCompare);
+        }
+
+        // the padding of the last byte is clear in the mask, so it is never counted
+        let distance = self
+            .data
+            .as_raw_slice()
+            .iter()
+            .zip(other.data.as_raw_slice())
+            .zip(mask.data.as_raw_slice())
+            .map(|((a, b), m)| ((a ^ b) & m).count_ones() as usize)
+            .sum();
+
+        Ok((distance, 1.0 - distance as f64 / compared as f64))
+    }
+
+    /// The hamming distance between this hash and the other hash, where each differing bit
+    /// counts with its weight instead of one. Bits with a weight of zero are ignored.
+    ///
+    /// # Arguments
+    /// * `other`: The hash to compare against
+    /// * `weights`: The non-negative weight of each bit, row by row
+    ///
+    /// # Returns
+    /// * The sum of the weights of the differing bits, and the similarity in `[0, 1]`, which is
+    ///   the share of the total weight on equal bits
+    /// * An [`ImageHashError::ShapeMismatch`] if the other hash has a different shape
+    /// * An [`ImageHashError::MatrixSizeMismatch`] if there is not exactly one weight per bit
+    /// * An [`ImageHashError::InvalidParameter`] if a weight is negative or not finite
+    /// * An [`ImageHashError::NothingToCompare`] if all weights are zero
+    pub fn distance_weighted(
+        &self,
+        other: &ImageHash,
+        weights: &[f32],
+    ) -> Result<(f64, f64), ImageHashError> {
+        self.check_shape(other)?;
+
+        if weights.len() != self.data.len() {
+            return Err(ImageHashError::MatrixSizeMismatch {
+                expected: self.data.len(),
+                actual: weights.len(),
+            });
+        }
+
+        if !weights.iter().all(|w| w.is_finite() && *w >= 0.0) {
+            return Err(ImageHashError::InvalidParameter {
+                name: "weights",
+                reason: "must be finite and non-negative",
+            });
+        }
+
+        let mut distance = 0.0;
+        let mut total = 0.0;
+
+        for ((a, b), &weight) in self.iter_bool().zip(other.iter_bool()).zip(weights) {
+            total += weight as f64;
+            if a != b {
+                distance += weight as f64;
+            }
+        }
+
+        if total == 0.0 {
+            return Err(ImageHashError::NothingToCompare);
+        }
+
+        Ok((distance, 1.0 - distance / total))
+    }
+
+    /// Checks that the other hash has the same shape as this hash.
+    fn check_shape(&self, other: &ImageHash) -> Result<(), ImageHashError> {
+        if self.shape() != other.shape() {
+            return Err(ImageHashError::ShapeMismatch {
+                self_shape: self.shape(),
+                other_shape: other.shape(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Mirrors the hash along its main diagonal, swapping rows and columns.
+    pub fn transpose(&self) -> ImageHash {
+        self.orient(Orientation::Transpose)
+    }
+
+    /// Mirrors the hash left to right.
+    pub fn flip_horizontal(&self) -> ImageHash {
+        self.orient(Orientation::FlipHorizontal)
+    }
+
+    /// Mirrors the hash top to bottom.
+    pub fn flip_vertical(&self) -> ImageHash {
+        self.orient(Orientation::FlipVertical)
+    }
+
+    /// Rotates the hash clockwise by 90 degrees.
+    pub fn rotate90(&self) -> ImageHash {
+        self.orient(Orientation::Rotate90)
+    }
+
+    /// Rotates or reflects the bit matrix of the hash.
+    ///
+    /// For the [`AverageHasher`](crate::average::AverageHasher) and the
+    /// [`MedianHasher`](crate::median::MedianHasher), the result is the hash of the image
+    /// transformed the same way, as long as the resize commutes with the transformation. This
+    /// holds exactly for flips and, with square hashes, for all eight orientations, up to
+    /// pixels that are rounded right at the mean or the median.
+    ///
+    /// It does not hold for the [`DifferenceHasher`](crate::difference::DifferenceHasher),
+    /// whose bits compare horizontal neighbours and flip their meaning when mirrored, nor for
+    /// the [`PerceptualHasher`](crate::perceptual::PerceptualHasher), whose bits are DCT
+    /// coefficients. The latter has a specialized
+    /// [`PerceptualHasher::hash_dihedral`](crate::perceptual::PerceptualHasher::hash_dihedral).
+    ///
+    /// # Arguments
+    /// * `orientation`: The rotation or reflection to apply
+    ///
+    /// # Returns
+    /// * The transformed hash, with rows and columns swapped if the orientation swaps axes
+    pub fn orient(&self, orientation: Orientation) -> ImageHash {
+        let (rows, columns) = self.shape();
+        let (transpose, flip_horizontal, flip_vertical) = orientation.parts();
+        let (width, height) = if transpose {
+            (rows, columns)
+        } else {
+            (columns, rows)
+        };
+
+        let bits = (0..height).flat_map(|row| (0..width).map(move |column| (row, column)));
+        let data = BitBox::from_iter(bits.map(|(row, column)| {
+            // undo the flips first and the transpose last, as they are applied the other way around
+            let row = if flip_vertical { height - 1 - row } else { row };
+            let column = if flip_horizontal {
+                width - 1 - column
+            } else {
+                column
+            };
+            let (row, column) = if transpose {
+                (column, row)
+            } else {
+                (row, column)
+            };
+
+            self.data[row * columns + column]
+        }));
+
+        ImageHash {
+            data,
+            width: width as u16,
+        }
+    }
+
+    /// The smallest hamming distance between this hash in any of the eight orientations and
+    /// the other hash.
+    ///
+    /// Orientations that swap rows and columns are only considered if the result has the shape
+    /// of the other hash, so non-square hashes are only compared in four orientations. See
+    /// [`ImageHash::orient`] for the hashers this is exact for.
+    ///
+    /// # Arguments
+    /// * `other`: The hash to compare against
+    ///
+    /// # Returns
+    /// * The smallest distance and the orientation that, applied to this hash, achieves it.
+    ///   Ties go to the first orientation in [`Orientation::ALL`]
+    /// * An [`ImageHashError::ShapeMismatch`] if no orientation matches the shape of the other hash
+    pub fn min_distance_dihedral(
+        &self,
+        other: &ImageHash,
+    ) -> Result<(usize, Orientation), ImageHashError> {
+        Orientation::ALL
+            .iter()
+            .filter_map(|&orientation| {
+                let oriented = self.orient(orientation);
+                oriented
+                    .distance(other)
+                    .ok()
+                    .map(|distance| (distance, orientation))
+            })
+            .min_by_key(|&(distance, _)| distance)
+            .ok_or(ImageHashError::ShapeMismatch {
+                self_shape: self.shape(),
+                other_shape: other.shape(),
+            })
+    }
+
     /// Encodes the bit matrix that represents the [`ImageHash`] into a hexadecimal string.
     /// This implementation is strictly compatible with `imagehash` package for Python.
     pub fn encode(&self) -> Result<String, ImageHashError> {
-        use std::io::Write;
+        use core::fmt::Write;
 
         if self.data.is_empty() || self.width == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        let mut result = Vec::new();
+        let mut result = String::new();
 
         let length = self.data.len();
         let size = length.div_ceil(8);
@@ -152,7 +555,7 @@ impl ImageHash {
         let odd = nibbles % 2 == 1;
 
         let buffer = BitBox::<u8, Msb0>::from_iter(
-            std::iter::repeat_n(false, padding).chain(self.iter_bool()),
+            core::iter::repeat_n(false, padding).chain(self.iter_bool()),
         );
 
         for byte in buffer.as_raw_slice().iter() {
@@ -164,8 +567,7 @@ impl ImageHash {
             }
         }
 
-        // Infallible: hex formatting only produces valid UTF-8 ASCII bytes
-        Ok(String::from_utf8(result).unwrap())
+        Ok(result)
     }
 
     /// Decodes a hexadecimal string into a bit matrix that represents the [`ImageHash`].
@@ -181,12 +583,13 @@ impl ImageHash {
     /// it allows the decoding of hashes that have been generated on non-square matrices. This is because
     /// the original package actually only allows the generation of hashes on square matrices, however this
     /// crate does allow arbitrary dimensions.
-    pub fn decode(s: &str, width: u8, height: u8) -> Result<ImageHash, ImageHashError> {
-        let length = width as usize * height as usize;
-
-        if length == 0 {
-            return Err(ImageHashError::EmptyMatrix);
-        }
+    pub fn decode(
+        s: &str,
+        width: impl TryInto<u16>,
+        height: impl TryInto<u16>,
+    ) -> Result<ImageHash, ImageHashError> {
+        let (width, height) = (dimension(width, "width")?, dimension(height, "height")?);
+        let length = checked_length(width, height)?;
 
         let size = length.div_ceil(8);
         let nibbles = length.div_ceil(4);
@@ -203,7 +606,7 @@ impl ImageHash {
 
         // Add padding if the number of nibbles is odd
         let mut iter =
-            std::iter::repeat_n('0', if nibbles % 2 == 1 { 1 } else { 0 }).chain(s.chars());
+            core::iter::repeat_n('0', if nibbles % 2 == 1 { 1 } else { 0 }).chain(s.chars());
 
         let mut data = Vec::<u8>::with_capacity(size);
 
@@ -231,8 +634,8 @@ impl ImageHash {
     }
 }
 
-impl std::fmt::Display for ImageHash {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for ImageHash {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self.encode() {
             Ok(s) => write!(f, "{}", s),
             Err(e) => write!(f, "<invalid hash: {}>", e),
@@ -240,6 +643,175 @@ impl std::fmt::Display for ImageHash {
     }
 }
 
+/// How far the value behind each bit of a hash was from the threshold it was compared
+/// against, as returned by the `hash_with_confidence` methods of the hashers.
+///
+/// Bits whose value sat close to the threshold are essentially noise and flip on slight
+/// changes like recompression. The margins lie in `[0, 1]`, where `0` is right at the
+/// threshold. They can be turned into a mask for [`ImageHash::distance_masked`] or used
+/// directly as weights for [`ImageHash::distance_weighted`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitMargins {
+    // The margin of each bit, row by row.
+    margins: Vec<f32>,
+
+    // Number of columns.
+    width: u16,
+}
+
+impl BitMargins {
+    /// Thresholds the values into a hash and computes the margin of each bit.
+    ///
+    /// # Arguments
+    /// * `values`: The values behind the bits, row by row
+    /// * `threshold`: The threshold a value has to exceed for its bit to be set
+    /// * `scale`: The margin that is normalized to `1`, larger margins are clamped
+    /// * `width`: Number of columns of the hash
+    /// * `height`: Number of rows of the hash
+    #[cfg(any(feature = "image", test))]
+    pub(crate) fn threshold(
+        values: &[f64],
+        threshold: f64,
+        scale: f64,
+        width: u16,
+        height: u16,
+    ) -> Result<(ImageHash, BitMargins), ImageHashError> {
+        let hash = ImageHash::from_bool_iter(values.iter().map(|&v| v > threshold), width, height)?;
+
+        let margins = values
+            .iter()
+            .map(|&v| {
+                if scale > 0.0 {
+                    ((v - threshold).abs() / scale).min(1.0) as f32
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        Ok((hash, BitMargins { margins, width }))
+    }
+
+    /// The margin of each bit, row by row.
+    pub fn margins(&self) -> &[f32] {
+        &self.margins
+    }
+
+    /// The margin of the bit at the specified position, or `None` if the position is outside
+    /// of the matrix.
+    pub fn get(&self, row: usize, column: usize) -> Option<f32> {
+        let (rows, columns) = self.shape();
+        (row < rows && column < columns).then(|| self.margins[row * columns + column])
+    }
+
+    /// The shape of the matrix of margins, in (number of rows, number of columns).
+    pub fn shape(&self) -> (usize, usize) {
+        (
+            self.margins.len() / self.width as usize,
+            self.width as usize,
+        )
+    }
+
+    /// Creates a mask of the reliable bits, to be passed to [`ImageHash::distance_masked`].
+    ///
+    /// # Arguments
+    /// * `min_margin`: The smallest margin of a bit that is kept in the mask
+    ///
+    /// # Returns
+    /// * A hash of the same shape, where the bits with a margin of at least `min_margin` are set
+    pub fn mask(&self, min_margin: f32) -> ImageHash {
+        ImageHash {
+            data: BitBox::from_iter(self.margins.iter().map(|&m| m >= min_margin)),
+            width: self.width,
+        }
+    }
+}
+
+/// Implements a bitwise operator between two hashes of the same shape, for owned hashes as
+/// well as references.
+macro_rules! impl_bit_op {
+    ($op:ident, $method:ident, $assign:ident, $assign_method:ident) => {
+        impl core::ops::$assign<&ImageHash> for ImageHash {
+            /// # Panics
+            /// If the hashes have different shapes.
+            fn $assign_method(&mut self, rhs: &ImageHash) {
+                assert_eq!(
+                    self.shape(),
+                    rhs.shape(),
+                    "Cannot combine hashes of different shapes"
+                );
+
+                core::ops::$assign::$assign_method(&mut self.data, &*rhs.data);
+            }
+        }
+
+        impl core::ops::$assign for ImageHash {
+            /// # Panics
+            /// If the hashes have different shapes.
+            fn $assign_method(&mut self, rhs: ImageHash) {
+                core::ops::$assign::$assign_method(self, &rhs);
+            }
+        }
+
+        impl core::ops::$op<&ImageHash> for &ImageHash {
+            type Output = ImageHash;
+
+            /// # Panics
+            /// If the hashes have different shapes.
+            fn $method(self, rhs: &ImageHash) -> ImageHash {
+                let mut result = self.clone();
+                core::ops::$assign::$assign_method(&mut result, rhs);
+                result
+            }
+        }
+
+        impl core::ops::$op<&ImageHash> for ImageHash {
+            type Output = ImageHash;
+
+            /// # Panics
+            /// If the hashes have different shapes.
+            fn $method(mut self, rhs: &ImageHash) -> ImageHash {
+                core::ops::$assign::$assign_method(&mut self, rhs);
+                self
+            }
+        }
+
+        impl core::ops::$op for ImageHash {
+            type Output = ImageHash;
+
+            /// # Panics
+            /// If the hashes have different shapes.
+            fn $method(mut self, rhs: ImageHash) -> ImageHash {
+                core::ops::$assign::$assign_method(&mut self, &rhs);
+                self
+            }
+        }
+    };
+}
+
+impl_bit_op!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+impl_bit_op!(BitAnd, bitand, BitAndAssign, bitand_assign);
+impl_bit_op!(BitOr, bitor, BitOrAssign, bitor_assign);
+
+impl core::ops::Not for ImageHash {
+    type Output = ImageHash;
+
+    fn not(mut self) -> ImageHash {
+        // only the bits of the hash are inverted, the padding of the last byte has to stay
+        // clear for the distance
+        let _ = !self.data.as_mut_bitslice();
+        self
+    }
+}
+
+impl core::ops::Not for &ImageHash {
+    type Output = ImageHash;
+
+    fn not(self) -> ImageHash {
+        !self.clone()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -665,6 +1237,34 @@ mod tests {
         assert_eq!(original, decoded);
     }
 
+    #[test]
+    fn test_image_hash_encode_decode_roundtrip_wider_than_u8() {
+        // Arrange: 512x2 = 1024 bits, a width that does not fit into u8
+        let bits = (0..1024).map(|i| i % 3 == 0).collect::<Vec<_>>();
+        let original = ImageHash::from_bool_iter(bits, 512, 2).unwrap();
+
+        // Act
+        let encoded = original.encode().unwrap();
+        let decoded = ImageHash::decode(&encoded, 512, 2).unwrap();
+
+        // Assert
+        assert_eq!(original.shape(), (2, 512));
+        assert_eq!(encoded.len(), 256);
+        assert_eq!(original, decoded);
+    }
+
+    #[test]
+    fn test_image_hash_decoding_with_u8_dimensions() {
+        // Arrange: dimensions coming from code written against the former u8 API
+        let (width, height): (u8, u8) = (4, 4);
+
+        // Act
+        let result = ImageHash::decode("24f0", width, height);
+
+        // Assert
+        assert_eq!(result.unwrap().shape(), (4, 4));
+    }
+
     // DISTANCE (continued)
 
     #[test]
@@ -748,4 +1348,496 @@ mod tests {
         // Act & Assert
         assert!(hash1.distance(&hash2).is_err());
     }
+
+    // ORIENTATION
+
+    /// A 3x2 hash with rows `[1, 0, 0]` and `[1, 1, 0]`.
+    fn asymmetric_hash() -> ImageHash {
+        ImageHash::from_bool_iter(vec![true, false, false, true, true, false], 3, 2).unwrap()
+    }
+
+    #[test]
+    fn test_image_hash_flips() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act
+        let horizontal = hash.flip_horizontal();
+        let vertical = hash.flip_vertical();
+
+        // Assert
+        assert_eq!(
+            horizontal.iter_bool().collect::<Vec<_>>(),
+            vec![false, false, true, false, true, true]
+        );
+        assert_eq!(
+            vertical.iter_bool().collect::<Vec<_>>(),
+            vec![true, true, false, true, false, false]
+        );
+        assert_eq!(horizontal.shape(), (2, 3));
+    }
+
+    #[test]
+    fn test_image_hash_transpose_and_rotate() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act
+        let transposed = hash.transpose();
+        let rotated = hash.rotate90();
+
+        // Assert
+        assert_eq!(transposed.shape(), (3, 2));
+        assert_eq!(
+            transposed.iter_bool().collect::<Vec<_>>(),
+            vec![true, true, false, true, false, false]
+        );
+        // rotating clockwise turns the bottom row into the left column
+        assert_eq!(rotated.shape(), (3, 2));
+        assert_eq!(
+            rotated.iter_bool().collect::<Vec<_>>(),
+            vec![true, true, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn test_image_hash_orientations_compose() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act & Assert
+        assert_eq!(
+            hash.rotate90().rotate90(),
+            hash.orient(Orientation::Rotate180)
+        );
+        assert_eq!(
+            hash.rotate90().rotate90().rotate90(),
+            hash.orient(Orientation::Rotate270)
+        );
+        assert_eq!(hash.rotate90().rotate90().rotate90().rotate90(), hash);
+        assert_eq!(
+            hash.transpose().orient(Orientation::Rotate180),
+            hash.orient(Orientation::Transverse)
+        );
+        assert_eq!(hash.flip_horizontal().flip_horizontal(), hash);
+        assert_eq!(hash.transpose().transpose(), hash);
+        assert_eq!(hash.orient(Orientation::Identity), hash);
+    }
+
+    #[test]
+    fn test_image_hash_min_distance_dihedral() {
+        // Arrange
+        let hash = ImageHash::from_bool_iter(
+            vec![
+                true, true, false, //
+                false, true, false, //
+                false, false, false,
+            ],
+            3,
+            3,
+        )
+        .unwrap();
+
+        for orientation in Orientation::ALL {
+            let other = hash.orient(orientation);
+
+            // Act
+            let (distance, found) = hash.min_distance_dihedral(&other).unwrap();
+
+            // Assert
+            assert_eq!(distance, 0);
+            assert_eq!(hash.orient(found), other);
+        }
+    }
+
+    #[test]
+    fn test_image_hash_min_distance_dihedral_non_square() {
+        // Arrange
+        let hash = asymmetric_hash();
+        let other = hash.orient(Orientation::Rotate180);
+        let transposed = hash.transpose();
+
+        // Act
+        let (distance, orientation) = hash.min_distance_dihedral(&other).unwrap();
+        let (transposed_distance, transposed_orientation) =
+            hash.min_distance_dihedral(&transposed).unwrap();
+
+        // Assert
+        assert_eq!((distance, orientation), (0, Orientation::Rotate180));
+        assert_eq!(transposed_distance, 0);
+        assert!(transposed_orientation.swaps_axes());
+    }
+
+    #[test]
+    fn test_image_hash_min_distance_dihedral_shape_mismatch() {
+        // Arrange
+        let hash = asymmetric_hash();
+        let other = ImageHash::from_bool_iter(vec![false; 4], 2, 2).unwrap();
+
+        // Act
+        let result = hash.min_distance_dihedral(&other);
+
+        // Assert
+        assert!(matches!(result, Err(ImageHashError::ShapeMismatch { .. })));
+    }
+
+    // BIT ACCESS
+
+    #[test]
+    fn test_image_hash_get() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act & Assert
+        assert_eq!(hash.get(0, 0), Some(true));
+        assert_eq!(hash.get(0, 1), Some(false));
+        assert_eq!(hash.get(1, 1), Some(true));
+        assert_eq!(hash.get(1, 2), Some(false));
+        assert_eq!(hash.get(2, 0), None);
+        assert_eq!(hash.get(0, 3), None);
+    }
+
+    #[test]
+    fn test_image_hash_set() {
+        // Arrange
+        let mut hash = asymmetric_hash();
+
+        // Act
+        hash.set(0, 2, true).unwrap();
+        hash.set(1, 0, false).unwrap();
+        let result = hash.set(2, 0, true);
+
+        // Assert
+        assert_eq!(
+            hash.iter_bool().collect::<Vec<_>>(),
+            vec![true, false, true, false, true, false]
+        );
+        assert!(matches!(
+            result,
+            Err(ImageHashError::PositionOutOfBounds {
+                row: 2,
+                column: 0,
+                shape: (2, 3)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_image_hash_count_ones() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act
+        let ones = hash.count_ones();
+
+        // Assert
+        assert_eq!(ones, 3);
+    }
+
+    #[test]
+    fn test_image_hash_rows() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act
+        let rows = hash
+            .rows()
+            .map(|row| row.collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // Assert
+        assert_eq!(
+            rows,
+            vec![vec![true, false, false], vec![true, true, false]]
+        );
+    }
+
+    // BITWISE OPERATIONS
+
+    #[test]
+    fn test_image_hash_bitwise_operations() {
+        // Arrange
+        let a = ImageHash::from_bool_iter(vec![true, true, false, false], 2, 2).unwrap();
+        let b = ImageHash::from_bool_iter(vec![true, false, true, false], 2, 2).unwrap();
+
+        // Act
+        let xor = &a ^ &b;
+        let and = &a & &b;
+        let or = a.clone() | b.clone();
+        let not = !&a;
+
+        // Assert
+        assert_eq!(
+            xor.iter_bool().collect::<Vec<_>>(),
+            vec![false, true, true, false]
+        );
+        assert_eq!(
+            and.iter_bool().collect::<Vec<_>>(),
+            vec![true, false, false, false]
+        );
+        assert_eq!(
+            or.iter_bool().collect::<Vec<_>>(),
+            vec![true, true, true, false]
+        );
+        assert_eq!(
+            not.iter_bool().collect::<Vec<_>>(),
+            vec![false, false, true, true]
+        );
+        assert_eq!(xor.count_ones(), a.distance(&b).unwrap());
+    }
+
+    #[test]
+    fn test_image_hash_bitwise_assign_operations() {
+        // Arrange
+        let mut hash = ImageHash::from_bool_iter(vec![true, true, false, false], 2, 2).unwrap();
+        let other = ImageHash::from_bool_iter(vec![true, false, true, false], 2, 2).unwrap();
+
+        // Act
+        hash ^= &other;
+        hash |= other.clone();
+        hash &= &other;
+
+        // Assert
+        assert_eq!(hash, other);
+    }
+
+    #[test]
+    fn test_image_hash_not_keeps_padding_clear() {
+        // Arrange: 6 bits leave 2 padding bits in the single byte of the hash
+        let hash = asymmetric_hash();
+        let zeros = ImageHash::from_bool_iter(vec![false; 6], 3, 2).unwrap();
+
+        // Act
+        let inverted = !&hash;
+
+        // Assert
+        assert_eq!(inverted.count_ones(), 3);
+        assert_eq!(inverted.distance(&zeros).unwrap(), 3);
+        assert_eq!(inverted.distance(&hash).unwrap(), 6);
+        assert_eq!(!inverted, hash);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot combine hashes of different shapes")]
+    fn test_image_hash_bitwise_operation_with_different_shapes() {
+        // Arrange
+        let hash = asymmetric_hash();
+        let other = hash.transpose();
+
+        // Act
+        let _ = &hash ^ &other;
+    }
+
+    // MASKED AND WEIGHTED DISTANCE
+
+    #[test]
+    fn test_image_hash_distance_masked() {
+        // Arrange
+        let a = ImageHash::from_bool_iter(vec![true, true, false, false], 2, 2).unwrap();
+        let b = ImageHash::from_bool_iter(vec![true, false, true, false], 2, 2).unwrap();
+        let mask = ImageHash::from_bool_iter(vec![true, true, false, true], 2, 2).unwrap();
+
+        // Act
+        let (distance, similarity) = a.distance_masked(&b, &mask).unwrap();
+
+        // Assert
+        assert_eq!(distance, 1);
+        assert!((similarity - 2.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_image_hash_distance_masked_with_full_mask() {
+        // Arrange
+        let a = asymmetric_hash();
+        let b = a.flip_horizontal();
+        let mask = !ImageHash::from_bool_iter(vec![false; 6], 3, 2).unwrap();
+
+        // Act
+        let (distance, similarity) = a.distance_masked(&b, &mask).unwrap();
+
+        // Assert
+        assert_eq!(distance, a.distance(&b).unwrap());
+        assert!((similarity - (1.0 - distance as f64 / 6.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_image_hash_distance_masked_with_empty_mask() {
+        // Arrange
+        let a = asymmetric_hash();
+        let mask = ImageHash::from_bool_iter(vec![false; 6], 3, 2).unwrap();
+
+        // Act
+        let result = a.distance_masked(&a, &mask);
+
+        // Assert
+        assert!(matches!(result, Err(ImageHashError::NothingToCompare)));
+    }
+
+    #[test]
+    fn test_image_hash_distance_masked_with_different_mask_shape() {
+        // Arrange
+        let a = asymmetric_hash();
+        let mask = a.transpose();
+
+        // Act
+        let result = a.distance_masked(&a, &mask);
+
+        // Assert
+        assert!(matches!(result, Err(ImageHashError::ShapeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_image_hash_distance_weighted() {
+        // Arrange
+        let a = ImageHash::from_bool_iter(vec![true, true, false, false], 2, 2).unwrap();
+        let b = ImageHash::from_bool_iter(vec![true, false, true, false], 2, 2).unwrap();
+        let weights = [1.0, 0.5, 2.0, 0.5];
+
+        // Act
+        let (distance, similarity) = a.distance_weighted(&b, &weights).unwrap();
+
+        // Assert
+        assert!((distance - 2.5).abs() < 1e-12);
+        assert!((similarity - 0.375).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_image_hash_distance_weighted_with_uniform_weights() {
+        // Arrange
+        let a = asymmetric_hash();
+        let b = a.flip_vertical();
+
+        // Act
+        let (distance, similarity) = a.distance_weighted(&b, &[1.0; 6]).unwrap();
+
+        // Assert
+        assert_eq!(distance, a.distance(&b).unwrap() as f64);
+        assert!((similarity - (1.0 - distance / 6.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_image_hash_distance_weighted_with_invalid_weights() {
+        // Arrange
+        let a = asymmetric_hash();
+
+        // Act
+        let too_few = a.distance_weighted(&a, &[1.0; 5]);
+        let negative = a.distance_weighted(&a, &[1.0, 1.0, -1.0, 1.0, 1.0, 1.0]);
+        let nan = a.distance_weighted(&a, &[f32::NAN; 6]);
+        let zero = a.distance_weighted(&a, &[0.0; 6]);
+
+        // Assert
+        assert!(matches!(
+            too_few,
+            Err(ImageHashError::MatrixSizeMismatch {
+                expected: 6,
+                actual: 5
+            })
+        ));
+        assert!(matches!(
+            negative,
+            Err(ImageHashError::InvalidParameter {
+                name: "weights",
+                ..
+            })
+        ));
+        assert!(matches!(nan, Err(ImageHashError::InvalidParameter { .. })));
+        assert!(matches!(zero, Err(ImageHashError::NothingToCompare)));
+    }
+
+    // MARGINS
+
+    #[test]
+    fn test_bit_margins_threshold() {
+        // Arrange
+        let values = [0.0, 40.0, 50.0, 100.0, 60.0, 45.0];
+
+        // Act
+        let (hash, margins) = BitMargins::threshold(&values, 50.0, 50.0, 3, 2).unwrap();
+
+        // Assert
+        assert_eq!(
+            hash.iter_bool().collect::<Vec<_>>(),
+            vec![false, false, false, true, true, false]
+        );
+        assert_eq!(margins.shape(), (2, 3));
+        assert_eq!(margins.margins(), &[1.0, 0.2, 0.0, 1.0, 0.2, 0.1]);
+        assert_eq!(margins.get(1, 2), Some(0.1));
+        assert_eq!(margins.get(2, 0), None);
+    }
+
+    #[test]
+    fn test_bit_margins_threshold_clamps_to_scale() {
+        // Arrange
+        let values = [0.0, 200.0, 20.0, 15.0];
+
+        // Act
+        let (_, margins) = BitMargins::threshold(&values, 10.0, 20.0, 2, 2).unwrap();
+
+        // Assert
+        assert_eq!(margins.margins(), &[0.5, 1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_bit_margins_threshold_with_zero_scale() {
+        // Act
+        let (_, margins) = BitMargins::threshold(&[7.0; 4], 7.0, 0.0, 2, 2).unwrap();
+
+        // Assert
+        assert_eq!(margins.margins(), &[0.0; 4]);
+    }
+
+    #[test]
+    fn test_bit_margins_mask() {
+        // Arrange
+        let values = [0.0, 40.0, 50.0, 100.0, 60.0, 45.0];
+        let (hash, margins) = BitMargins::threshold(&values, 50.0, 50.0, 3, 2).unwrap();
+        let other =
+            ImageHash::from_bool_iter(vec![true, true, true, true, true, true], 3, 2).unwrap();
+
+        // Act
+        let mask = margins.mask(0.2);
+
+        // Assert
+        assert_eq!(
+            mask.iter_bool().collect::<Vec<_>>(),
+            vec![true, true, false, true, true, false]
+        );
+        assert_eq!(hash.distance(&other).unwrap(), 4);
+        assert_eq!(hash.distance_masked(&other, &mask).unwrap().0, 2);
+    }
+
+    // SIMILARITY
+
+    #[test]
+    fn test_image_hash_similarity() {
+        // Arrange
+        let hash = asymmetric_hash();
+        let flipped = hash.flip_horizontal();
+
+        // Act
+        let same = hash.similarity(&hash).unwrap();
+        let inverted = hash.similarity(&!&hash).unwrap();
+        let similarity = hash.similarity(&flipped).unwrap();
+
+        // Assert
+        assert_eq!(same, 1.0);
+        assert_eq!(inverted, 0.0);
+        assert_eq!(
+            similarity,
+            1.0 - hash.distance(&flipped).unwrap() as f64 / 6.0
+        );
+    }
+
+    #[test]
+    fn test_image_hash_similarity_with_different_shapes() {
+        // Arrange
+        let hash = asymmetric_hash();
+
+        // Act
+        let result = hash.similarity(&hash.transpose());
+
+        // Assert
+        assert!(matches!(result, Err(ImageHashError::ShapeMismatch { .. })));
+    }
 }
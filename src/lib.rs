@@ -13,6 +13,8 @@
 //! ## Quick start
 //!
 //! ```no_run
+//! # #[cfg(feature = "image")]
+//! # fn main() {
 //! use std::path::Path;
 //! use imghash::average_hash;
 //!
@@ -28,6 +30,9 @@
 //! // Compare two hashes
 //! let distance = hash.distance(&decoded).unwrap();
 //! assert_eq!(distance, 0);
+//! # }
+//! # #[cfg(not(feature = "image"))]
+//! # fn main() {}
 //! ```
 //!
 //! ## Custom hashers
@@ -35,17 +40,52 @@
 //! For more control over hash dimensions and color space, use the hasher structs directly:
 //!
 //! ```no_run
+//! # #[cfg(feature = "image")]
+//! # fn main() {
 //! use std::path::Path;
 //! use imghash::{average::AverageHasher, ColorSpace, ImageHasher};
 //!
 //! let hasher = AverageHasher::new(16, 16, ColorSpace::REC601).unwrap();
 //! let hash = hasher.hash_from_path(Path::new("path/to/image.png")).unwrap();
+//! # }
+//! # #[cfg(not(feature = "image"))]
+//! # fn main() {}
 //! ```
+//!
+//! ## Features
+//!
+//! - `image` (default): Loading, decoding and hashing images through the `image` crate.
+//!   Without it, hashes can still be computed from raw pixel buffers.
+//! - `std` (default, implied by `image`): Use the standard library. Without it the crate is
+//!   `no_std` and only depends on `alloc`, which requires the `libm` feature for float math.
+//! - `libm`: Use `libm` for float math in `no_std` builds.
+//! - `rayon`: Parallel hashing of many files in the [`batch`] module.
+//! - `cli`: The `imghash` command-line binary for hashing, comparing and finding duplicates.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("either the `std` or the `libm` feature has to be enabled for float math");
 
+extern crate alloc;
+
+#[cfg(feature = "image")]
 use average::AverageHasher;
+#[cfg(feature = "image")]
+pub(crate) use decode::{open_image, read_image};
+#[cfg(feature = "image")]
 use difference::DifferenceHasher;
+#[cfg(not(any(feature = "std", test)))]
+use float::Float;
+#[cfg(feature = "image")]
+use image::ImageFormat;
+#[cfg(feature = "image")]
 use median::MedianHasher;
+#[cfg(feature = "image")]
 use perceptual::PerceptualHasher;
+#[cfg(feature = "image")]
+use std::io::{BufRead, Cursor, Seek};
+#[cfg(feature = "image")]
 use std::path::Path;
 
 /// Trait for generating image hashes
@@ -59,13 +99,151 @@ pub trait ImageHasher {
     /// # Returns
     ///
     /// The generated image hash.
+    #[cfg(feature = "image")]
     fn hash_from_path(&self, path: &Path) -> Result<ImageHash, ImageHashError> {
-        let img = image::ImageReader::open(path)
-            .map_err(|e| ImageHashError::IoError {
-                source: e,
-                path: path.to_path_buf(),
-            })?
-            .decode()?;
+        self.hash_from_path_with_options(path, DecodeOptions::STORED)
+    }
+
+    /// Generates a hash for an image specified by its file path, decoding it with the given
+    /// [`DecodeOptions`]. Unlike [`ImageHasher::hash_from_path`], the default options apply the
+    /// EXIF orientation of the image before hashing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the image file.
+    /// * `options` - The options for decoding the image.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_path_with_options(
+        &self,
+        path: &Path,
+        options: DecodeOptions,
+    ) -> Result<ImageHash, ImageHashError> {
+        let img = open_image(path, options)?;
+        self.hash_from_img(&img)
+    }
+
+    /// Generates a hash for an encoded image held in memory, guessing its format
+    /// from the leading bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded image, for example the contents of a PNG file.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_bytes(&self, bytes: &[u8]) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_bytes_with_options(bytes, DecodeOptions::STORED)
+    }
+
+    /// Generates a hash for an encoded image held in memory with an explicit format.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded image, for example the contents of a PNG file.
+    /// * `format` - The format the image is encoded in.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_bytes_with_format(
+        &self,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_bytes_with_options(bytes, DecodeOptions::STORED.format(format))
+    }
+
+    /// Generates a hash for an encoded image held in memory, decoding it with the given
+    /// [`DecodeOptions`]. Unlike [`ImageHasher::hash_from_bytes`], the default options apply the
+    /// EXIF orientation of the image before hashing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded image, for example the contents of a JPEG file.
+    /// * `options` - The options for decoding the image.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_bytes_with_options(
+        &self,
+        bytes: &[u8],
+        options: DecodeOptions,
+    ) -> Result<ImageHash, ImageHashError> {
+        let img = read_image(Cursor::new(bytes), options)?;
+        self.hash_from_img(&img)
+    }
+
+    /// Generates a hash for an encoded image read from the given reader, guessing its
+    /// format from the leading bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader yielding the encoded image.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_reader<R: BufRead + Seek>(&self, reader: R) -> Result<ImageHash, ImageHashError>
+    where
+        Self: Sized,
+    {
+        self.hash_from_reader_with_options(reader, DecodeOptions::STORED)
+    }
+
+    /// Generates a hash for an encoded image read from the given reader with an explicit format.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader yielding the encoded image.
+    /// * `format` - The format the image is encoded in.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_reader_with_format<R: BufRead + Seek>(
+        &self,
+        reader: R,
+        format: ImageFormat,
+    ) -> Result<ImageHash, ImageHashError>
+    where
+        Self: Sized,
+    {
+        self.hash_from_reader_with_options(reader, DecodeOptions::STORED.format(format))
+    }
+
+    /// Generates a hash for an encoded image read from the given reader, decoding it with the
+    /// given [`DecodeOptions`]. Unlike [`ImageHasher::hash_from_reader`], the default options
+    /// apply the EXIF orientation of the image before hashing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader yielding the encoded image.
+    /// * `options` - The options for decoding the image.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_reader_with_options<R: BufRead + Seek>(
+        &self,
+        reader: R,
+        options: DecodeOptions,
+    ) -> Result<ImageHash, ImageHashError>
+    where
+        Self: Sized,
+    {
+        let img = read_image(reader, options)?;
         self.hash_from_img(&img)
     }
 
@@ -78,7 +256,333 @@ pub trait ImageHasher {
     /// # Returns
     ///
     /// The generated image hash.
+    #[cfg(feature = "image")]
     fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError>;
+
+    /// Generates a hash for a given image after trimming its near-uniform borders, like
+    /// letterbox bars or whitespace padding.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The image to generate the hash for.
+    /// * `trim` - The options for detecting the borders.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash together with the detected [`CropRect`] of the content,
+    /// which is the whole image if no borders were found.
+    #[cfg(feature = "image")]
+    fn hash_from_img_trimmed(
+        &self,
+        img: &image::DynamicImage,
+        trim: BorderTrim,
+    ) -> Result<(ImageHash, CropRect), ImageHashError> {
+        let crop = trim.detect(img);
+
+        if (crop.width, crop.height) == (img.width(), img.height()) {
+            return Ok((self.hash_from_img(img)?, crop));
+        }
+
+        let cropped = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        Ok((self.hash_from_img(&cropped)?, crop))
+    }
+
+    /// Generates a hash for a given image, reusing the buffers of a [`HashContext`].
+    ///
+    /// Hashing many images with the same context avoids allocating the intermediate
+    /// buffers over and over again. The hashes are identical to [`ImageHasher::hash_from_img`].
+    ///
+    /// The default implementation grayscales and resizes the image according to
+    /// [`ImageHasher::resize_target`] and passes it on to [`ImageHasher::hash_from_resized`].
+    /// Images with more than 8 bits per channel are passed on to
+    /// [`ImageHasher::hash_from_resized_f32`] instead, without quantizing them to 8 bits.
+    /// Hashers without a resize target ignore the context and fall back to
+    /// [`ImageHasher::hash_from_img`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context whose buffers are reused.
+    /// * `img` - The image to generate the hash for.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    #[cfg(feature = "image")]
+    fn hash_from_img_with(
+        &self,
+        ctx: &mut HashContext,
+        img: &image::DynamicImage,
+    ) -> Result<ImageHash, ImageHashError> {
+        let Some(target) = self.resize_target() else {
+            return self.hash_from_img(img);
+        };
+
+        // the conversion buffers are taken out of the context for the duration of the
+        // hash, so the hasher is free to use the remaining buffers of the context
+        let mut buffers = core::mem::take(&mut ctx.convert);
+
+        // high bit depth images are not quantized to 8 bits, so no precision is lost
+        let hash = match imageops::convert_target(img, &target, &mut buffers) {
+            imageops::Converted::Luma8(pixels) => self.hash_from_resized(ctx, pixels),
+            imageops::Converted::LumaF32(pixels) => self.hash_from_resized_f32(ctx, pixels),
+        };
+
+        ctx.convert = buffers;
+
+        hash
+    }
+
+    /// Generates a hash for a raw buffer of 8-bit luma values, for example the Y plane
+    /// of a YUV420 or NV12 camera frame. No color conversion is applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - The luma values, row by row.
+    /// * `width` - The width of the image in pixels.
+    /// * `height` - The height of the image in pixels.
+    /// * `stride` - The number of bytes between the starts of two consecutive rows.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    fn hash_from_luma(
+        &self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<ImageHash, ImageHashError> {
+        let img = RawImage::new(pixels, width, height, stride, PixelLayout::Luma)?;
+        self.hash_from_raw(&img)
+    }
+
+    /// Generates a hash for a raw pixel buffer with a declared [`PixelLayout`].
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The raw image to generate the hash for.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    fn hash_from_raw(&self, img: &RawImage) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_raw_with(&mut HashContext::new(), img)
+    }
+
+    /// Generates a hash for a raw pixel buffer, reusing the buffers of a [`HashContext`].
+    ///
+    /// The default implementation grayscales and resizes the raw pixels according to
+    /// [`ImageHasher::resize_target`] and passes them on to [`ImageHasher::hash_from_resized`].
+    /// Hashers without a resize target are given a copy of the pixels as a decoded image,
+    /// which requires the `image` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context whose buffers are reused.
+    /// * `img` - The raw image to generate the hash for.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    fn hash_from_raw_with(
+        &self,
+        ctx: &mut HashContext,
+        img: &RawImage,
+    ) -> Result<ImageHash, ImageHashError> {
+        let Some(target) = self.resize_target() else {
+            #[cfg(feature = "image")]
+            return self.hash_from_img(&img.to_dynamic_image());
+
+            #[cfg(not(feature = "image"))]
+            return Err(ImageHashError::ResizedNotSupported);
+        };
+
+        // see `hash_from_img_with` for why the buffers are taken out of the context
+        let mut buffers = core::mem::take(&mut ctx.convert);
+        let pixels = imageops::convert_raw(
+            img,
+            target.width,
+            target.height,
+            target.color_space,
+            target.alpha,
+            target.normalization,
+            &mut buffers,
+        );
+
+        let hash = self.hash_from_resized(ctx, pixels);
+        ctx.convert = buffers;
+
+        hash
+    }
+
+    /// The size and color space of the grayscaled image this hasher operates on.
+    ///
+    /// Returns `None` if the hasher does not operate on a grayscaled and resized image,
+    /// in which case [`ImageHasher::hash_from_resized`] is not supported. The default
+    /// implementation returns `None`.
+    fn resize_target(&self) -> Option<ResizeTarget> {
+        None
+    }
+
+    /// Generates a hash for an image that was already grayscaled and resized
+    /// according to [`ImageHasher::resize_target`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context whose buffers are reused.
+    /// * `pixels` - The row-major luma values of the resized image.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    fn hash_from_resized(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[u8],
+    ) -> Result<ImageHash, ImageHashError> {
+        let _ = (ctx, pixels);
+        Err(ImageHashError::ResizedNotSupported)
+    }
+
+    /// Generates a hash for an image that already is grayscaled and resized to
+    /// [`ImageHasher::resize_target`], holding luma values with full precision.
+    ///
+    /// This is used for images with more than 8 bits per channel. The default implementation
+    /// rounds the values to 8 bits and passes them on to [`ImageHasher::hash_from_resized`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context whose buffers are reused.
+    /// * `pixels` - The row-major luma values of the resized image, where `255.0` is white.
+    ///   High dynamic range images may exceed that value.
+    ///
+    /// # Returns
+    ///
+    /// The generated image hash.
+    fn hash_from_resized_f32(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[f32],
+    ) -> Result<ImageHash, ImageHashError> {
+        let pixels = pixels
+            .iter()
+            .map(|&value| value.clamp(0.0, 255.0).round() as u8)
+            .collect::<alloc::vec::Vec<_>>();
+
+        self.hash_from_resized(ctx, &pixels)
+    }
+
+    /// A short name identifying the hash algorithm, for example `"phash"`.
+    ///
+    /// The default implementation returns the type name of the hasher.
+    fn name(&self) -> &str {
+        core::any::type_name::<Self>()
+    }
+
+    /// The algorithm and parameters of the hasher, if it is one of the hashers of this crate.
+    /// Its string representation is the canonical spec string, which can be stored alongside
+    /// the hashes and turned back into the same hasher with [`spec::hasher_from_spec`].
+    ///
+    /// The default implementation returns `None`.
+    fn spec(&self) -> Option<HasherSpec> {
+        None
+    }
+
+    /// The largest share of bits in `[0, 1]` that may differ between two hashes of this hasher
+    /// for them to count as near duplicates under the preset. See [`Preset`] for how the
+    /// thresholds were calibrated.
+    ///
+    /// The default implementation uses the thresholds of the algorithm reported by
+    /// [`ImageHasher::spec`], and those of the average hash if there is none.
+    fn threshold(&self, preset: Preset) -> f64 {
+        let algorithm = self
+            .spec()
+            .map_or(HashAlgorithm::Average, |spec| spec.algorithm);
+
+        preset.threshold(algorithm)
+    }
+
+    /// Checks whether two hashes generated by this hasher belong to near duplicate images.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - The hash of the first image.
+    /// * `b` - The hash of the second image.
+    /// * `preset` - How similar the hashes have to be.
+    ///
+    /// # Returns
+    ///
+    /// * Whether the share of differing bits is at most [`ImageHasher::threshold`] of the preset.
+    /// * An [`ImageHashError::ShapeMismatch`] if the hashes have different shapes.
+    fn is_near_duplicate(
+        &self,
+        a: &ImageHash,
+        b: &ImageHash,
+        preset: Preset,
+    ) -> Result<bool, ImageHashError> {
+        let distance = a.distance(b)?;
+        let (rows, columns) = a.shape();
+
+        Ok(distance as f64 <= self.threshold(preset) * (rows * columns) as f64)
+    }
+}
+
+/// Boxed hashers, like the ones built by [`spec::hasher_from_spec`], forward to the hasher
+/// they hold, so they can be passed on wherever a hasher is expected.
+impl<H: ImageHasher + ?Sized> ImageHasher for alloc::boxed::Box<H> {
+    #[cfg(feature = "image")]
+    fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError> {
+        (**self).hash_from_img(img)
+    }
+
+    #[cfg(feature = "image")]
+    fn hash_from_img_with(
+        &self,
+        ctx: &mut HashContext,
+        img: &image::DynamicImage,
+    ) -> Result<ImageHash, ImageHashError> {
+        (**self).hash_from_img_with(ctx, img)
+    }
+
+    fn hash_from_raw_with(
+        &self,
+        ctx: &mut HashContext,
+        img: &RawImage,
+    ) -> Result<ImageHash, ImageHashError> {
+        (**self).hash_from_raw_with(ctx, img)
+    }
+
+    fn resize_target(&self) -> Option<ResizeTarget> {
+        (**self).resize_target()
+    }
+
+    fn hash_from_resized(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[u8],
+    ) -> Result<ImageHash, ImageHashError> {
+        (**self).hash_from_resized(ctx, pixels)
+    }
+
+    fn hash_from_resized_f32(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[f32],
+    ) -> Result<ImageHash, ImageHashError> {
+        (**self).hash_from_resized_f32(ctx, pixels)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn spec(&self) -> Option<HasherSpec> {
+        (**self).spec()
+    }
+
+    fn threshold(&self, preset: Preset) -> f64 {
+        (**self).threshold(preset)
+    }
 }
 
 /// Calculate the average hash for an image at the specified path. Uses the default
@@ -91,6 +595,7 @@ pub trait ImageHasher {
 /// # Returns
 /// * An [`ImageHash`]-struct that can be encoded into a string representation
 /// * An [`ImageHashError`] if something went wrong while loading the image
+#[cfg(feature = "image")]
 pub fn average_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
     // create the hasher
     let hasher = AverageHasher::default();
@@ -108,6 +613,7 @@ pub fn average_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
 /// # Returns
 /// * An [`ImageHash`]-struct that can be encoded into a string representation
 /// * An [`ImageHashError`] if something went wrong while loading the image
+#[cfg(feature = "image")]
 pub fn median_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
     // create the hasher
     let hasher = MedianHasher::default();
@@ -125,6 +631,7 @@ pub fn median_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
 /// # Returns
 /// * An [`ImageHash`]-struct that can be encoded into a string representation
 /// * An [`ImageHashError`] if something went wrong while loading the image
+#[cfg(feature = "image")]
 pub fn difference_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
     // create the hasher
     let hasher = DifferenceHasher::default();
@@ -142,6 +649,7 @@ pub fn difference_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
 /// # Returns
 /// * An [`ImageHash`]-struct that can be encoded into a string representation
 /// * An [`ImageHashError`] if something went wrong while loading the image
+#[cfg(feature = "image")]
 pub fn perceptual_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
     // create the hasher
     let hasher = PerceptualHasher::default();
@@ -151,21 +659,54 @@ pub fn perceptual_hash(path: &Path) -> Result<ImageHash, ImageHashError> {
 
 // public modules
 pub mod average;
+#[cfg(feature = "rayon")]
+pub mod batch;
 pub mod difference;
+#[cfg(feature = "image")]
+pub mod eval;
 pub mod median;
+#[cfg(feature = "image")]
+pub mod multi;
 pub mod perceptual;
+pub mod spec;
+#[cfg(feature = "image")]
+pub mod trim;
 
 // private modules
+mod context;
+#[cfg(feature = "image")]
+mod decode;
+#[cfg(not(any(feature = "std", test)))]
+mod float;
 mod imageops;
 mod imghash;
 mod math;
+mod preset;
 
 // public exports
+pub use crate::context::HashContext;
+#[cfg(feature = "image")]
+pub use crate::decode::DecodeOptions;
+pub use crate::imageops::AlphaPolicy;
 pub use crate::imageops::ColorSpace;
+pub use crate::imageops::Normalization;
+pub use crate::imageops::PixelLayout;
+pub use crate::imageops::RawImage;
+pub use crate::imageops::ResizeTarget;
+pub use crate::imageops::ToneMapping;
+pub use crate::imghash::BitMargins;
 pub use crate::imghash::ImageHash;
 pub use crate::imghash::ImageHashError;
-
-#[cfg(test)]
+pub use crate::imghash::Orientation;
+pub use crate::preset::Preset;
+pub use crate::spec::HashAlgorithm;
+pub use crate::spec::HasherSpec;
+#[cfg(feature = "image")]
+pub use crate::trim::BorderTrim;
+#[cfg(feature = "image")]
+pub use crate::trim::CropRect;
+
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use super::*;
 
@@ -271,4 +812,163 @@ mod tests {
         // Assert
         assert!(hash.is_err());
     }
+
+    #[test]
+    fn test_hash_from_bytes_with_unknown_format() {
+        // Arrange
+        let bytes = std::fs::read(TXT_FILE).unwrap();
+
+        // Act
+        let hash = AverageHasher::default().hash_from_bytes(&bytes);
+
+        // Assert
+        assert!(matches!(hash, Err(ImageHashError::UnknownFormat)));
+    }
+
+    #[test]
+    fn test_hash_from_bytes_with_wrong_format() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+
+        // Act
+        let hash = AverageHasher::default().hash_from_bytes_with_format(&bytes, ImageFormat::Jpeg);
+
+        // Assert
+        assert!(matches!(hash, Err(ImageHashError::ImageError(_))));
+    }
+
+    #[test]
+    fn test_hash_from_bytes_with_truncated_image() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+
+        // Act
+        let hash = AverageHasher::default().hash_from_bytes(&bytes[..bytes.len() / 2]);
+
+        // Assert
+        assert!(matches!(hash, Err(ImageHashError::ImageError(_))));
+    }
+
+    #[test]
+    fn test_hash_from_reader_with_guessed_format() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+
+        // Act
+        let hash = PerceptualHasher::default().hash_from_reader(Cursor::new(bytes));
+
+        // Assert
+        assert_eq!(hash.unwrap().encode().unwrap(), "acdbe86135344e3a");
+    }
+
+    #[test]
+    fn test_is_near_duplicate_across_algorithms_and_sizes() {
+        // Arrange
+        let img = image::open(TEST_IMG).unwrap();
+        let mut jpeg = Vec::new();
+        img.to_rgb8()
+            .write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(
+                &mut jpeg, 50,
+            ))
+            .unwrap();
+        let copy = image::load_from_memory(&jpeg).unwrap().brighten(10);
+        let distinct = image::open("./data/img/logo-white.png").unwrap();
+
+        for size in [8, 16] {
+            let hashers: Vec<Box<dyn ImageHasher>> = vec![
+                Box::new(AverageHasher::builder().size(size, size).build().unwrap()),
+                Box::new(MedianHasher::builder().size(size, size).build().unwrap()),
+                Box::new(
+                    DifferenceHasher::builder()
+                        .size(size, size)
+                        .build()
+                        .unwrap(),
+                ),
+                Box::new(
+                    PerceptualHasher::builder()
+                        .size(size, size)
+                        .build()
+                        .unwrap(),
+                ),
+            ];
+
+            for hasher in hashers {
+                // Act
+                let original = hasher.hash_from_img(&img).unwrap();
+                let copy = hasher.hash_from_img(&copy).unwrap();
+                let distinct = hasher.hash_from_img(&distinct).unwrap();
+
+                // Assert
+                for preset in Preset::ALL {
+                    assert!(
+                        hasher.is_near_duplicate(&original, &copy, preset).unwrap(),
+                        "{} {}x{} {:?}",
+                        hasher.name(),
+                        size,
+                        size,
+                        preset
+                    );
+                    assert!(!hasher
+                        .is_near_duplicate(&original, &distinct, preset)
+                        .unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_near_duplicate_uses_threshold_of_hasher() {
+        // Arrange
+        let ahash = AverageHasher::default();
+        let phash = PerceptualHasher::default();
+        let zeros = ImageHash::from_bool_iter(vec![false; 64], 8, 8).unwrap();
+        let mut other = zeros.clone();
+        for column in 0..8 {
+            other.set(0, column, true).unwrap();
+        }
+
+        // Act
+        let average = ahash.is_near_duplicate(&zeros, &other, Preset::Default);
+        let perceptual = phash.is_near_duplicate(&zeros, &other, Preset::Default);
+
+        // Assert: 8 of 64 bits is exactly the default threshold of the average hash
+        assert_eq!(ahash.threshold(Preset::Default), 0.125);
+        assert!(average.unwrap());
+        assert!(perceptual.unwrap());
+        assert!(!ahash
+            .is_near_duplicate(&zeros, &other, Preset::Strict)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_is_near_duplicate_with_different_shapes() {
+        // Arrange
+        let hasher = AverageHasher::default();
+        let a = ImageHash::from_bool_iter(vec![false; 64], 8, 8).unwrap();
+        let b = ImageHash::from_bool_iter(vec![false; 256], 16, 16).unwrap();
+
+        // Act
+        let result = hasher.is_near_duplicate(&a, &b, Preset::Default);
+
+        // Assert
+        assert!(matches!(result, Err(ImageHashError::ShapeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_boxed_hasher_forwards_to_inner_hasher() {
+        // Arrange
+        let boxed = spec::hasher_from_spec("ahash").unwrap();
+        let path = Path::new(TEST_IMG);
+
+        // Act
+        let hash = boxed.hash_from_path(path).unwrap();
+
+        // Assert
+        assert_eq!(boxed.name(), "ahash");
+        assert_eq!(
+            boxed.resize_target(),
+            AverageHasher::default().resize_target()
+        );
+        assert_eq!(hash.encode().unwrap(), "ffffff0e00000301");
+    }
 }
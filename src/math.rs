@@ -1,3 +1,11 @@
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+
+// The full in-place DCT is no longer used for hashing, but it is kept as the reference
+// implementation that `dct2_2d_truncated` is verified against in the tests.
+#[cfg(test)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, Default)]
 pub enum Axis {
     #[default]
@@ -5,6 +13,32 @@ pub enum Axis {
     Column,
 }
 
+/// Computes a single DCT Type-II coefficient `k` over `n` strided elements of `input`.
+///
+/// Each coefficient is a weighted sum of all input values, where the weights
+/// are cosine basis functions at increasing frequencies. Both the full and the
+/// truncated DCT go through this function, so they produce bit-identical values.
+fn dct2_coefficient(input: &[f64], skip: usize, n: usize, k: usize) -> f64 {
+    2.0 * input
+        // chunks(skip) gives us windows of `skip` elements; we only use
+        // the first element of each chunk (x[0]), effectively stepping
+        // through the array with the given stride.
+        .chunks(skip)
+        .enumerate()
+        .map(|(i, x)| {
+            // cos(pi * k * (2i+1) / 2N) is the DCT-II basis function.
+            // - k selects the frequency (0 = DC / average, higher = finer detail)
+            // - i is the position of the current input sample
+            let numerator = core::f64::consts::PI * k as f64 * (2 * i + 1) as f64;
+            let denominator = (2 * n) as f64;
+
+            let cosine = (numerator / denominator).cos();
+
+            x[0] * cosine
+        })
+        .sum::<f64>()
+}
+
 /// Computes the DCT Type-II for a given slice of floats in-place.
 ///
 /// The Discrete Cosine Transform (DCT) converts spatial data (like pixel values)
@@ -27,6 +61,7 @@ pub enum Axis {
 /// * `skip`: Stride between elements. Use `1` for contiguous (row-wise) data, or
 ///   `width` to step through a single column of a row-major matrix.
 /// * `buf`: Temporary buffer for intermediate results. Must be at least N elements long.
+#[cfg(test)]
 pub fn dct2_in_place(input: &mut [f64], skip: usize, buf: &mut [f64]) {
     // Internal invariant: all callers control `skip` directly (1 for rows, `width` for columns).
     // A zero skip is a programming bug, not a recoverable error.
@@ -46,29 +81,8 @@ pub fn dct2_in_place(input: &mut [f64], skip: usize, buf: &mut [f64]) {
     assert!(n <= buf.len(), "buffer is too small for the DCT result");
 
     // For each output frequency index k, compute the DCT coefficient.
-    // Each coefficient is a weighted sum of all input values, where the weights
-    // are cosine basis functions at increasing frequencies.
     (0..n)
-        .map(|k| {
-            2.0 * input
-                // chunks(skip) gives us windows of `skip` elements; we only use
-                // the first element of each chunk (x[0]), effectively stepping
-                // through the array with the given stride.
-                .chunks(skip)
-                .enumerate()
-                .map(|(i, x)| {
-                    // cos(pi * k * (2i+1) / 2N) is the DCT-II basis function.
-                    // - k selects the frequency (0 = DC / average, higher = finer detail)
-                    // - i is the position of the current input sample
-                    let numerator = std::f64::consts::PI * k as f64 * (2 * i + 1) as f64;
-                    let denominator = (2 * n) as f64;
-
-                    let cosine = (numerator / denominator).cos();
-
-                    x[0] * cosine
-                })
-                .sum::<f64>()
-        })
+        .map(|k| dct2_coefficient(input, skip, n, k))
         .enumerate()
         .for_each(|(i, value)| buf[i] = value);
 
@@ -95,6 +109,7 @@ pub fn dct2_in_place(input: &mut [f64], skip: usize, buf: &mut [f64]) {
 /// * `axis`: Which direction to apply the DCT:
 ///   - `Axis::Row`: transform each row independently (left-to-right frequencies).
 ///   - `Axis::Column`: transform each column independently (top-to-bottom frequencies).
+#[cfg(test)]
 pub fn dct2_over_matrix_in_place(input: &mut [f64], width: usize, axis: Axis) {
     if input.is_empty() || width == 0 {
         return;
@@ -104,7 +119,7 @@ pub fn dct2_over_matrix_in_place(input: &mut [f64], width: usize, axis: Axis) {
         Axis::Row => {
             // Process each row as a contiguous slice of `width` elements.
             // skip=1 because elements within a row are adjacent in memory.
-            let buf = &mut vec![0.0; width];
+            let buf = &mut alloc::vec![0.0; width];
             for row in input.chunks_mut(width) {
                 dct2_in_place(row, 1, buf);
             }
@@ -113,7 +128,7 @@ pub fn dct2_over_matrix_in_place(input: &mut [f64], width: usize, axis: Axis) {
             // To process a column in a row-major layout, we start at the column's
             // index (n) and skip `width` elements to reach the next row's value in
             // the same column. The `skip` parameter of dct2_in_place handles this stride.
-            let buf = &mut vec![0.0; input.len() / width];
+            let buf = &mut alloc::vec![0.0; input.len() / width];
             for n in 0..width {
                 dct2_in_place(&mut input[n..], width, buf);
             }
@@ -121,28 +136,83 @@ pub fn dct2_over_matrix_in_place(input: &mut [f64], width: usize, axis: Axis) {
     }
 }
 
-/// Computes the median for slice of float values.
+/// Computes only the low-frequency `out_width` x `out_height` block of the 2D DCT Type-II
+/// over a flat row-major matrix.
+///
+/// This yields the same values as running `dct2_over_matrix_in_place` over the columns
+/// and then the rows and cropping the top-left corner afterwards, but it never computes the
+/// high-frequency coefficients that would be discarded anyway. The column pass only computes
+/// the first `out_height` coefficients of every column and the row pass only computes the first
+/// `out_width` coefficients of those rows.
+///
+/// # Arguments
+/// * `input`: A flat row-major matrix of floats (length = rows * width).
+/// * `width`: The number of columns in the matrix.
+/// * `out_width`: The number of low-frequency coefficients to keep per row.
+/// * `out_height`: The number of low-frequency coefficients to keep per column.
+/// * `buf`: Temporary buffer for the intermediate column pass.
+/// * `output`: The buffer the flat row-major `out_width * out_height` coefficients are written to.
+pub fn dct2_2d_truncated(
+    input: &[f64],
+    width: usize,
+    (out_width, out_height): (usize, usize),
+    buf: &mut Vec<f64>,
+    output: &mut Vec<f64>,
+) {
+    output.clear();
+
+    if input.is_empty() || width == 0 {
+        return;
+    }
+
+    let height = input.len() / width;
+
+    // Internal invariant: the requested block must fit into the input matrix.
+    assert!(
+        out_width <= width && out_height <= height,
+        "truncated block exceeds the matrix dimensions"
+    );
+
+    // Column pass: only the first `out_height` frequencies of every column are needed,
+    // as the lower rows of the intermediate matrix would be cropped away afterwards.
+    buf.clear();
+    buf.resize(out_height * width, 0.0);
+    for n in 0..width {
+        for k in 0..out_height {
+            buf[k * width + n] = dct2_coefficient(&input[n..], width, height, k);
+        }
+    }
+
+    // Row pass: only the first `out_width` frequencies of the remaining rows are needed.
+    for row in buf.chunks(width) {
+        output.extend((0..out_width).map(|k| dct2_coefficient(row, 1, width, k)));
+    }
+}
+
+/// Computes the median for slice of float values, using `buf` for sorting the values.
 ///
 /// # Arguments
 /// * `input`: A reference to a slice of floats
+/// * `buf`: A buffer that is reused for sorting the values
 ///
 /// # Returns
 /// * Returns a float that represents the median
 /// * Returns `None` if `input` is empty
-pub fn median(input: impl IntoIterator<Item = f64>) -> Option<f64> {
-    let mut sorted = input.into_iter().collect::<Vec<_>>();
+pub fn median(input: impl IntoIterator<Item = f64>, buf: &mut Vec<f64>) -> Option<f64> {
+    buf.clear();
+    buf.extend(input);
 
-    if sorted.is_empty() {
+    if buf.is_empty() {
         return None;
     }
 
-    sorted.sort_by(|a, b| a.total_cmp(b));
+    buf.sort_by(|a, b| a.total_cmp(b));
 
-    let mid = sorted.len() / 2;
-    if sorted.len() % 2 == 0 {
-        Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+    let mid = buf.len() / 2;
+    if buf.len() % 2 == 0 {
+        Some((buf[mid - 1] + buf[mid]) / 2.0)
     } else {
-        Some(sorted[mid])
+        Some(buf[mid])
     }
 }
 
@@ -292,13 +362,73 @@ mod tests {
         assert_eq!(input, vec![]);
     }
 
+    #[test]
+    fn test_dct2_2d_truncated_matches_full_dct() {
+        // Arrange
+        let input = (0..48).map(|v| ((v * 37) % 11) as f64).collect::<Vec<_>>();
+
+        let mut full = input.clone();
+        dct2_over_matrix_in_place(&mut full, 8, Axis::Column);
+        dct2_over_matrix_in_place(&mut full, 8, Axis::Row);
+
+        let expected = full
+            .chunks(8)
+            .take(2)
+            .flat_map(|row| &row[0..3])
+            .copied()
+            .collect::<Vec<_>>();
+
+        // Act
+        let mut truncated = vec![];
+        dct2_2d_truncated(&input, 8, (3, 2), &mut vec![], &mut truncated);
+
+        // Assert
+        assert_eq!(truncated, expected);
+    }
+
+    #[test]
+    fn test_dct2_2d_truncated_with_full_size() {
+        // Arrange
+        let input = vec![
+            1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., 12., 13., 14., 15., 16.,
+        ];
+
+        let mut expected = input.clone();
+        dct2_over_matrix_in_place(&mut expected, 4, Axis::Column);
+        dct2_over_matrix_in_place(&mut expected, 4, Axis::Row);
+
+        // Act
+        let mut truncated = vec![];
+        dct2_2d_truncated(&input, 4, (4, 4), &mut vec![], &mut truncated);
+
+        // Assert
+        assert_eq!(truncated, expected);
+    }
+
+    #[test]
+    fn test_dct2_2d_truncated_with_empty_input() {
+        // Act
+        let mut truncated = vec![1.0];
+        dct2_2d_truncated(&[], 0, (0, 0), &mut vec![], &mut truncated);
+
+        // Assert
+        assert!(truncated.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "truncated block exceeds the matrix dimensions")]
+    fn test_dct2_2d_truncated_with_oversized_block() {
+        let input = vec![1., 2., 3., 4.];
+        dct2_2d_truncated(&input, 2, (3, 1), &mut vec![], &mut vec![]);
+    }
+
     #[test]
     fn test_median_with_even_numbers() {
         // Arrange
         let input = vec![3., 2., 1., 4.];
 
         // Act
-        let result = median(input);
+        let result = median(input, &mut vec![]);
 
         // Assert
         assert_eq!(result, Some(2.5));
@@ -310,7 +440,7 @@ mod tests {
         let input = vec![3., 4., 1., 2., 5.];
 
         // Act
-        let result = median(input);
+        let result = median(input, &mut vec![]);
 
         // Assert
         assert_eq!(result, Some(3.));
@@ -322,19 +452,33 @@ mod tests {
         let input = vec![42.0];
 
         // Act
-        let result = median(input);
+        let result = median(input, &mut vec![]);
 
         // Assert
         assert_eq!(result, Some(42.0));
     }
 
+    #[test]
+    fn test_median_with_reused_buffer() {
+        // Arrange
+        let mut buf = vec![10., 20., 30.];
+
+        // Act
+        let first = median(vec![3., 2., 1., 4.], &mut buf);
+        let second = median(vec![5.], &mut buf);
+
+        // Assert
+        assert_eq!(first, Some(2.5));
+        assert_eq!(second, Some(5.));
+    }
+
     #[test]
     fn test_median_with_empty_vector() {
         // Arrange
         let input = vec![];
 
         // Act
-        let result = median(input);
+        let result = median(input, &mut vec![]);
 
         // Assert
         assert_eq!(result, None);
@@ -1,66 +1,215 @@
-use crate::{imageops::convert, ColorSpace, ImageHash, ImageHashError, ImageHasher};
+use crate::imghash::{dimension, invalid_dimension};
+use crate::{
+    AlphaPolicy, ColorSpace, HashAlgorithm, HashContext, HasherSpec, ImageHash, ImageHashError,
+    ImageHasher, Normalization, ResizeTarget, ToneMapping,
+};
+
+#[cfg(feature = "image")]
+use crate::{
+    imageops::{self, ConvertBuffers},
+    BitMargins,
+};
 
 #[derive(Debug, Clone)]
 pub struct MedianHasher {
     /// The target width of the matrix
-    width: u8,
+    width: u16,
 
     /// The target height of the matrix
-    height: u8,
+    height: u16,
 
     /// The color space which will be used for grayscaling.
     /// Default is Rec. 601
     color_space: ColorSpace,
+
+    /// The way transparent pixels are treated during grayscaling.
+    /// Default is to ignore the alpha channel
+    alpha: AlphaPolicy,
+
+    /// The tone mapping for high bit depth and high dynamic range images.
+    /// Default is no tone mapping
+    tone_mapping: ToneMapping,
+
+    /// The contrast normalization that is applied after grayscaling.
+    /// Default is no normalization
+    normalization: Normalization,
 }
 
 impl MedianHasher {
-    pub fn new(width: u8, height: u8, color_space: ColorSpace) -> Result<Self, ImageHashError> {
+    pub fn new(
+        width: impl TryInto<u16>,
+        height: impl TryInto<u16>,
+        color_space: ColorSpace,
+    ) -> Result<Self, ImageHashError> {
+        let (width, height) = (dimension(width, "width")?, dimension(height, "height")?);
+
         if width == 0 || height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        Ok(Self {
+        color_space.check()?;
+
+        let hasher = Self {
             width,
             height,
             color_space,
-        })
+            alpha: AlphaPolicy::default(),
+            tone_mapping: ToneMapping::default(),
+            normalization: Normalization::default(),
+        };
+
+        // the resized image must stay addressable, even on 16-bit and 32-bit targets
+        hasher.target().checked_len()?;
+
+        Ok(hasher)
     }
 
-    pub fn width(&self) -> u8 {
+    /// Creates a builder for a [`MedianHasher`], starting from the default configuration.
+    ///
+    /// Unlike [`MedianHasher::new`], the builder reports which parameter is invalid.
+    pub fn builder() -> MedianHasherBuilder {
+        MedianHasherBuilder::default()
+    }
+
+    pub fn width(&self) -> u16 {
         self.width
     }
 
-    pub fn height(&self) -> u8 {
+    pub fn height(&self) -> u16 {
         self.height
     }
 
     pub fn color_space(&self) -> ColorSpace {
         self.color_space
     }
+
+    pub fn alpha(&self) -> AlphaPolicy {
+        self.alpha
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// The grayscaled and resized image the hash is computed on.
+    fn target(&self) -> ResizeTarget {
+        ResizeTarget {
+            width: self.width as u32,
+            height: self.height as u32,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+        }
+    }
+
+    /// Generates the hash of an image along with the margin of each bit, which is the
+    /// distance of its pixel to the median relative to the full luma range.
+    ///
+    /// # Arguments
+    /// * `img`: The image to hash
+    ///
+    /// # Returns
+    /// * The same hash as [`ImageHasher::hash_from_img`] and the [`BitMargins`] of its bits
+    #[cfg(feature = "image")]
+    pub fn hash_with_confidence(
+        &self,
+        img: &image::DynamicImage,
+    ) -> Result<(ImageHash, BitMargins), ImageHashError> {
+        let mut buffers = ConvertBuffers::default();
+        let mut values = Vec::new();
+        imageops::convert_target(img, &self.target(), &mut buffers).extend_f64(&mut values);
+
+        if values.is_empty() {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        let mut sorted = values.clone();
+        let len = sorted.len();
+        let median = *sorted.select_nth_unstable_by(len / 2, f64::total_cmp).1;
+
+        BitMargins::threshold(&values, median, 255.0, self.width, self.height)
+    }
 }
 
 impl ImageHasher for MedianHasher {
+    #[cfg(feature = "image")]
     fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_img_with(&mut HashContext::new(), img)
+    }
+
+    fn resize_target(&self) -> Option<ResizeTarget> {
+        Some(self.target())
+    }
+
+    fn hash_from_resized(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[u8],
+    ) -> Result<ImageHash, ImageHashError> {
         if self.width == 0 || self.height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        let width = self.width as u32;
-        let height = self.height as u32;
+        let target = self.target();
+        target.check(pixels)?;
 
-        let converted = convert(img, width, height, self.color_space);
-
-        let mut values: Vec<u8> = converted.as_bytes().to_vec();
+        let values = &mut ctx.values;
+        values.clear();
+        values.extend_from_slice(pixels);
 
         let len = values.len();
         let median = *values.select_nth_unstable(len / 2).1;
 
+        ImageHash::from_bool_iter(pixels.iter().map(|&p| p > median), self.width, self.height)
+    }
+
+    fn hash_from_resized_f32(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[f32],
+    ) -> Result<ImageHash, ImageHashError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(ImageHashError::EmptyMatrix);
+        }
+
+        let target = self.target();
+        target.check(pixels)?;
+
+        let values = &mut ctx.sorted;
+        values.clear();
+        values.extend(pixels.iter().map(|&p| p as f64));
+
+        let len = values.len();
+        let median = *values.select_nth_unstable_by(len / 2, f64::total_cmp).1;
+
         ImageHash::from_bool_iter(
-            converted.as_bytes().iter().map(|&p| p > median),
+            pixels.iter().map(|&p| p as f64 > median),
             self.width,
             self.height,
         )
     }
+
+    fn name(&self) -> &str {
+        "mhash"
+    }
+
+    fn spec(&self) -> Option<HasherSpec> {
+        Some(HasherSpec {
+            algorithm: HashAlgorithm::Median,
+            width: self.width,
+            height: self.height,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+            ..HasherSpec::new(HashAlgorithm::Median)
+        })
+    }
 }
 
 impl Default for MedianHasher {
@@ -69,20 +218,124 @@ impl Default for MedianHasher {
             width: 8,
             height: 8,
             color_space: ColorSpace::REC601,
+            alpha: AlphaPolicy::Ignore,
+            tone_mapping: ToneMapping::None,
+            normalization: Normalization::None,
+        }
+    }
+}
+
+/// A builder for a [`MedianHasher`], created by [`MedianHasher::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct MedianHasherBuilder {
+    /// The configuration that is validated on [`MedianHasherBuilder::build`]
+    hasher: MedianHasher,
+
+    /// The first dimension that does not fit into `u16`, reported on [`MedianHasherBuilder::build`]
+    invalid: Option<&'static str>,
+}
+
+impl MedianHasherBuilder {
+    /// Sets the target width and height of the matrix.
+    pub fn size(self, width: impl TryInto<u16>, height: impl TryInto<u16>) -> Self {
+        self.width(width).height(height)
+    }
+
+    /// Sets the target width of the matrix.
+    pub fn width(mut self, width: impl TryInto<u16>) -> Self {
+        match width.try_into() {
+            Ok(width) => self.hasher.width = width,
+            Err(_) => self.invalid = self.invalid.or(Some("width")),
+        }
+        self
+    }
+
+    /// Sets the target height of the matrix.
+    pub fn height(mut self, height: impl TryInto<u16>) -> Self {
+        match height.try_into() {
+            Ok(height) => self.hasher.height = height,
+            Err(_) => self.invalid = self.invalid.or(Some("height")),
+        }
+        self
+    }
+
+    /// Sets the color space which will be used for grayscaling.
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.hasher.color_space = color_space;
+        self
+    }
+
+    /// Sets the way transparent pixels are treated during grayscaling.
+    pub fn alpha(mut self, alpha: AlphaPolicy) -> Self {
+        self.hasher.alpha = alpha;
+        self
+    }
+
+    /// Sets the tone mapping for high bit depth and high dynamic range images.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.hasher.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Sets the contrast normalization that is applied after grayscaling.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.hasher.normalization = normalization;
+        self
+    }
+
+    /// Validates the configuration and creates the [`MedianHasher`].
+    ///
+    /// # Returns
+    /// * The new [`MedianHasher`]
+    /// * An [`ImageHashError::InvalidParameter`] naming the first invalid parameter,
+    ///   including custom color space coefficients that are not finite and CLAHE
+    ///   parameters of zero
+    /// * An [`ImageHashError::DimensionOverflow`] if the resized image would not be addressable
+    pub fn build(&self) -> Result<MedianHasher, ImageHashError> {
+        if let Some(name) = self.invalid {
+            return Err(invalid_dimension(name));
+        }
+
+        if self.hasher.width == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "width",
+                reason: "must be greater than zero",
+            });
         }
+
+        if self.hasher.height == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "height",
+                reason: "must be greater than zero",
+            });
+        }
+
+        self.hasher.color_space.check()?;
+        self.hasher.normalization.check()?;
+        self.hasher.target().checked_len()?;
+
+        Ok(self.hasher.clone())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use std::path::Path;
 
-    use image::ImageReader;
+    use image::{DynamicImage, ImageBuffer, ImageReader, Luma};
 
     use super::*;
+    use crate::{DecodeOptions, Normalization, PixelLayout, RawImage};
 
     const TEST_IMG: &str = "./data/img/test.png";
     const TXT_FILE: &str = "./data/misc/test.txt";
+    const GRAY_IMG: &str = "./data/img/gray-601.png";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+    const DARK_IMG: &str = "./data/img/test-dark.png";
+    const LOW_CONTRAST_IMG: &str = "./data/img/test-low-contrast.png";
+    const LOGO_IMG: &str = "./data/img/logo-alpha.png";
+    const LOGO_WHITE_IMG: &str = "./data/img/logo-white.png";
+    const LOGO_BLACK_IMG: &str = "./data/img/logo-black.png";
 
     const REC_601_HASH: &str = "ffffff1e00000301";
     const REC_709_HASH: &str = "ffffff1e00000301";
@@ -109,6 +362,57 @@ mod tests {
         assert_eq!(hasher.color_space(), ColorSpace::REC601);
     }
 
+    #[test]
+    fn test_builder_with_defaults() {
+        // Act
+        let hasher = MedianHasher::builder().build().unwrap();
+
+        // Assert
+        let default = MedianHasher::default();
+        assert_eq!(hasher.width(), default.width());
+        assert_eq!(hasher.height(), default.height());
+        assert_eq!(hasher.color_space(), default.color_space());
+    }
+
+    #[test]
+    fn test_builder_with_custom_configuration() {
+        // Act
+        let hasher = MedianHasher::builder()
+            .size(16, 12)
+            .color_space(ColorSpace::REC709)
+            .build()
+            .unwrap();
+
+        // Assert
+        assert_eq!(hasher.width(), 16);
+        assert_eq!(hasher.height(), 12);
+        assert_eq!(hasher.color_space(), ColorSpace::REC709);
+    }
+
+    #[test]
+    fn test_builder_with_zero_width() {
+        // Act
+        let result = MedianHasher::builder().width(0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "width", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_zero_height() {
+        // Act
+        let result = MedianHasher::builder().size(8, 0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "height", .. })
+        ));
+    }
+
     #[test]
     fn test_median_hash_from_img() {
         // Arrange
@@ -145,6 +449,26 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_709_HASH)
     }
 
+    #[test]
+    fn test_median_hash_from_img_with_reused_context() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = MedianHasher::default();
+        let mut ctx = HashContext::new();
+
+        // Act
+        let first = hasher.hash_from_img_with(&mut ctx, &img);
+        let second = hasher.hash_from_img_with(&mut ctx, &img);
+
+        // Assert
+        assert_eq!(first.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_eq!(second.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
     #[test]
     fn test_median_hash_from_path() {
         // Arrange
@@ -158,6 +482,84 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
     }
 
+    #[test]
+    fn test_median_hash_from_bytes() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+        let hasher = MedianHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes(&bytes);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_median_hash_from_reader_with_format() {
+        // Arrange
+        let file = std::fs::File::open(TEST_IMG).unwrap();
+        let hasher = MedianHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_reader_with_format(std::io::BufReader::new(file), image::ImageFormat::Png);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_median_hash_from_luma() {
+        // Arrange
+        let img = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = MedianHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_luma(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize,
+        );
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_median_hash_from_raw_rgb() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let raw = RawImage::new(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize * 3,
+            PixelLayout::Rgb,
+        )
+        .unwrap();
+        let hasher = MedianHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_raw(&raw);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
     #[test]
     fn test_median_hash_from_img_with_non_default_size() {
         // Arrange
@@ -177,6 +579,151 @@ mod tests {
         assert_eq!(hash.shape(), (16, 16));
     }
 
+    #[test]
+    fn test_median_hash_from_img_with_width_larger_than_u8() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = MedianHasher::new(300, 2, ColorSpace::REC601).unwrap();
+
+        // Act
+        let hash = hasher.hash_from_img(&img);
+
+        // Assert
+        assert_eq!(hash.unwrap().shape(), (2, 300));
+    }
+
+    #[test]
+    fn test_median_hash_with_alpha_composited_over_background() {
+        for (background, flattened) in [([255; 3], LOGO_WHITE_IMG), ([0; 3], LOGO_BLACK_IMG)] {
+            // Arrange
+            let hasher = MedianHasher::builder()
+                .alpha(AlphaPolicy::CompositeOver(background))
+                .build()
+                .unwrap();
+
+            // Act
+            let hash = hasher.hash_from_path(Path::new(LOGO_IMG)).unwrap();
+
+            // Assert: the transparent logo hashes like the logo shown on the background
+            let expected = MedianHasher::default()
+                .hash_from_path(Path::new(flattened))
+                .unwrap();
+            assert_eq!(hash, expected);
+        }
+    }
+
+    #[test]
+    fn test_median_hash_from_narrow_range_16_bit_image() {
+        // Arrange: the gray image squeezed into 256 of 65536 levels, which would collapse
+        // into two values when quantized to 8 bits
+        let gray = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_luma8();
+        let narrow = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([30000 + gray.get_pixel(x, y).0[0] as u16])
+        });
+        let full = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([gray.get_pixel(x, y).0[0] as u16 * 257])
+        });
+
+        let hasher = MedianHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(narrow))
+            .unwrap();
+
+        // Assert
+        let expected = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(full))
+            .unwrap();
+        assert_eq!(hash, expected);
+    }
+
+    #[test]
+    fn test_median_hash_from_path_with_orientation() {
+        // Arrange
+        let hasher = MedianHasher::default();
+
+        // Act
+        let oriented =
+            hasher.hash_from_path_with_options(Path::new(ROTATED_IMG), DecodeOptions::new());
+        let stored = hasher.hash_from_path(Path::new(ROTATED_IMG));
+
+        // Assert: the stored image is rotated, applying the orientation brings it upright
+        assert_eq!(oriented.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_ne!(stored.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
+    #[test]
+    fn test_median_hash_from_bytes_with_orientation() {
+        // Arrange
+        let bytes = std::fs::read(ROTATED_IMG).unwrap();
+        let hasher = MedianHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes_with_options(&bytes, DecodeOptions::new());
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_median_hash_with_equalization_is_robust_to_exposure() {
+        // Arrange
+        let plain = MedianHasher::default();
+        let equalized = MedianHasher::builder()
+            .normalization(Normalization::Equalize)
+            .build()
+            .unwrap();
+
+        let distance = |hasher: &MedianHasher, altered: &str| {
+            let original = hasher.hash_from_path(Path::new(TEST_IMG)).unwrap();
+            let altered = hasher.hash_from_path(Path::new(altered)).unwrap();
+            original.distance(&altered).unwrap()
+        };
+
+        for altered in [DARK_IMG, LOW_CONTRAST_IMG] {
+            // Act
+            let before = distance(&plain, altered);
+            let after = distance(&equalized, altered);
+
+            // Assert: the darkened copy flips a few bits without normalization, but at most
+            // two once the histograms are equalized
+            assert!(after <= before, "{altered}: {after} > {before}");
+            assert!(after <= 2, "{altered}: {after}");
+        }
+
+        assert!(distance(&plain, DARK_IMG) >= 3);
+    }
+
+    #[test]
+    fn test_median_builder_with_zero_clahe_tiles() {
+        // Act
+        let result = MedianHasher::builder()
+            .normalization(Normalization::Clahe {
+                tiles: 0,
+                clip_limit: 2,
+            })
+            .build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter {
+                name: "normalization",
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_median_hash_from_nonexisting_path() {
         // Arrange
@@ -200,4 +747,63 @@ mod tests {
         // Assert
         assert!(hash.is_err());
     }
+
+    #[test]
+    fn test_median_hash_with_confidence_matches_hash() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = MedianHasher::default();
+
+        // Act
+        let (hash, margins) = hasher.hash_with_confidence(&img).unwrap();
+
+        // Assert
+        assert_eq!(hash, hasher.hash_from_img(&img).unwrap());
+        assert_eq!(margins.shape(), hash.shape());
+        assert!(margins.margins().iter().all(|m| (0.0..=1.0).contains(m)));
+        assert!(margins.margins().iter().any(|&m| m > 0.0));
+    }
+
+    #[test]
+    fn test_median_hash_with_confidence_flags_unstable_bits() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let dark = ImageReader::open(Path::new(DARK_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = MedianHasher::default();
+
+        // Act
+        let (hash, margins) = hasher.hash_with_confidence(&img).unwrap();
+        let dark_hash = hasher.hash_from_img(&dark).unwrap();
+
+        // Assert: the bits that flip all sit in the lower three quarters of the margins
+        let mut sorted = margins.margins().to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let quartile = sorted[sorted.len() * 3 / 4];
+
+        let flipped = hash
+            .iter_bool()
+            .zip(dark_hash.iter_bool())
+            .zip(margins.margins())
+            .filter(|((a, b), _)| a != b)
+            .map(|(_, &margin)| margin)
+            .collect::<Vec<_>>();
+
+        assert!(!flipped.is_empty());
+        assert!(flipped.iter().all(|&margin| margin < quartile));
+        assert_eq!(
+            hash.distance_masked(&dark_hash, &margins.mask(0.05))
+                .unwrap()
+                .0,
+            0
+        );
+    }
 }
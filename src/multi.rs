@@ -0,0 +1,674 @@
+//! Computing several hashes of the same image at once.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use imghash::{
+//!     average::AverageHasher, difference::DifferenceHasher, median::MedianHasher,
+//!     multi::MultiHasher, perceptual::PerceptualHasher,
+//! };
+//!
+//! let hasher = MultiHasher::new(vec![
+//!     Box::new(AverageHasher::default()),
+//!     Box::new(DifferenceHasher::default()),
+//!     Box::new(MedianHasher::default()),
+//!     Box::new(PerceptualHasher::default()),
+//! ])
+//! .unwrap();
+//!
+//! let hashes = hasher.hash_from_path(Path::new("path/to/image.png")).unwrap();
+//! println!("{}", hashes["phash"]);
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::mem;
+use std::path::Path;
+
+use image::DynamicImage;
+
+use crate::{
+    imageops::{grayscale, grayscale_f32, is_high_bit_depth, normalize, resize, resize_f32, round},
+    open_image, read_image, AlphaPolicy, BorderTrim, ColorSpace, CropRect, DecodeOptions,
+    HashContext, ImageHash, ImageHashError, ImageHasher, Normalization, ResizeTarget, ToneMapping,
+};
+
+/// Computes the hashes of several hashers from a single decode of an image.
+///
+/// The image is only grayscaled once per distinct [`ColorSpace`], [`AlphaPolicy`] and
+/// [`Normalization`] and only resized once per distinct [`ResizeTarget`] of the hashers. For
+/// example, the default average and median hashers share the same 8 x 8 image. Hashers without
+/// a resize target are given the decoded image directly.
+pub struct MultiHasher {
+    /// The hashers together with the name their hash is reported under
+    hashers: Vec<(String, Box<dyn ImageHasher + Send + Sync>)>,
+}
+
+impl MultiHasher {
+    /// Creates a new [`MultiHasher`], reporting each hash under [`ImageHasher::name`].
+    ///
+    /// # Arguments
+    /// * `hashers`: The hashers to compute for every image
+    ///
+    /// # Returns
+    /// * The new [`MultiHasher`]
+    /// * An [`ImageHashError::DuplicateHasherName`] if two hashers share the same name,
+    ///   use [`MultiHasher::with_names`] in that case
+    pub fn new(hashers: Vec<Box<dyn ImageHasher + Send + Sync>>) -> Result<Self, ImageHashError> {
+        Self::with_names(
+            hashers
+                .into_iter()
+                .map(|hasher| (hasher.name().to_string(), hasher)),
+        )
+    }
+
+    /// Creates a new [`MultiHasher`], reporting each hash under an explicit name.
+    ///
+    /// # Arguments
+    /// * `hashers`: The hashers to compute for every image together with their names
+    ///
+    /// # Returns
+    /// * The new [`MultiHasher`]
+    /// * An [`ImageHashError::DuplicateHasherName`] if two hashers share the same name
+    pub fn with_names(
+        hashers: impl IntoIterator<Item = (String, Box<dyn ImageHasher + Send + Sync>)>,
+    ) -> Result<Self, ImageHashError> {
+        let hashers = hashers.into_iter().collect::<Vec<_>>();
+
+        for (i, (name, _)) in hashers.iter().enumerate() {
+            if hashers[..i].iter().any(|(other, _)| other == name) {
+                return Err(ImageHashError::DuplicateHasherName(name.clone()));
+            }
+        }
+
+        Ok(Self { hashers })
+    }
+
+    /// The names the hashes are reported under, in the order of the hashers.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.hashers.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Generates the hashes for an image specified by its file path.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the image file
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes
+    pub fn hash_from_path(
+        &self,
+        path: &Path,
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        self.hash_from_path_with_options(path, DecodeOptions::STORED)
+    }
+
+    /// Generates the hashes for an image specified by its file path, decoding it with the
+    /// given [`DecodeOptions`]. Unlike [`MultiHasher::hash_from_path`], the default options
+    /// apply the EXIF orientation of the image before hashing it.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the image file
+    /// * `options`: The options for decoding the image
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes
+    pub fn hash_from_path_with_options(
+        &self,
+        path: &Path,
+        options: DecodeOptions,
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        let img = open_image(path, options)?;
+        self.hash_from_img(&img)
+    }
+
+    /// Generates the hashes for an encoded image held in memory, guessing its format
+    /// from the leading bytes.
+    ///
+    /// # Arguments
+    /// * `bytes`: The encoded image, for example the contents of a PNG file
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes
+    pub fn hash_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        self.hash_from_bytes_with_options(bytes, DecodeOptions::STORED)
+    }
+
+    /// Generates the hashes for an encoded image held in memory, decoding it with the given
+    /// [`DecodeOptions`]. Unlike [`MultiHasher::hash_from_bytes`], the default options apply
+    /// the EXIF orientation of the image before hashing it.
+    ///
+    /// # Arguments
+    /// * `bytes`: The encoded image, for example the contents of a JPEG file
+    /// * `options`: The options for decoding the image
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes
+    pub fn hash_from_bytes_with_options(
+        &self,
+        bytes: &[u8],
+        options: DecodeOptions,
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        let img = read_image(Cursor::new(bytes), options)?;
+        self.hash_from_img(&img)
+    }
+
+    /// Generates the hashes for a given image.
+    ///
+    /// # Arguments
+    /// * `img`: The image to generate the hashes for
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes
+    pub fn hash_from_img(
+        &self,
+        img: &DynamicImage,
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        self.hash_from_img_with(&mut HashContext::new(), img)
+    }
+
+    /// Generates the hashes for a given image after trimming its near-uniform borders.
+    ///
+    /// # Arguments
+    /// * `img`: The image to generate the hashes for
+    /// * `trim`: The options for detecting the borders
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes, together with the detected
+    ///   [`CropRect`] of the content
+    pub fn hash_from_img_trimmed(
+        &self,
+        img: &DynamicImage,
+        trim: BorderTrim,
+    ) -> Result<(BTreeMap<String, ImageHash>, CropRect), ImageHashError> {
+        let crop = trim.detect(img);
+
+        if (crop.width, crop.height) == (img.width(), img.height()) {
+            return Ok((self.hash_from_img(img)?, crop));
+        }
+
+        let cropped = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        Ok((self.hash_from_img(&cropped)?, crop))
+    }
+
+    /// Generates the hashes for a given image, reusing the buffers of a [`HashContext`].
+    ///
+    /// The context keeps the grayscaled and resized images of every distinct parameter set,
+    /// so hashing a batch of images with the same context does not allocate anything apart
+    /// from the returned map and hashes once the buffers have grown to the image sizes.
+    ///
+    /// # Arguments
+    /// * `ctx`: The context whose buffers are reused
+    /// * `img`: The image to generate the hashes for
+    ///
+    /// # Returns
+    /// * A map from the hasher names to the generated hashes
+    pub fn hash_from_img_with(
+        &self,
+        ctx: &mut HashContext,
+        img: &DynamicImage,
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        // the hashers borrow the context as well, so the buffers are taken out while hashing
+        let mut buffers = mem::take(&mut ctx.multi);
+        let hashes = self.hash_with_buffers(ctx, &mut buffers, img);
+        ctx.multi = buffers;
+
+        hashes
+    }
+
+    fn hash_with_buffers(
+        &self,
+        ctx: &mut HashContext,
+        buffers: &mut MultiBuffers,
+        img: &DynamicImage,
+    ) -> Result<BTreeMap<String, ImageHash>, ImageHashError> {
+        let MultiBuffers {
+            grayscaled,
+            resized,
+            grayscaled_f32,
+            resized_f32,
+            weights,
+            intermediate,
+            lookup,
+        } = buffers;
+
+        grayscaled.reset();
+        resized.reset();
+        grayscaled_f32.reset();
+        resized_f32.reset();
+
+        // high bit depth images are converted without quantizing them to 8 bits
+        let high_bit_depth = is_high_bit_depth(img);
+        let size = (img.width(), img.height());
+
+        let mut hashes = BTreeMap::new();
+
+        for (name, hasher) in &self.hashers {
+            let hash = match hasher.resize_target() {
+                Some(target) if high_bit_depth => {
+                    let pixels = resized_f32.get_or_compute(target, |pixels| {
+                        let key = (
+                            target.color_space,
+                            target.alpha,
+                            target.tone_mapping,
+                            target.normalization,
+                        );
+                        let gray = grayscaled_f32.get_or_compute(key, |gray| {
+                            grayscale_f32(
+                                img,
+                                target.color_space,
+                                target.alpha,
+                                target.tone_mapping,
+                                gray,
+                            );
+                            normalize(gray, size, target.normalization, lookup, |value| value);
+                        });
+
+                        resize_f32(
+                            gray,
+                            size,
+                            (target.width, target.height),
+                            weights,
+                            intermediate,
+                            pixels,
+                        );
+                    });
+
+                    hasher.hash_from_resized_f32(ctx, pixels)?
+                }
+                Some(target) => {
+                    let pixels = resized.get_or_compute(target, |pixels| {
+                        let key = (target.color_space, target.alpha, target.normalization);
+                        let gray = grayscaled.get_or_compute(key, |gray| {
+                            grayscale(img, target.color_space, target.alpha, gray);
+                            normalize(gray, size, target.normalization, lookup, round);
+                        });
+
+                        resize(
+                            gray,
+                            size,
+                            (target.width, target.height),
+                            weights,
+                            intermediate,
+                            pixels,
+                        );
+                    });
+
+                    hasher.hash_from_resized(ctx, pixels)?
+                }
+                None => hasher.hash_from_img_with(ctx, img)?,
+            };
+
+            hashes.insert(name.clone(), hash);
+        }
+
+        Ok(hashes)
+    }
+}
+
+/// The intermediate images of a [`MultiHasher`], kept in a [`HashContext`] between images.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MultiBuffers {
+    /// The grayscaled and normalized images
+    grayscaled: Cache<(ColorSpace, AlphaPolicy, Normalization), u8>,
+
+    /// The resized images
+    resized: Cache<ResizeTarget, u8>,
+
+    /// The grayscaled and normalized images of high bit depth images
+    grayscaled_f32: Cache<(ColorSpace, AlphaPolicy, ToneMapping, Normalization), f32>,
+
+    /// The resized images of high bit depth images
+    resized_f32: Cache<ResizeTarget, f32>,
+
+    /// The normalized filter weights of the resize
+    weights: Vec<f32>,
+
+    /// The vertically resampled image of the resize
+    intermediate: Vec<f32>,
+
+    /// The lookup tables of the contrast normalization
+    lookup: Vec<f32>,
+}
+
+/// Buffers keyed by the parameters they were computed with. The buffers of earlier images are
+/// kept, but only count as computed once they were filled for the current image.
+#[derive(Debug, Clone)]
+struct Cache<K, T> {
+    /// The key, the buffer and whether it was computed for the current image
+    entries: Vec<(K, Vec<T>, bool)>,
+}
+
+impl<K, T> Default for Cache<K, T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<K: PartialEq, T> Cache<K, T> {
+    /// Marks all buffers as outdated for the next image.
+    fn reset(&mut self) {
+        for (_, _, current) in &mut self.entries {
+            *current = false;
+        }
+    }
+
+    /// The buffer of the key, which is filled by `compute` unless it was already computed
+    /// for the current image.
+    fn get_or_compute(&mut self, key: K, compute: impl FnOnce(&mut Vec<T>)) -> &[T] {
+        let index = match self.entries.iter().position(|(other, _, _)| *other == key) {
+            Some(index) => index,
+            None => {
+                self.entries.push((key, Vec::new(), false));
+                self.entries.len() - 1
+            }
+        };
+
+        let (_, buffer, current) = &mut self.entries[index];
+        if !*current {
+            compute(buffer);
+            *current = true;
+        }
+
+        buffer
+    }
+}
+
+impl std::fmt::Debug for MultiHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiHasher")
+            .field("names", &self.names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::ImageReader;
+
+    use super::*;
+    use crate::{
+        average::AverageHasher, difference::DifferenceHasher, median::MedianHasher,
+        perceptual::PerceptualHasher,
+    };
+
+    const TEST_IMG: &str = "./data/img/test.png";
+    const TXT_FILE: &str = "./data/misc/test.txt";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+
+    fn default_hashers() -> Vec<Box<dyn ImageHasher + Send + Sync>> {
+        vec![
+            Box::new(AverageHasher::default()),
+            Box::new(DifferenceHasher::default()),
+            Box::new(MedianHasher::default()),
+            Box::new(PerceptualHasher::default()),
+        ]
+    }
+
+    /// A hasher without a resize target, which has to be given the decoded image.
+    struct WidthHasher;
+
+    impl ImageHasher for WidthHasher {
+        fn hash_from_img(&self, img: &DynamicImage) -> Result<ImageHash, ImageHashError> {
+            ImageHash::from_bool_iter((0..8).map(|i| img.width() & (1 << i) != 0), 8, 1)
+        }
+    }
+
+    #[test]
+    fn test_multi_hash_from_path() {
+        // Arrange
+        let hasher = MultiHasher::new(default_hashers()).unwrap();
+
+        // Act
+        let hashes = hasher.hash_from_path(Path::new(TEST_IMG)).unwrap();
+
+        // Assert
+        assert_eq!(hashes.len(), 4);
+        assert_eq!(hashes["ahash"].encode().unwrap(), "ffffff0e00000301");
+        assert_eq!(hashes["dhash"].encode().unwrap(), "cc99717ed9ea0627");
+        assert_eq!(hashes["mhash"].encode().unwrap(), "ffffff1e00000301");
+        assert_eq!(hashes["phash"].encode().unwrap(), "acdbe86135344e3a");
+    }
+
+    #[test]
+    fn test_multi_hash_matches_individual_hashers() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hashers: Vec<(String, Box<dyn ImageHasher + Send + Sync>)> = vec![
+            (
+                "ahash-709".to_string(),
+                Box::new(AverageHasher::new(8, 8, ColorSpace::REC709).unwrap()),
+            ),
+            (
+                "ahash-16".to_string(),
+                Box::new(AverageHasher::new(16, 16, ColorSpace::REC601).unwrap()),
+            ),
+            (
+                "dhash-709".to_string(),
+                Box::new(DifferenceHasher::new(8, 8, ColorSpace::REC709).unwrap()),
+            ),
+            (
+                "phash-16".to_string(),
+                Box::new(PerceptualHasher::new(16, 16, 2, ColorSpace::REC601).unwrap()),
+            ),
+            (
+                "mhash-equalized".to_string(),
+                Box::new(
+                    MedianHasher::builder()
+                        .normalization(Normalization::Equalize)
+                        .build()
+                        .unwrap(),
+                ),
+            ),
+            (
+                "phash-clahe".to_string(),
+                Box::new(
+                    PerceptualHasher::builder()
+                        .normalization(Normalization::Clahe {
+                            tiles: 8,
+                            clip_limit: 2,
+                        })
+                        .build()
+                        .unwrap(),
+                ),
+            ),
+            ("width".to_string(), Box::new(WidthHasher)),
+        ];
+
+        let expected = hashers
+            .iter()
+            .map(|(name, hasher)| (name.clone(), hasher.hash_from_img(&img).unwrap()))
+            .collect::<BTreeMap<_, _>>();
+
+        let hasher = MultiHasher::with_names(hashers).unwrap();
+
+        // Act
+        let hashes = hasher.hash_from_img(&img).unwrap();
+
+        // Assert
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn test_multi_hash_matches_individual_hashers_with_16_bit_image() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let img = DynamicImage::ImageRgb16(img.to_rgb16());
+
+        let hashers: Vec<(String, Box<dyn ImageHasher + Send + Sync>)> = vec![
+            ("ahash".to_string(), Box::new(AverageHasher::default())),
+            (
+                "ahash-clamp".to_string(),
+                Box::new(
+                    AverageHasher::builder()
+                        .tone_mapping(ToneMapping::Clamp)
+                        .build()
+                        .unwrap(),
+                ),
+            ),
+            ("mhash".to_string(), Box::new(MedianHasher::default())),
+            ("dhash".to_string(), Box::new(DifferenceHasher::default())),
+            ("phash".to_string(), Box::new(PerceptualHasher::default())),
+        ];
+
+        let expected = hashers
+            .iter()
+            .map(|(name, hasher)| (name.clone(), hasher.hash_from_img(&img).unwrap()))
+            .collect::<BTreeMap<_, _>>();
+
+        let hasher = MultiHasher::with_names(hashers).unwrap();
+
+        // Act
+        let hashes = hasher.hash_from_img(&img).unwrap();
+
+        // Assert
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn test_multi_hash_reuses_context_buffers() {
+        // Arrange
+        let open = |path| {
+            ImageReader::open(Path::new(path))
+                .unwrap()
+                .decode()
+                .unwrap()
+        };
+        let (img, logo) = (open(TEST_IMG), open("./data/img/logo-white.png"));
+
+        let hasher = MultiHasher::new(default_hashers()).unwrap();
+        let mut ctx = HashContext::new();
+        hasher.hash_from_img_with(&mut ctx, &img).unwrap();
+
+        let buffers = |ctx: &HashContext| {
+            ctx.multi
+                .resized
+                .entries
+                .iter()
+                .map(|(_, buffer, _)| buffer.as_ptr())
+                .collect::<Vec<_>>()
+        };
+        let before = buffers(&ctx);
+
+        // Act
+        let logo_hashes = hasher.hash_from_img_with(&mut ctx, &logo).unwrap();
+        let hashes = hasher.hash_from_img_with(&mut ctx, &img).unwrap();
+
+        // Assert: the buffers of the previous image are recomputed, not reallocated
+        assert_eq!(logo_hashes, hasher.hash_from_img(&logo).unwrap());
+        assert_eq!(hashes, hasher.hash_from_img(&img).unwrap());
+        assert_eq!(buffers(&ctx), before);
+    }
+
+    #[test]
+    fn test_multi_hash_from_img_trimmed() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let mut padded =
+            image::RgbImage::from_pixel(img.width() + 60, img.height(), image::Rgb([255; 3]));
+        image::imageops::replace(&mut padded, &img.to_rgb8(), 30, 0);
+        let padded = DynamicImage::ImageRgb8(padded);
+
+        let hasher = MultiHasher::new(default_hashers()).unwrap();
+
+        // Act
+        let (hashes, crop) = hasher
+            .hash_from_img_trimmed(&padded, BorderTrim::new())
+            .unwrap();
+
+        // Assert
+        assert_eq!(hashes, hasher.hash_from_img(&img).unwrap());
+        assert_eq!(
+            crop,
+            CropRect {
+                x: 30,
+                y: 0,
+                width: img.width(),
+                height: img.height()
+            }
+        );
+    }
+
+    #[test]
+    fn test_multi_hash_with_duplicate_names() {
+        // Arrange
+        let hashers: Vec<Box<dyn ImageHasher + Send + Sync>> = vec![
+            Box::new(AverageHasher::default()),
+            Box::new(AverageHasher::new(16, 16, ColorSpace::REC601).unwrap()),
+        ];
+
+        // Act
+        let result = MultiHasher::new(hashers);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::DuplicateHasherName(name)) if name == "ahash"
+        ));
+    }
+
+    #[test]
+    fn test_multi_hash_from_bytes() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+        let hasher = MultiHasher::new(default_hashers()).unwrap();
+
+        // Act
+        let hashes = hasher.hash_from_bytes(&bytes).unwrap();
+
+        // Assert
+        assert_eq!(hashes, hasher.hash_from_path(Path::new(TEST_IMG)).unwrap());
+    }
+
+    #[test]
+    fn test_multi_hash_from_path_with_orientation() {
+        // Arrange
+        let hasher = MultiHasher::new(default_hashers()).unwrap();
+
+        // Act
+        let hashes = hasher
+            .hash_from_path_with_options(Path::new(ROTATED_IMG), DecodeOptions::new())
+            .unwrap();
+
+        // Assert
+        assert_eq!(hashes, hasher.hash_from_path(Path::new(TEST_IMG)).unwrap());
+    }
+
+    #[test]
+    fn test_multi_hash_from_txt_file() {
+        // Arrange
+        let hasher = MultiHasher::new(default_hashers()).unwrap();
+
+        // Act
+        let hashes = hasher.hash_from_path(Path::new(TXT_FILE));
+
+        // Assert
+        assert!(hashes.is_err());
+    }
+
+    #[test]
+    fn test_multi_hash_with_no_hashers() {
+        // Arrange
+        let hasher = MultiHasher::new(vec![]).unwrap();
+
+        // Act
+        let hashes = hasher.hash_from_path(Path::new(TEST_IMG)).unwrap();
+
+        // Assert
+        assert!(hashes.is_empty());
+    }
+}
@@ -1,16 +1,23 @@
+use crate::imghash::{dimension, invalid_dimension};
 use crate::{
-    imageops::convert,
-    math::{dct2_over_matrix_in_place, median, Axis},
-    ColorSpace, ImageHash, ImageHashError, ImageHasher,
+    math::{dct2_2d_truncated, median},
+    AlphaPolicy, ColorSpace, HashAlgorithm, HashContext, HasherSpec, ImageHash, ImageHashError,
+    ImageHasher, Normalization, ResizeTarget, ToneMapping,
+};
+
+#[cfg(feature = "image")]
+use crate::{
+    imageops::{self, Converted},
+    BitMargins, Orientation,
 };
 
 #[derive(Debug, Clone)]
 pub struct PerceptualHasher {
     /// The target width of the matrix
-    width: u8,
+    width: u16,
 
     /// The target height of the matrix
-    height: u8,
+    height: u16,
 
     /// The factor for the DCT matrix. We will rescale the image to
     /// (width * factor, height * factor) before we calculate the DCT on it.
@@ -19,32 +26,63 @@ pub struct PerceptualHasher {
     /// The color space which will be used for grayscaling.
     /// Default is Rec. 601
     color_space: ColorSpace,
+
+    /// The way transparent pixels are treated during grayscaling.
+    /// Default is to ignore the alpha channel
+    alpha: AlphaPolicy,
+
+    /// The tone mapping for high bit depth and high dynamic range images.
+    /// Default is no tone mapping
+    tone_mapping: ToneMapping,
+
+    /// The contrast normalization that is applied after grayscaling.
+    /// Default is no normalization
+    normalization: Normalization,
 }
 
 impl PerceptualHasher {
     pub fn new(
-        width: u8,
-        height: u8,
+        width: impl TryInto<u16>,
+        height: impl TryInto<u16>,
         factor: u8,
         color_space: ColorSpace,
     ) -> Result<Self, ImageHashError> {
+        let (width, height) = (dimension(width, "width")?, dimension(height, "height")?);
+
         if width == 0 || height == 0 || factor == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        Ok(Self {
+        color_space.check()?;
+
+        let hasher = Self {
             width,
             height,
             factor,
             color_space,
-        })
+            alpha: AlphaPolicy::default(),
+            tone_mapping: ToneMapping::default(),
+            normalization: Normalization::default(),
+        };
+
+        // the resized image must stay addressable, even on 16-bit and 32-bit targets
+        hasher.target().checked_len()?;
+
+        Ok(hasher)
     }
 
-    pub fn width(&self) -> u8 {
+    /// Creates a builder for a [`PerceptualHasher`], starting from the default configuration.
+    ///
+    /// Unlike [`PerceptualHasher::new`], the builder reports which parameter is invalid.
+    pub fn builder() -> PerceptualHasherBuilder {
+        PerceptualHasherBuilder::default()
+    }
+
+    pub fn width(&self) -> u16 {
         self.width
     }
 
-    pub fn height(&self) -> u8 {
+    pub fn height(&self) -> u16 {
         self.height
     }
 
@@ -55,48 +93,276 @@ impl PerceptualHasher {
     pub fn color_space(&self) -> ColorSpace {
         self.color_space
     }
-}
 
-impl ImageHasher for PerceptualHasher {
-    fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError> {
+    pub fn alpha(&self) -> AlphaPolicy {
+        self.alpha
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// The grayscaled and resized image the hash is computed on.
+    fn target(&self) -> ResizeTarget {
+        ResizeTarget {
+            width: self.width as u32 * self.factor as u32,
+            height: self.height as u32 * self.factor as u32,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+        }
+    }
+
+    /// Fills the matrix of the context with the resized image.
+    fn load_matrix<T: Copy + Into<f64>>(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[T],
+    ) -> Result<(), ImageHashError> {
         if self.width == 0 || self.height == 0 {
             return Err(ImageHashError::EmptyMatrix);
         }
 
-        let width = self.width as u32 * self.factor as u32;
-        let height = self.height as u32 * self.factor as u32;
-
-        let high_freq = convert(img, width, height, self.color_space);
+        self.target().check(pixels)?;
 
         // convert the higher frequency image to a matrix of f64
-        let mut dct_matrix = high_freq
-            .as_bytes()
-            .iter()
-            .copied()
-            .map(|v| v as f64)
-            .collect::<Vec<_>>();
+        ctx.matrix.clear();
+        ctx.matrix.extend(pixels.iter().copied().map(Into::into));
 
-        // now we compute the DCT for each column and then for each row
-        dct2_over_matrix_in_place(&mut dct_matrix, width as usize, Axis::Column);
-        dct2_over_matrix_in_place(&mut dct_matrix, width as usize, Axis::Row);
+        Ok(())
+    }
 
-        // now we crop the dct matrix to the actual target width and height
-        let scaled_matrix = dct_matrix
-            .chunks(width as usize)
-            .take(self.height as usize)
-            .flat_map(|row| &row[0..self.width as usize])
-            .copied()
-            .collect::<Vec<_>>();
+    /// Fills the matrix of the context with the grayscaled and resized image.
+    #[cfg(feature = "image")]
+    fn load_image(
+        &self,
+        ctx: &mut HashContext,
+        img: &image::DynamicImage,
+    ) -> Result<(), ImageHashError> {
+        let mut buffers = core::mem::take(&mut ctx.convert);
+
+        let loaded = match imageops::convert_target(img, &self.target(), &mut buffers) {
+            Converted::Luma8(pixels) => self.load_matrix(ctx, pixels),
+            Converted::LumaF32(pixels) => self.load_matrix(ctx, pixels),
+        };
+
+        ctx.convert = buffers;
+
+        loaded
+    }
+
+    /// Computes the low-frequency DCT coefficients of the resized image held in the matrix of
+    /// the context.
+    fn coefficients(&self, ctx: &mut HashContext) {
+        // now we compute the DCT for each column and then for each row, but only
+        // for the low-frequency block of the target width and height, as everything
+        // else would be cropped away anyway
+        dct2_2d_truncated(
+            &ctx.matrix,
+            self.target().width as usize,
+            (self.width as usize, self.height as usize),
+            &mut ctx.dct,
+            &mut ctx.coefficients,
+        );
+    }
+
+    /// Computes the hash from the resized image held in the matrix of the context.
+    fn hash_from_matrix(&self, ctx: &mut HashContext) -> Result<ImageHash, ImageHashError> {
+        self.coefficients(ctx);
+        let scaled_matrix = &ctx.coefficients;
 
         // compute the median over the flattened matrix
-        let median = median(scaled_matrix.iter().copied()).ok_or(ImageHashError::EmptyMatrix)?;
+        let median = median(scaled_matrix.iter().copied(), &mut ctx.sorted)
+            .ok_or(ImageHashError::EmptyMatrix)?;
 
         ImageHash::from_bool_iter(
-            scaled_matrix.into_iter().map(|pixel| pixel > median),
+            scaled_matrix.iter().map(|&pixel| pixel > median),
             self.width,
             self.height,
         )
     }
+
+    /// Generates the hash of an image along with the margin of each bit, which is the
+    /// distance of its DCT coefficient to the median. The margins are relative to the largest
+    /// margin of the coefficients besides the DC term, which would dwarf all others.
+    ///
+    /// # Arguments
+    /// * `img`: The image to hash
+    ///
+    /// # Returns
+    /// * The same hash as [`ImageHasher::hash_from_img`] and the [`BitMargins`] of its bits
+    #[cfg(feature = "image")]
+    pub fn hash_with_confidence(
+        &self,
+        img: &image::DynamicImage,
+    ) -> Result<(ImageHash, BitMargins), ImageHashError> {
+        let mut ctx = HashContext::new();
+        self.load_image(&mut ctx, img)?;
+        self.coefficients(&mut ctx);
+
+        let median = median(ctx.coefficients.iter().copied(), &mut ctx.sorted)
+            .ok_or(ImageHashError::EmptyMatrix)?;
+
+        let scale = ctx.coefficients[1..]
+            .iter()
+            .map(|&c| (c - median).abs())
+            .fold(0.0, f64::max);
+
+        BitMargins::threshold(&ctx.coefficients, median, scale, self.width, self.height)
+    }
+
+    /// Generates the hashes of an image in all eight orientations from a single DCT.
+    ///
+    /// Mirroring an image negates its DCT coefficients of odd frequency along the mirrored
+    /// axis, and transposing the image transposes the coefficients. So instead of rotating
+    /// the image and hashing it eight times, the coefficients are rearranged and thresholded
+    /// against their own median. The hashes match those of the transformed images, up to the
+    /// rounding of the resize.
+    ///
+    /// Orientations that swap rows and columns are only generated for square hashes, since
+    /// they would need a hasher with swapped width and height otherwise.
+    ///
+    /// # Arguments
+    /// * `img`: The image to hash
+    ///
+    /// # Returns
+    /// * The hash of the image in each orientation, in the order of [`Orientation::ALL`]
+    #[cfg(feature = "image")]
+    pub fn hash_dihedral(
+        &self,
+        img: &image::DynamicImage,
+    ) -> Result<Vec<(Orientation, ImageHash)>, ImageHashError> {
+        let mut ctx = HashContext::new();
+        self.load_image(&mut ctx, img)?;
+        self.coefficients(&mut ctx);
+
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut oriented = Vec::with_capacity(width * height);
+        let mut hashes = Vec::with_capacity(Orientation::ALL.len());
+
+        for orientation in Orientation::ALL {
+            let (transpose, flip_horizontal, flip_vertical) = orientation.parts();
+
+            if transpose && width != height {
+                continue;
+            }
+
+            oriented.clear();
+            oriented.extend(
+                (0..height)
+                    .flat_map(|v| (0..width).map(move |u| (v, u)))
+                    .map(|(v, u)| {
+                        let coefficient = if transpose {
+                            ctx.coefficients[u * width + v]
+                        } else {
+                            ctx.coefficients[v * width + u]
+                        };
+                        let negate =
+                            (flip_horizontal && u % 2 == 1) != (flip_vertical && v % 2 == 1);
+
+                        if negate {
+                            -coefficient
+                        } else {
+                            coefficient
+                        }
+                    }),
+            );
+
+            let median = median(oriented.iter().copied(), &mut ctx.sorted)
+                .ok_or(ImageHashError::EmptyMatrix)?;
+
+            let hash = ImageHash::from_bool_iter(
+                oriented.iter().map(|&coefficient| coefficient > median),
+                self.width,
+                self.height,
+            )?;
+
+            hashes.push((orientation, hash));
+        }
+
+        Ok(hashes)
+    }
+
+    /// The smallest hamming distance between the hash of an image in any of the eight
+    /// orientations and the other hash, see [`PerceptualHasher::hash_dihedral`].
+    ///
+    /// # Arguments
+    /// * `img`: The image to hash
+    /// * `other`: The hash to compare against
+    ///
+    /// # Returns
+    /// * The smallest distance and the orientation of the image that achieves it.
+    ///   Ties go to the first orientation in [`Orientation::ALL`]
+    /// * An [`ImageHashError::ShapeMismatch`] if the other hash has a different shape
+    #[cfg(feature = "image")]
+    pub fn min_distance_dihedral(
+        &self,
+        img: &image::DynamicImage,
+        other: &ImageHash,
+    ) -> Result<(usize, Orientation), ImageHashError> {
+        let mut best: Option<(usize, Orientation)> = None;
+
+        for (orientation, hash) in self.hash_dihedral(img)? {
+            let distance = hash.distance(other)?;
+
+            if best.is_none_or(|(min, _)| distance < min) {
+                best = Some((distance, orientation));
+            }
+        }
+
+        best.ok_or(ImageHashError::EmptyMatrix)
+    }
+}
+
+impl ImageHasher for PerceptualHasher {
+    #[cfg(feature = "image")]
+    fn hash_from_img(&self, img: &image::DynamicImage) -> Result<ImageHash, ImageHashError> {
+        self.hash_from_img_with(&mut HashContext::new(), img)
+    }
+
+    fn resize_target(&self) -> Option<ResizeTarget> {
+        Some(self.target())
+    }
+
+    fn hash_from_resized(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[u8],
+    ) -> Result<ImageHash, ImageHashError> {
+        self.load_matrix(ctx, pixels)?;
+        self.hash_from_matrix(ctx)
+    }
+
+    fn hash_from_resized_f32(
+        &self,
+        ctx: &mut HashContext,
+        pixels: &[f32],
+    ) -> Result<ImageHash, ImageHashError> {
+        self.load_matrix(ctx, pixels)?;
+        self.hash_from_matrix(ctx)
+    }
+
+    fn name(&self) -> &str {
+        "phash"
+    }
+
+    fn spec(&self) -> Option<HasherSpec> {
+        Some(HasherSpec {
+            algorithm: HashAlgorithm::Perceptual,
+            width: self.width,
+            height: self.height,
+            factor: self.factor,
+            color_space: self.color_space,
+            alpha: self.alpha,
+            tone_mapping: self.tone_mapping,
+            normalization: self.normalization,
+        })
+    }
 }
 
 impl Default for PerceptualHasher {
@@ -106,20 +372,137 @@ impl Default for PerceptualHasher {
             height: 8,
             factor: 4,
             color_space: ColorSpace::REC601,
+            alpha: AlphaPolicy::Ignore,
+            tone_mapping: ToneMapping::None,
+            normalization: Normalization::None,
+        }
+    }
+}
+
+/// A builder for a [`PerceptualHasher`], created by [`PerceptualHasher::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct PerceptualHasherBuilder {
+    /// The configuration that is validated on [`PerceptualHasherBuilder::build`]
+    hasher: PerceptualHasher,
+
+    /// The first dimension that does not fit into `u16`, reported on [`PerceptualHasherBuilder::build`]
+    invalid: Option<&'static str>,
+}
+
+impl PerceptualHasherBuilder {
+    /// Sets the target width and height of the matrix.
+    pub fn size(self, width: impl TryInto<u16>, height: impl TryInto<u16>) -> Self {
+        self.width(width).height(height)
+    }
+
+    /// Sets the target width of the matrix.
+    pub fn width(mut self, width: impl TryInto<u16>) -> Self {
+        match width.try_into() {
+            Ok(width) => self.hasher.width = width,
+            Err(_) => self.invalid = self.invalid.or(Some("width")),
+        }
+        self
+    }
+
+    /// Sets the target height of the matrix.
+    pub fn height(mut self, height: impl TryInto<u16>) -> Self {
+        match height.try_into() {
+            Ok(height) => self.hasher.height = height,
+            Err(_) => self.invalid = self.invalid.or(Some("height")),
+        }
+        self
+    }
+
+    /// Sets the factor for the DCT matrix. The image is rescaled to
+    /// (width * factor, height * factor) before the DCT is computed on it.
+    pub fn factor(mut self, factor: u8) -> Self {
+        self.hasher.factor = factor;
+        self
+    }
+
+    /// Sets the color space which will be used for grayscaling.
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.hasher.color_space = color_space;
+        self
+    }
+
+    /// Sets the way transparent pixels are treated during grayscaling.
+    pub fn alpha(mut self, alpha: AlphaPolicy) -> Self {
+        self.hasher.alpha = alpha;
+        self
+    }
+
+    /// Sets the tone mapping for high bit depth and high dynamic range images.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.hasher.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Sets the contrast normalization that is applied after grayscaling.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.hasher.normalization = normalization;
+        self
+    }
+
+    /// Validates the configuration and creates the [`PerceptualHasher`].
+    ///
+    /// # Returns
+    /// * The new [`PerceptualHasher`]
+    /// * An [`ImageHashError::InvalidParameter`] naming the first invalid parameter,
+    ///   including custom color space coefficients that are not finite and CLAHE
+    ///   parameters of zero
+    /// * An [`ImageHashError::DimensionOverflow`] if the resized image would not be addressable
+    pub fn build(&self) -> Result<PerceptualHasher, ImageHashError> {
+        if let Some(name) = self.invalid {
+            return Err(invalid_dimension(name));
         }
+
+        if self.hasher.width == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "width",
+                reason: "must be greater than zero",
+            });
+        }
+
+        if self.hasher.height == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "height",
+                reason: "must be greater than zero",
+            });
+        }
+
+        if self.hasher.factor == 0 {
+            return Err(ImageHashError::InvalidParameter {
+                name: "factor",
+                reason: "must be greater than zero",
+            });
+        }
+
+        self.hasher.color_space.check()?;
+        self.hasher.normalization.check()?;
+        self.hasher.target().checked_len()?;
+
+        Ok(self.hasher.clone())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use std::path::Path;
 
-    use image::ImageReader;
+    use image::{DynamicImage, ImageBuffer, ImageReader, Luma};
 
     use super::*;
+    use crate::{DecodeOptions, PixelLayout, RawImage};
 
     const TEST_IMG: &str = "./data/img/test.png";
     const TXT_FILE: &str = "./data/misc/test.txt";
+    const GRAY_IMG: &str = "./data/img/gray-601.png";
+    const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+    const DARK_IMG: &str = "./data/img/test-dark.png";
+    const LOGO_IMG: &str = "./data/img/logo-alpha.png";
+    const LOGO_WHITE_IMG: &str = "./data/img/logo-white.png";
+    const LOGO_BLACK_IMG: &str = "./data/img/logo-black.png";
 
     const REC_601_HASH: &str = "acdbe86135344e3a";
     const REC_709_HASH: &str = "acdbe86135344e3a";
@@ -153,6 +536,71 @@ mod tests {
         assert_eq!(hasher.color_space(), ColorSpace::REC601);
     }
 
+    #[test]
+    fn test_builder_with_defaults() {
+        // Act
+        let hasher = PerceptualHasher::builder().build().unwrap();
+
+        // Assert
+        let default = PerceptualHasher::default();
+        assert_eq!(hasher.width(), default.width());
+        assert_eq!(hasher.height(), default.height());
+        assert_eq!(hasher.color_space(), default.color_space());
+    }
+
+    #[test]
+    fn test_builder_with_custom_configuration() {
+        // Act
+        let hasher = PerceptualHasher::builder()
+            .size(16, 12)
+            .factor(2)
+            .color_space(ColorSpace::REC709)
+            .build()
+            .unwrap();
+
+        // Assert
+        assert_eq!(hasher.width(), 16);
+        assert_eq!(hasher.height(), 12);
+        assert_eq!(hasher.factor(), 2);
+        assert_eq!(hasher.color_space(), ColorSpace::REC709);
+    }
+
+    #[test]
+    fn test_builder_with_zero_width() {
+        // Act
+        let result = PerceptualHasher::builder().width(0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "width", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_zero_height() {
+        // Act
+        let result = PerceptualHasher::builder().size(8, 0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "height", .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_zero_factor() {
+        // Act
+        let result = PerceptualHasher::builder().factor(0).build();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "factor", .. })
+        ));
+    }
+
     #[test]
     fn test_perceptual_hash_from_img() {
         // Arrange
@@ -189,6 +637,26 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_709_HASH)
     }
 
+    #[test]
+    fn test_perceptual_hash_from_img_with_reused_context() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = PerceptualHasher::default();
+        let mut ctx = HashContext::new();
+
+        // Act
+        let first = hasher.hash_from_img_with(&mut ctx, &img);
+        let second = hasher.hash_from_img_with(&mut ctx, &img);
+
+        // Assert
+        assert_eq!(first.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_eq!(second.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
     #[test]
     fn test_perceptual_hash_from_path() {
         // Arrange
@@ -202,6 +670,84 @@ mod tests {
         assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
     }
 
+    #[test]
+    fn test_perceptual_hash_from_bytes() {
+        // Arrange
+        let bytes = std::fs::read(TEST_IMG).unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes(&bytes);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_perceptual_hash_from_reader_with_format() {
+        // Arrange
+        let file = std::fs::File::open(TEST_IMG).unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_reader_with_format(std::io::BufReader::new(file), image::ImageFormat::Png);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_perceptual_hash_from_luma() {
+        // Arrange
+        let img = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_luma(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize,
+        );
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
+    #[test]
+    fn test_perceptual_hash_from_raw_rgb() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let raw = RawImage::new(
+            img.as_bytes(),
+            img.width(),
+            img.height(),
+            img.width() as usize * 3,
+            PixelLayout::Rgb,
+        )
+        .unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_raw(&raw);
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
     #[test]
     fn test_perceptual_hash_from_img_with_non_default_size() {
         // Arrange
@@ -221,6 +767,102 @@ mod tests {
         assert_eq!(hash.shape(), (16, 16));
     }
 
+    #[test]
+    fn test_perceptual_hash_from_img_with_width_larger_than_u8() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let hasher = PerceptualHasher::new(256, 1, 2, ColorSpace::REC601).unwrap();
+
+        // Act
+        let hash = hasher.hash_from_img(&img);
+
+        // Assert
+        assert_eq!(hash.unwrap().shape(), (1, 256));
+    }
+
+    #[test]
+    fn test_perceptual_hash_with_alpha_composited_over_background() {
+        for (background, flattened) in [([255; 3], LOGO_WHITE_IMG), ([0; 3], LOGO_BLACK_IMG)] {
+            // Arrange
+            let hasher = PerceptualHasher::builder()
+                .alpha(AlphaPolicy::CompositeOver(background))
+                .build()
+                .unwrap();
+
+            // Act
+            let hash = hasher.hash_from_path(Path::new(LOGO_IMG)).unwrap();
+
+            // Assert: the transparent logo hashes like the logo shown on the background
+            let expected = PerceptualHasher::default()
+                .hash_from_path(Path::new(flattened))
+                .unwrap();
+            assert_eq!(hash, expected);
+        }
+    }
+
+    #[test]
+    fn test_perceptual_hash_from_narrow_range_16_bit_image() {
+        // Arrange: the gray image squeezed into 256 of 65536 levels, which would collapse
+        // into two values when quantized to 8 bits
+        let gray = ImageReader::open(Path::new(GRAY_IMG))
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_luma8();
+        let narrow = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([30000 + gray.get_pixel(x, y).0[0] as u16])
+        });
+        let full = ImageBuffer::from_fn(gray.width(), gray.height(), |x, y| {
+            Luma([gray.get_pixel(x, y).0[0] as u16 * 257])
+        });
+
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hash = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(narrow))
+            .unwrap();
+
+        // Assert
+        let expected = hasher
+            .hash_from_img(&DynamicImage::ImageLuma16(full))
+            .unwrap();
+        assert_eq!(hash, expected);
+    }
+
+    #[test]
+    fn test_perceptual_hash_from_path_with_orientation() {
+        // Arrange
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let oriented =
+            hasher.hash_from_path_with_options(Path::new(ROTATED_IMG), DecodeOptions::new());
+        let stored = hasher.hash_from_path(Path::new(ROTATED_IMG));
+
+        // Assert: the stored image is rotated, applying the orientation brings it upright
+        assert_eq!(oriented.unwrap().encode().unwrap(), REC_601_HASH);
+        assert_ne!(stored.unwrap().encode().unwrap(), REC_601_HASH);
+    }
+
+    #[test]
+    fn test_perceptual_hash_from_bytes_with_orientation() {
+        // Arrange
+        let bytes = std::fs::read(ROTATED_IMG).unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hash = hasher.hash_from_bytes_with_options(&bytes, DecodeOptions::new());
+
+        // Assert
+        assert!(hash.is_ok());
+        assert_eq!(hash.unwrap().encode().unwrap(), REC_601_HASH)
+    }
+
     #[test]
     fn test_perceptual_hash_from_nonexisting_path() {
         // Arrange
@@ -244,4 +886,176 @@ mod tests {
         // Assert
         assert!(hash.is_err());
     }
+
+    /// Applies an orientation to an image the way [`ImageHash::orient`] applies it to a hash.
+    fn orient_img(img: &DynamicImage, orientation: Orientation) -> DynamicImage {
+        match orientation {
+            Orientation::Identity => img.clone(),
+            Orientation::Rotate90 => img.rotate90(),
+            Orientation::Rotate180 => img.rotate180(),
+            Orientation::Rotate270 => img.rotate270(),
+            Orientation::FlipHorizontal => img.fliph(),
+            Orientation::FlipVertical => img.flipv(),
+            Orientation::Transpose => img.rotate90().fliph(),
+            Orientation::Transverse => img.rotate270().fliph(),
+        }
+    }
+
+    #[test]
+    fn test_perceptual_hash_dihedral_matches_transformed_images() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let hashes = hasher.hash_dihedral(&img).unwrap();
+
+        // Assert
+        assert_eq!(hashes.len(), 8);
+        assert_eq!(hashes[0].1, hasher.hash_from_img(&img).unwrap());
+
+        for (orientation, hash) in hashes {
+            let expected = hasher
+                .hash_from_img(&orient_img(&img, orientation))
+                .unwrap();
+
+            assert!(
+                hash.distance(&expected).unwrap() <= 1,
+                "{:?}: {} vs {}",
+                orientation,
+                hash,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_perceptual_hash_dihedral_non_square() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = PerceptualHasher::builder().size(16, 8).build().unwrap();
+
+        // Act
+        let hashes = hasher.hash_dihedral(&img).unwrap();
+
+        // Assert
+        assert_eq!(hashes.len(), 4);
+        assert!(hashes
+            .iter()
+            .all(|(orientation, _)| !orientation.swaps_axes()));
+    }
+
+    #[test]
+    fn test_perceptual_min_distance_dihedral() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = PerceptualHasher::default();
+        let mirrored = hasher.hash_from_img(&img.fliph()).unwrap();
+        let rotated = hasher.hash_from_img(&img.rotate90()).unwrap();
+
+        // Act
+        let (mirrored_distance, mirrored_orientation) =
+            hasher.min_distance_dihedral(&img, &mirrored).unwrap();
+        let (rotated_distance, rotated_orientation) =
+            hasher.min_distance_dihedral(&img, &rotated).unwrap();
+
+        // Assert
+        assert!(
+            hasher
+                .hash_from_img(&img)
+                .unwrap()
+                .distance(&mirrored)
+                .unwrap()
+                > 10
+        );
+        assert!(mirrored_distance <= 1);
+        assert_eq!(mirrored_orientation, Orientation::FlipHorizontal);
+        assert!(rotated_distance <= 1);
+        assert_eq!(rotated_orientation, Orientation::Rotate90);
+    }
+
+    #[test]
+    fn test_perceptual_min_distance_dihedral_with_shape_mismatch() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = PerceptualHasher::default();
+        let other = ImageHash::from_bool_iter(vec![false; 16], 4, 4).unwrap();
+
+        // Act
+        let result = hasher.min_distance_dihedral(&img, &other);
+
+        // Assert
+        assert!(matches!(result, Err(ImageHashError::ShapeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_perceptual_hash_with_confidence_matches_hash() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let (hash, margins) = hasher.hash_with_confidence(&img).unwrap();
+
+        // Assert
+        assert_eq!(hash, hasher.hash_from_img(&img).unwrap());
+        assert_eq!(margins.shape(), hash.shape());
+        assert!(margins.margins().iter().all(|m| (0.0..=1.0).contains(m)));
+        assert!(margins.margins().iter().any(|&m| m > 0.0));
+    }
+
+    #[test]
+    fn test_perceptual_hash_with_confidence_flags_unstable_bits() {
+        // Arrange
+        let img = ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let dark = ImageReader::open(Path::new(DARK_IMG))
+            .unwrap()
+            .decode()
+            .unwrap();
+        let hasher = PerceptualHasher::default();
+
+        // Act
+        let (hash, margins) = hasher.hash_with_confidence(&img).unwrap();
+        let dark_hash = hasher.hash_from_img(&dark).unwrap();
+
+        // Assert: the bits that flip all sit in the lower three quarters of the margins
+        let mut sorted = margins.margins().to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let quartile = sorted[sorted.len() * 3 / 4];
+
+        let flipped = hash
+            .iter_bool()
+            .zip(dark_hash.iter_bool())
+            .zip(margins.margins())
+            .filter(|((a, b), _)| a != b)
+            .map(|(_, &margin)| margin)
+            .collect::<Vec<_>>();
+
+        assert!(!flipped.is_empty());
+        assert!(flipped.iter().all(|&margin| margin < quartile));
+        assert_eq!(
+            hash.distance_masked(&dark_hash, &margins.mask(0.05))
+                .unwrap()
+                .0,
+            0
+        );
+    }
 }
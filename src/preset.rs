@@ -0,0 +1,131 @@
+use crate::HashAlgorithm;
+
+/// How similar two hashes have to be to count as near duplicates, used by
+/// [`ImageHasher::is_near_duplicate`](crate::ImageHasher::is_near_duplicate).
+///
+/// The thresholds are the largest share of differing bits, so they apply to hashes of any size.
+/// They were calibrated per algorithm on 8 x 8 and 16 x 16 hashes of a 3 x 3 grid of tiles of
+/// the test image, comparing each tile to its [standard](crate::eval::Transform::standard)
+/// transformed copies and to the other tiles and their copies. The ignored
+/// `test_print_calibration` test in this module prints the equal error rates and operating
+/// points, and the `eval` module can do the same for other images.
+/// The last column is the smallest share of differing bits between distinct tiles:
+///
+/// | Algorithm | Strict | Default | Loose | Distinct (8 x 8 / 16 x 16) |
+/// |-----------|--------|---------|-------|----------------------------|
+/// | `ahash`   | 0.05   | 0.125   | 0.2   | 0.16 / 0.27                |
+/// | `mhash`   | 0.05   | 0.125   | 0.2   | 0.14 / 0.21                |
+/// | `dhash`   | 0.175  | 0.25    | 0.3   | 0.30 / 0.31                |
+/// | `phash`   | 0.1    | 0.25    | 0.3   | 0.28 / 0.40                |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Preset {
+    /// Matches re-encoded, rescaled, slightly blurred or noisy copies, like JPEG recompression
+    /// down to a quality of 30.
+    Strict,
+
+    /// Additionally matches brightness and contrast changes, crops of 2% per side and
+    /// rotations of a degree. No distinct images matched during calibration.
+    #[default]
+    Default,
+
+    /// Additionally matches most crops of 5% per side, rotations of a few degrees and small
+    /// watermarks. All algorithms matched a few distinct 8 x 8 tiles with a similar layout
+    /// during calibration.
+    Loose,
+}
+
+impl Preset {
+    /// All presets, from the strictest to the loosest.
+    pub const ALL: [Preset; 3] = [Preset::Strict, Preset::Default, Preset::Loose];
+
+    /// The largest share of bits in `[0, 1]` that may differ between two hashes of the
+    /// algorithm for them to count as near duplicates.
+    pub fn threshold(&self, algorithm: HashAlgorithm) -> f64 {
+        match (algorithm, self) {
+            (HashAlgorithm::Average | HashAlgorithm::Median, Preset::Strict) => 0.05,
+            (HashAlgorithm::Average | HashAlgorithm::Median, Preset::Default) => 0.125,
+            (HashAlgorithm::Average | HashAlgorithm::Median, Preset::Loose) => 0.2,
+            (HashAlgorithm::Difference, Preset::Strict) => 0.175,
+            (HashAlgorithm::Difference, Preset::Default) => 0.25,
+            (HashAlgorithm::Difference, Preset::Loose) => 0.3,
+            (HashAlgorithm::Perceptual, Preset::Strict) => 0.1,
+            (HashAlgorithm::Perceptual, Preset::Default) => 0.25,
+            (HashAlgorithm::Perceptual, Preset::Loose) => 0.3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thresholds_grow_with_looser_presets() {
+        for algorithm in HashAlgorithm::ALL {
+            // Act
+            let thresholds = Preset::ALL.map(|preset| preset.threshold(algorithm));
+
+            // Assert
+            assert!(thresholds[0] < thresholds[1]);
+            assert!(thresholds[1] < thresholds[2]);
+            assert!(thresholds.iter().all(|t| (0.0..=1.0).contains(t)));
+        }
+    }
+
+    /// Prints the operating points behind the thresholds, run it with
+    /// `cargo test --release calibration -- --ignored --nocapture`.
+    #[cfg(feature = "image")]
+    #[test]
+    #[ignore = "prints the calibration instead of asserting"]
+    fn test_print_calibration() {
+        use crate::eval::{evaluate, tiles, Transform};
+        use crate::{HasherSpec, ImageHasher};
+
+        // Arrange
+        let images = tiles(&image::open("./data/img/test.png").unwrap(), 3, 3);
+        let transforms = Transform::standard();
+
+        for algorithm in HashAlgorithm::ALL {
+            for size in [8, 16] {
+                let hasher = HasherSpec {
+                    width: size,
+                    height: size,
+                    ..HasherSpec::new(algorithm)
+                }
+                .build()
+                .unwrap();
+                let hashers: [&dyn ImageHasher; 1] = [hasher.as_ref()];
+
+                // Act
+                let report = evaluate(&images, &hashers, &transforms).unwrap().remove(0);
+
+                // Assert
+                println!(
+                    "{} {size} x {size}: equal error rate {:.3} at {:.3}, distinct from {:.3}",
+                    report.name,
+                    report.equal_error.rate,
+                    report.equal_error.threshold,
+                    report.inter.min().unwrap()
+                );
+
+                for preset in Preset::ALL {
+                    let threshold = preset.threshold(algorithm);
+                    let missed = report
+                        .transforms
+                        .iter()
+                        .filter(|(_, distances)| distances.share_within(threshold) < 1.0)
+                        .map(|(transform, _)| transform.to_string())
+                        .collect::<Vec<_>>();
+
+                    println!(
+                        "  {preset:?} at {threshold}: {:.3} of copies, {:.3} of distinct pairs, \
+                         missing {}",
+                        report.intra.share_within(threshold),
+                        report.inter.share_within(threshold),
+                        missed.join(", ")
+                    );
+                }
+            }
+        }
+    }
+}
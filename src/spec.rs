@@ -0,0 +1,702 @@
+//! Building hashers from configuration strings.
+//!
+//! A [`HasherSpec`] describes a hasher and its parameters as a short string, for example
+//! `phash?size=16&factor=4&color=709`. The part before the `?` is the [`HashAlgorithm`],
+//! followed by optional `key=value` parameters separated by `&`:
+//!
+//! - `size`: The hash size, either `N` for a square hash or `WxH`. Default is `8`
+//! - `factor`: The DCT factor, only valid for `phash`. Default is `4`
+//! - `color`: The color space used for grayscaling: `601`, `709`, `2020`, `srgb-linear`, `red`,
+//!   `green`, `blue`, `lightness` or three custom coefficients like `0.3,0.6,0.1`. Default is `601`
+//! - `alpha`: The treatment of transparent pixels: `ignore`, `white`, `black` or `over-RRGGBB`
+//!   to composite over a hexadecimal color. Default is `ignore`
+//! - `tone`: The tone mapping for high bit depth images: `none`, `clamp` or `reinhard`.
+//!   Default is `none`
+//! - `norm`: The contrast normalization: `none`, `minmax`, `equalize` or `clahe`, optionally
+//!   with the number of tiles and the clip limit like `clahe-8-2`. Default is `none`
+//!
+//! ```
+//! use imghash::{HasherSpec, ImageHasher};
+//!
+//! let spec: HasherSpec = "phash?size=16".parse().unwrap();
+//! let hasher = spec.build().unwrap();
+//!
+//! assert_eq!(hasher.name(), "phash");
+//! assert_eq!(
+//!     hasher.spec().unwrap().to_string(),
+//!     "phash?size=16&factor=4&color=601&alpha=ignore&tone=none&norm=none"
+//! );
+//! ```
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::{
+    average::AverageHasher, difference::DifferenceHasher, median::MedianHasher,
+    perceptual::PerceptualHasher, AlphaPolicy, ColorSpace, ImageHashError, ImageHasher,
+    Normalization, ToneMapping,
+};
+
+/// The hash algorithms provided by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// The [`AverageHasher`], named `ahash`
+    Average,
+
+    /// The [`MedianHasher`], named `mhash`
+    Median,
+
+    /// The [`DifferenceHasher`], named `dhash`
+    Difference,
+
+    /// The [`PerceptualHasher`], named `phash`
+    Perceptual,
+}
+
+impl HashAlgorithm {
+    /// All algorithms, in a stable order.
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Average,
+        HashAlgorithm::Median,
+        HashAlgorithm::Difference,
+        HashAlgorithm::Perceptual,
+    ];
+
+    /// The short name of the algorithm, which matches [`ImageHasher::name`] of its hasher.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Average => "ahash",
+            HashAlgorithm::Median => "mhash",
+            HashAlgorithm::Difference => "dhash",
+            HashAlgorithm::Perceptual => "phash",
+        }
+    }
+
+    /// The long name of the algorithm, also accepted when parsing.
+    fn long_name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Average => "average",
+            HashAlgorithm::Median => "median",
+            HashAlgorithm::Difference => "difference",
+            HashAlgorithm::Perceptual => "perceptual",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = ImageHashError;
+
+    /// Parses the short name of an algorithm (`ahash`, `mhash`, `dhash`, `phash`) or its
+    /// long name (`average`, `median`, `difference`, `perceptual`), ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        HashAlgorithm::ALL
+            .into_iter()
+            .find(|algorithm| {
+                s.eq_ignore_ascii_case(algorithm.name())
+                    || s.eq_ignore_ascii_case(algorithm.long_name())
+            })
+            .ok_or_else(|| ImageHashError::UnknownAlgorithm(s.to_string()))
+    }
+}
+
+/// A hash algorithm together with all of its parameters.
+///
+/// The [`Display`](fmt::Display) implementation produces the canonical spec string, which lists
+/// every parameter explicitly, so it can be stored alongside the hashes and parsed again later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HasherSpec {
+    /// The hash algorithm
+    pub algorithm: HashAlgorithm,
+
+    /// The width of the hash
+    pub width: u16,
+
+    /// The height of the hash
+    pub height: u16,
+
+    /// The DCT factor, only used by [`HashAlgorithm::Perceptual`]
+    pub factor: u8,
+
+    /// The color space which is used for grayscaling
+    pub color_space: ColorSpace,
+
+    /// The way transparent pixels are treated during grayscaling
+    pub alpha: AlphaPolicy,
+
+    /// The tone mapping for high bit depth and high dynamic range images
+    pub tone_mapping: ToneMapping,
+
+    /// The contrast normalization that is applied after grayscaling
+    pub normalization: Normalization,
+}
+
+impl HasherSpec {
+    /// Creates the spec of an algorithm with the default parameters of its hasher.
+    ///
+    /// # Arguments
+    /// * `algorithm`: The hash algorithm
+    ///
+    /// # Returns
+    /// * The spec of an 8 x 8 hash using Rec. 601 and a factor of 4, ignoring the alpha channel
+    ///   and without tone mapping or normalization
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            width: 8,
+            height: 8,
+            factor: 4,
+            color_space: ColorSpace::REC601,
+            alpha: AlphaPolicy::Ignore,
+            tone_mapping: ToneMapping::None,
+            normalization: Normalization::None,
+        }
+    }
+
+    /// Builds the hasher described by this spec.
+    ///
+    /// # Returns
+    /// * The hasher, ready to be used
+    /// * An [`ImageHashError::InvalidParameter`] naming the parameter rejected by the hasher
+    pub fn build(&self) -> Result<Box<dyn ImageHasher + Send + Sync>, ImageHashError> {
+        let (width, height, color_space, alpha, tone_mapping, normalization) = (
+            self.width,
+            self.height,
+            self.color_space,
+            self.alpha,
+            self.tone_mapping,
+            self.normalization,
+        );
+
+        Ok(match self.algorithm {
+            HashAlgorithm::Average => Box::new(
+                AverageHasher::builder()
+                    .size(width, height)
+                    .color_space(color_space)
+                    .alpha(alpha)
+                    .tone_mapping(tone_mapping)
+                    .normalization(normalization)
+                    .build()?,
+            ),
+            HashAlgorithm::Median => Box::new(
+                MedianHasher::builder()
+                    .size(width, height)
+                    .color_space(color_space)
+                    .alpha(alpha)
+                    .tone_mapping(tone_mapping)
+                    .normalization(normalization)
+                    .build()?,
+            ),
+            HashAlgorithm::Difference => Box::new(
+                DifferenceHasher::builder()
+                    .size(width, height)
+                    .color_space(color_space)
+                    .alpha(alpha)
+                    .tone_mapping(tone_mapping)
+                    .normalization(normalization)
+                    .build()?,
+            ),
+            HashAlgorithm::Perceptual => Box::new(
+                PerceptualHasher::builder()
+                    .size(width, height)
+                    .factor(self.factor)
+                    .color_space(color_space)
+                    .alpha(alpha)
+                    .tone_mapping(tone_mapping)
+                    .normalization(normalization)
+                    .build()?,
+            ),
+        })
+    }
+}
+
+impl From<HashAlgorithm> for HasherSpec {
+    fn from(algorithm: HashAlgorithm) -> Self {
+        Self::new(algorithm)
+    }
+}
+
+impl fmt::Display for HasherSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}?size=", self.algorithm)?;
+
+        if self.width == self.height {
+            write!(f, "{}", self.width)?;
+        } else {
+            write!(f, "{}x{}", self.width, self.height)?;
+        }
+
+        if self.algorithm == HashAlgorithm::Perceptual {
+            write!(f, "&factor={}", self.factor)?;
+        }
+
+        f.write_str("&color=")?;
+        write_color_space(f, self.color_space)?;
+
+        f.write_str("&alpha=")?;
+        write_alpha(f, self.alpha)?;
+
+        f.write_str("&tone=")?;
+        f.write_str(match self.tone_mapping {
+            ToneMapping::None => "none",
+            ToneMapping::Clamp => "clamp",
+            ToneMapping::Reinhard => "reinhard",
+        })?;
+
+        f.write_str("&norm=")?;
+        match self.normalization {
+            Normalization::None => f.write_str("none"),
+            Normalization::MinMax => f.write_str("minmax"),
+            Normalization::Equalize => f.write_str("equalize"),
+            Normalization::Clahe { tiles, clip_limit } => write!(f, "clahe-{tiles}-{clip_limit}"),
+        }
+    }
+}
+
+impl FromStr for HasherSpec {
+    type Err = ImageHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (algorithm, params) = s.split_once('?').unwrap_or((s, ""));
+
+        let mut spec = HasherSpec::new(algorithm.parse()?);
+        let mut seen = [false; 6];
+
+        for param in params.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("parameter '{param}' is missing a value")))?;
+
+            let index = match key {
+                "size" => {
+                    (spec.width, spec.height) = match value.split_once(['x', 'X']) {
+                        Some((width, height)) => {
+                            (parse_number(key, width)?, parse_number(key, height)?)
+                        }
+                        None => {
+                            let size = parse_number(key, value)?;
+                            (size, size)
+                        }
+                    };
+                    0
+                }
+                "factor" if spec.algorithm == HashAlgorithm::Perceptual => {
+                    spec.factor = parse_number(key, value)?;
+                    1
+                }
+                "factor" => {
+                    return Err(invalid(format!(
+                        "parameter 'factor' is not supported by '{}'",
+                        spec.algorithm
+                    )))
+                }
+                "color" => {
+                    spec.color_space = parse_color_space(value)?;
+                    2
+                }
+                "alpha" => {
+                    spec.alpha = parse_alpha(value)?;
+                    3
+                }
+                "tone" => {
+                    spec.tone_mapping = match value.to_ascii_lowercase().as_str() {
+                        "none" => ToneMapping::None,
+                        "clamp" => ToneMapping::Clamp,
+                        "reinhard" => ToneMapping::Reinhard,
+                        _ => {
+                            return Err(invalid(format!(
+                                "invalid value '{value}' for parameter 'tone'"
+                            )))
+                        }
+                    };
+                    4
+                }
+                "norm" => {
+                    spec.normalization = parse_normalization(value)?;
+                    5
+                }
+                _ => return Err(invalid(format!("unknown parameter '{key}'"))),
+            };
+
+            if core::mem::replace(&mut seen[index], true) {
+                return Err(invalid(format!(
+                    "parameter '{key}' is given more than once"
+                )));
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+/// Builds the hasher described by a spec string, for example `phash?size=16&color=709`.
+///
+/// # Arguments
+/// * `spec`: The spec string, see the [module documentation](self) for the format
+///
+/// # Returns
+/// * The hasher, ready to be used
+/// * An [`ImageHashError`] if the spec string is invalid or the parameters are rejected
+pub fn hasher_from_spec(spec: &str) -> Result<Box<dyn ImageHasher + Send + Sync>, ImageHashError> {
+    spec.parse::<HasherSpec>()?.build()
+}
+
+fn invalid(reason: alloc::string::String) -> ImageHashError {
+    ImageHashError::InvalidSpec(reason)
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, ImageHashError> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid value '{value}' for parameter '{key}'")))
+}
+
+/// Writes the spec value of a color space.
+fn write_color_space(f: &mut fmt::Formatter<'_>, color_space: ColorSpace) -> fmt::Result {
+    match color_space {
+        ColorSpace::REC601 => f.write_str("601"),
+        ColorSpace::REC709 => f.write_str("709"),
+        ColorSpace::REC2020 => f.write_str("2020"),
+        ColorSpace::SRGBLinear => f.write_str("srgb-linear"),
+        ColorSpace::Red => f.write_str("red"),
+        ColorSpace::Green => f.write_str("green"),
+        ColorSpace::Blue => f.write_str("blue"),
+        ColorSpace::Lightness => f.write_str("lightness"),
+        ColorSpace::Custom([r, g, b]) => write!(f, "{r},{g},{b}"),
+    }
+}
+
+/// Parses the spec value of a color space, which is either a name like `709` or three
+/// comma separated custom coefficients like `0.3,0.6,0.1`.
+fn parse_color_space(value: &str) -> Result<ColorSpace, ImageHashError> {
+    let error = || invalid(format!("invalid value '{value}' for parameter 'color'"));
+
+    if value.contains(',') {
+        let mut coefficients = [0.0; 3];
+        let mut parts = value.split(',');
+
+        for coefficient in coefficients.iter_mut() {
+            *coefficient = parts
+                .next()
+                .and_then(|part| part.trim().parse::<f64>().ok())
+                .filter(|c| c.is_finite())
+                .ok_or_else(error)?;
+        }
+
+        if parts.next().is_some() {
+            return Err(error());
+        }
+
+        return Ok(ColorSpace::Custom(coefficients));
+    }
+
+    match value.to_ascii_lowercase().trim_start_matches("rec") {
+        "601" => Ok(ColorSpace::REC601),
+        "709" => Ok(ColorSpace::REC709),
+        "2020" => Ok(ColorSpace::REC2020),
+        "srgb-linear" => Ok(ColorSpace::SRGBLinear),
+        "r" | "red" => Ok(ColorSpace::Red),
+        "g" | "green" => Ok(ColorSpace::Green),
+        "b" | "blue" => Ok(ColorSpace::Blue),
+        "l*" | "lightness" => Ok(ColorSpace::Lightness),
+        _ => Err(error()),
+    }
+}
+
+/// Parses the spec value of a normalization, which is `none`, `minmax`, `equalize`, `clahe`
+/// or `clahe-T-C` with the number of tiles and the clip limit of CLAHE.
+fn parse_normalization(value: &str) -> Result<Normalization, ImageHashError> {
+    let error = || invalid(format!("invalid value '{value}' for parameter 'norm'"));
+
+    match value.to_ascii_lowercase().as_str() {
+        "none" => Ok(Normalization::None),
+        "minmax" => Ok(Normalization::MinMax),
+        "equalize" => Ok(Normalization::Equalize),
+        "clahe" => Ok(Normalization::Clahe {
+            tiles: 8,
+            clip_limit: 2,
+        }),
+        value => {
+            let (tiles, clip_limit) = value
+                .strip_prefix("clahe-")
+                .and_then(|params| params.split_once('-'))
+                .ok_or_else(error)?;
+
+            Ok(Normalization::Clahe {
+                tiles: tiles.parse().map_err(|_| error())?,
+                clip_limit: clip_limit.parse().map_err(|_| error())?,
+            })
+        }
+    }
+}
+
+/// Writes the spec value of an alpha policy.
+fn write_alpha(f: &mut fmt::Formatter<'_>, alpha: AlphaPolicy) -> fmt::Result {
+    match alpha {
+        AlphaPolicy::Ignore => f.write_str("ignore"),
+        AlphaPolicy::CompositeOver([r, g, b]) => write!(f, "over-{r:02x}{g:02x}{b:02x}"),
+    }
+}
+
+/// Parses the spec value of an alpha policy, which is `ignore`, `white`, `black` or
+/// `over-RRGGBB` with a hexadecimal background color.
+fn parse_alpha(value: &str) -> Result<AlphaPolicy, ImageHashError> {
+    let error = || invalid(format!("invalid value '{value}' for parameter 'alpha'"));
+
+    match value.to_ascii_lowercase().as_str() {
+        "ignore" => Ok(AlphaPolicy::Ignore),
+        "white" => Ok(AlphaPolicy::CompositeOver([255; 3])),
+        "black" => Ok(AlphaPolicy::CompositeOver([0; 3])),
+        value => {
+            let hex = value
+                .strip_prefix("over-")
+                .filter(|hex| hex.len() == 6 && hex.is_ascii())
+                .ok_or_else(error)?;
+
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| error());
+
+            Ok(AlphaPolicy::CompositeOver([
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+            ]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_algorithm_from_str() {
+        // Act & Assert
+        assert_eq!(
+            "phash".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Perceptual
+        );
+        assert_eq!(
+            "Average".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Average
+        );
+        assert_eq!(
+            " dhash ".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Difference
+        );
+        assert!(matches!(
+            "whash".parse::<HashAlgorithm>(),
+            Err(ImageHashError::UnknownAlgorithm(name)) if name == "whash"
+        ));
+    }
+
+    #[test]
+    fn test_algorithm_display_roundtrip() {
+        for algorithm in HashAlgorithm::ALL {
+            // Act
+            let parsed = algorithm.to_string().parse::<HashAlgorithm>().unwrap();
+
+            // Assert
+            assert_eq!(parsed, algorithm);
+        }
+    }
+
+    #[test]
+    fn test_spec_from_str_with_defaults() {
+        // Act
+        let spec = "mhash".parse::<HasherSpec>().unwrap();
+
+        // Assert
+        assert_eq!(spec, HasherSpec::new(HashAlgorithm::Median));
+    }
+
+    #[test]
+    fn test_spec_from_str_with_all_parameters() {
+        // Act
+        let spec = "phash?size=16&factor=2&color=709"
+            .parse::<HasherSpec>()
+            .unwrap();
+
+        // Assert
+        assert_eq!(spec.algorithm, HashAlgorithm::Perceptual);
+        assert_eq!((spec.width, spec.height), (16, 16));
+        assert_eq!(spec.factor, 2);
+        assert_eq!(spec.color_space, ColorSpace::REC709);
+    }
+
+    #[test]
+    fn test_spec_from_str_with_alpha() {
+        // Act & Assert
+        for (value, alpha) in [
+            ("white", AlphaPolicy::CompositeOver([255, 255, 255])),
+            ("black", AlphaPolicy::CompositeOver([0, 0, 0])),
+            ("over-FF8000", AlphaPolicy::CompositeOver([255, 128, 0])),
+        ] {
+            let spec = format!("ahash?alpha={value}")
+                .parse::<HasherSpec>()
+                .unwrap();
+            assert_eq!(spec.alpha, alpha);
+        }
+    }
+
+    #[test]
+    fn test_spec_from_str_with_non_square_size() {
+        // Act
+        let spec = "dhash?size=300x2".parse::<HasherSpec>().unwrap();
+
+        // Assert
+        assert_eq!((spec.width, spec.height), (300, 2));
+        assert_eq!(
+            spec.to_string(),
+            "dhash?size=300x2&color=601&alpha=ignore&tone=none&norm=none"
+        );
+    }
+
+    #[test]
+    fn test_spec_from_str_with_invalid_parameters() {
+        for spec in [
+            "ahash?size=",
+            "ahash?size=8x",
+            "ahash?size=70000",
+            "ahash?factor=4",
+            "phash?factor=256",
+            "ahash?color=2021",
+            "ahash?color=0.3,0.6",
+            "ahash?color=0.3,0.6,inf",
+            "ahash?size",
+            "ahash?seed=1",
+            "ahash?size=8&size=16",
+            "ahash?alpha=over",
+            "ahash?alpha=over-fffff",
+            "ahash?alpha=over-gggggg",
+            "ahash?alpha=multiply",
+            "ahash?alpha=premultiplied",
+            "ahash?tone=aces",
+            "ahash?norm=clahe-8",
+            "ahash?norm=clahe-8-x",
+            "ahash?norm=clahe-256-2",
+            "ahash?norm=gamma",
+        ] {
+            // Act
+            let result = spec.parse::<HasherSpec>();
+
+            // Assert
+            assert!(
+                matches!(result, Err(ImageHashError::InvalidSpec(_))),
+                "{spec} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_spec_display_roundtrip() {
+        for s in [
+            "ahash?size=8&color=601&alpha=ignore&tone=none&norm=none",
+            "mhash?size=16x8&color=709&alpha=ignore&tone=none&norm=none",
+            "dhash?size=8&color=601&alpha=ignore&tone=none&norm=none",
+            "phash?size=16&factor=4&color=709&alpha=ignore&tone=none&norm=none",
+            "ahash?size=8&color=2020&alpha=ignore&tone=none&norm=none",
+            "ahash?size=8&color=srgb-linear&alpha=ignore&tone=none&norm=none",
+            "dhash?size=8&color=green&alpha=ignore&tone=none&norm=none",
+            "mhash?size=8&color=lightness&alpha=ignore&tone=none&norm=none",
+            "phash?size=8&factor=4&color=0.25,0.5,0.25&alpha=ignore&tone=none&norm=none",
+            "ahash?size=8&color=601&alpha=over-ff8000&tone=none&norm=none",
+            "phash?size=8&factor=4&color=601&alpha=ignore&tone=reinhard&norm=none",
+            "dhash?size=8&color=601&alpha=ignore&tone=clamp&norm=none",
+            "ahash?size=8&color=601&alpha=ignore&tone=none&norm=minmax",
+            "mhash?size=8&color=601&alpha=ignore&tone=none&norm=equalize",
+            "ahash?size=8&color=601&alpha=ignore&tone=none&norm=clahe-4-3",
+        ] {
+            // Act
+            let spec = s.parse::<HasherSpec>().unwrap();
+
+            // Assert
+            assert_eq!(spec.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_hasher_from_spec_reports_spec() {
+        for algorithm in HashAlgorithm::ALL {
+            // Arrange
+            let spec = HasherSpec {
+                width: 12,
+                height: 4,
+                factor: 3,
+                color_space: ColorSpace::REC709,
+                alpha: AlphaPolicy::CompositeOver([255; 3]),
+                tone_mapping: ToneMapping::Reinhard,
+                normalization: Normalization::Clahe {
+                    tiles: 4,
+                    clip_limit: 3,
+                },
+                ..HasherSpec::new(algorithm)
+            };
+
+            // Act
+            let hasher = hasher_from_spec(&spec.to_string()).unwrap();
+
+            // Assert
+            assert_eq!(hasher.name(), algorithm.name());
+            assert_eq!(hasher.spec().unwrap().to_string(), spec.to_string());
+        }
+    }
+
+    #[test]
+    fn test_hasher_from_spec_with_zero_size() {
+        // Act
+        let result = hasher_from_spec("ahash?size=8x0");
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter { name: "height", .. })
+        ));
+    }
+
+    #[test]
+    fn test_spec_from_str_with_normalization() {
+        // Act & Assert
+        for (value, normalization) in [
+            ("MinMax", Normalization::MinMax),
+            ("equalize", Normalization::Equalize),
+            (
+                "clahe",
+                Normalization::Clahe {
+                    tiles: 8,
+                    clip_limit: 2,
+                },
+            ),
+        ] {
+            let spec = format!("ahash?norm={value}").parse::<HasherSpec>().unwrap();
+            assert_eq!(spec.normalization, normalization);
+        }
+    }
+
+    #[test]
+    fn test_hasher_from_spec_with_zero_clahe_tiles() {
+        // Act
+        let result = hasher_from_spec("ahash?norm=clahe-0-2");
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ImageHashError::InvalidParameter {
+                name: "normalization",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,384 @@
+//! Trimming uniform borders before hashing.
+//!
+//! Screenshots and video stills often have black letterbox bars or uniform white padding,
+//! which shifts the content inside the resized image and changes the hash. A [`BorderTrim`]
+//! detects such borders, so they can be cropped before the image is grayscaled and resized.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use imghash::{average::AverageHasher, trim::BorderTrim, ImageHasher};
+//!
+//! let img = image::open(Path::new("path/to/still.png")).unwrap();
+//! let hasher = AverageHasher::default();
+//!
+//! let (hash, crop) = hasher
+//!     .hash_from_img_trimmed(&img, BorderTrim::new())
+//!     .unwrap();
+//! println!("{} (cropped to {:?})", hash, crop);
+//! ```
+
+use image::{DynamicImage, GenericImageView, Rgba};
+
+/// A rectangle within an image, as detected by [`BorderTrim::detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CropRect {
+    /// The column of the left edge
+    pub x: u32,
+
+    /// The row of the top edge
+    pub y: u32,
+
+    /// The width of the rectangle
+    pub width: u32,
+
+    /// The height of the rectangle
+    pub height: u32,
+}
+
+/// Options for detecting near-uniform borders around an image.
+///
+/// Each side is trimmed while its outermost rows or columns stay within the tolerance of the
+/// color in its corner, so black bars at the top and bottom are trimmed as well as padding
+/// on all four sides. A border has to end in a clear edge to the content, and images that
+/// are uniform as a whole are not trimmed at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BorderTrim {
+    /// The largest difference of a channel to the border color that still counts as border.
+    /// Default is `16`
+    tolerance: u8,
+}
+
+impl BorderTrim {
+    /// The largest tolerance. A border has to end in an edge that differs by more than twice
+    /// the tolerance, which a larger tolerance would make impossible for 8-bit channels.
+    pub const MAX_TOLERANCE: u8 = 127;
+
+    /// Creates the options with the default tolerance of `16`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest difference of a channel to the border color that still counts as
+    /// border. A value of `0` only trims perfectly uniform borders, while compressed images
+    /// usually need some tolerance for the noise in their bars. Values above
+    /// [`BorderTrim::MAX_TOLERANCE`] are clamped to it.
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance.min(Self::MAX_TOLERANCE);
+        self
+    }
+
+    /// The tolerance that was set with [`BorderTrim::tolerance`], which is at most
+    /// [`BorderTrim::MAX_TOLERANCE`].
+    pub fn border_tolerance(&self) -> u8 {
+        self.tolerance
+    }
+
+    /// Detects the near-uniform borders of an image.
+    ///
+    /// # Arguments
+    /// * `img`: The image to detect the borders of
+    ///
+    /// # Returns
+    /// * The rectangle of the content inside the borders, which is the whole image if there
+    ///   are no borders or the image is uniform as a whole
+    pub fn detect(&self, img: &DynamicImage) -> CropRect {
+        let (width, height) = img.dimensions();
+        let full = CropRect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+
+        if width == 0 || height == 0 {
+            return full;
+        }
+
+        let row = |y: u32| (0..width).map(move |x| img.get_pixel(x, y));
+
+        // the rows are trimmed first, the columns are then only checked within the remaining rows
+        let top = self.border(img.get_pixel(0, 0), 0..height, row);
+        let bottom = self.border(img.get_pixel(0, height - 1), (top..height).rev(), row);
+
+        if top + bottom == height {
+            return full;
+        }
+
+        let rows = top..height - bottom;
+        let column = |x: u32| rows.clone().map(move |y| img.get_pixel(x, y));
+
+        let left = self.border(img.get_pixel(0, top), 0..width, column);
+        let right = self.border(img.get_pixel(width - 1, top), (left..width).rev(), column);
+
+        if left + right == width {
+            return full;
+        }
+
+        CropRect {
+            x: left,
+            y: top,
+            width: width - left - right,
+            height: height - top - bottom,
+        }
+    }
+}
+
+impl BorderTrim {
+    /// Counts the lines from the start of `lines` whose pixels are within the tolerance of the
+    /// reference color.
+    ///
+    /// A border only counts if the line after it clearly differs from the reference, by more
+    /// than twice the tolerance. This keeps smooth gradients like a clear sky from being
+    /// mistaken for a border, since they slowly drift out of the tolerance.
+    fn border<P: Iterator<Item = Rgba<u8>>>(
+        &self,
+        reference: Rgba<u8>,
+        lines: impl Iterator<Item = u32>,
+        pixels: impl Fn(u32) -> P,
+    ) -> u32 {
+        let mut count = 0;
+
+        for line in lines {
+            let difference = pixels(line)
+                .map(|pixel| {
+                    reference
+                        .0
+                        .iter()
+                        .zip(pixel.0)
+                        .map(|(&a, b)| a.abs_diff(b))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .max()
+                .unwrap_or(0);
+
+            if difference > self.tolerance {
+                let edge = difference as u16 > 2 * self.tolerance as u16;
+                return if edge { count } else { 0 };
+            }
+
+            count += 1;
+        }
+
+        count
+    }
+}
+
+impl Default for BorderTrim {
+    fn default() -> Self {
+        BorderTrim { tolerance: 16 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use image::{GenericImage, ImageReader, RgbaImage};
+
+    use super::*;
+    use crate::{
+        average::AverageHasher, difference::DifferenceHasher, median::MedianHasher,
+        perceptual::PerceptualHasher, ImageHasher,
+    };
+
+    const TEST_IMG: &str = "./data/img/test.png";
+
+    fn test_img() -> DynamicImage {
+        ImageReader::open(Path::new(TEST_IMG))
+            .unwrap()
+            .decode()
+            .unwrap()
+    }
+
+    /// Places the image on a canvas of the given color, with a slight noise in the border.
+    fn pad(
+        img: &DynamicImage,
+        (left, top, right, bottom): (u32, u32, u32, u32),
+        color: u8,
+    ) -> DynamicImage {
+        let mut canvas = RgbaImage::from_fn(
+            img.width() + left + right,
+            img.height() + top + bottom,
+            |x, y| {
+                let noise = ((x * 7 + y * 13) % 5) as u8;
+                let value = if color > 127 {
+                    color - noise
+                } else {
+                    color + noise
+                };
+                Rgba([value, value, value, 255])
+            },
+        );
+        canvas.copy_from(&img.to_rgba8(), left, top).unwrap();
+
+        DynamicImage::ImageRgba8(canvas)
+    }
+
+    #[test]
+    fn test_detect_letterbox() {
+        // Arrange
+        let img = test_img();
+        let letterboxed = pad(&img, (0, 40, 0, 40), 0);
+
+        // Act
+        let crop = BorderTrim::new().detect(&letterboxed);
+
+        // Assert
+        assert_eq!(
+            crop,
+            CropRect {
+                x: 0,
+                y: 40,
+                width: img.width(),
+                height: img.height()
+            }
+        );
+    }
+
+    #[test]
+    fn test_detect_whitespace() {
+        // Arrange
+        let img = test_img();
+        let padded = pad(&img, (12, 5, 30, 17), 255);
+
+        // Act
+        let crop = BorderTrim::new().detect(&padded);
+
+        // Assert
+        assert_eq!(
+            crop,
+            CropRect {
+                x: 12,
+                y: 5,
+                width: img.width(),
+                height: img.height()
+            }
+        );
+    }
+
+    #[test]
+    fn test_detect_with_zero_tolerance() {
+        // Arrange
+        let img = test_img();
+        let letterboxed = pad(&img, (0, 40, 0, 40), 0);
+
+        // Act
+        let crop = BorderTrim::new().tolerance(0).detect(&letterboxed);
+
+        // Assert: the noise in the bars is above the tolerance
+        assert_eq!(crop.height, letterboxed.height());
+    }
+
+    #[test]
+    fn test_detect_with_tolerance_above_maximum() {
+        // Arrange: a black bar, whose top left corner is pure black, above white content,
+        // which differs by the full 255. The line keeps the content from being uniform
+        let content = RgbaImage::from_fn(64, 48, |_, y| match y {
+            24 => Rgba([0, 0, 0, 255]),
+            _ => Rgba([255; 4]),
+        });
+        let letterboxed = pad(&DynamicImage::ImageRgba8(content), (0, 8, 0, 0), 0);
+
+        // Act
+        let trim = BorderTrim::new().tolerance(200);
+        let crop = trim.detect(&letterboxed);
+
+        // Assert: the clamped tolerance still leaves room for the edge
+        assert_eq!(trim.border_tolerance(), BorderTrim::MAX_TOLERANCE);
+        assert_eq!(crop.y, 8);
+    }
+
+    #[test]
+    fn test_detect_without_border() {
+        // Arrange
+        let img = test_img();
+
+        // Act
+        let crop = BorderTrim::new().detect(&img);
+
+        // Assert
+        assert_eq!(
+            crop,
+            CropRect {
+                x: 0,
+                y: 0,
+                width: img.width(),
+                height: img.height()
+            }
+        );
+    }
+
+    #[test]
+    fn test_detect_uniform_image() {
+        // Arrange
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 10, Rgba([0, 0, 0, 255])));
+
+        // Act
+        let crop = BorderTrim::new().detect(&img);
+
+        // Assert
+        assert_eq!(
+            crop,
+            CropRect {
+                x: 0,
+                y: 0,
+                width: 20,
+                height: 10
+            }
+        );
+    }
+
+    #[test]
+    fn test_detect_two_colored_image() {
+        // Arrange
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(20, 10, |_, y| {
+            Rgba([if y < 5 { 0 } else { 255 }; 4])
+        }));
+
+        // Act
+        let crop = BorderTrim::new().detect(&img);
+
+        // Assert: there is no content between the two halves, so nothing is trimmed
+        assert_eq!(crop.height, 10);
+    }
+
+    #[test]
+    fn test_detect_gradient_without_edge() {
+        // Arrange
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(20, 40, |_, y| {
+            Rgba([y as u8, y as u8, y as u8, 255])
+        }));
+
+        // Act
+        let crop = BorderTrim::new().detect(&img);
+
+        // Assert: the gradient slowly leaves the tolerance, which is no border
+        assert_eq!(crop.height, 40);
+    }
+
+    #[test]
+    fn test_hash_from_img_trimmed_matches_original() {
+        // Arrange
+        let img = test_img();
+        let letterboxed = pad(&img, (0, 40, 0, 40), 0);
+        let hashers: Vec<Box<dyn ImageHasher>> = vec![
+            Box::new(AverageHasher::default()),
+            Box::new(MedianHasher::default()),
+            Box::new(DifferenceHasher::default()),
+            Box::new(PerceptualHasher::default()),
+        ];
+
+        for hasher in hashers {
+            // Act
+            let (hash, crop) = hasher
+                .hash_from_img_trimmed(&letterboxed, BorderTrim::new())
+                .unwrap();
+
+            // Assert
+            assert_eq!(hash, hasher.hash_from_img(&img).unwrap());
+            assert_ne!(hash, hasher.hash_from_img(&letterboxed).unwrap());
+            assert_eq!(crop.y, 40);
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const TEST_IMG: &str = "./data/img/test.png";
+const ROTATED_IMG: &str = "./data/img/test-exif-rotated.png";
+const LOGO_IMG: &str = "./data/img/logo-white.png";
+const TXT_FILE: &str = "./data/misc/test.txt";
+
+fn imghash(args: &[impl AsRef<OsStr>]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_imghash"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+/// Creates an empty directory for a test with copies of the given images.
+fn fixture_dir(name: &str, images: &[(&str, &str)]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+
+    for (source, target) in images {
+        fs::copy(source, dir.join(target)).unwrap();
+    }
+
+    dir
+}
+
+#[test]
+fn test_hash_prints_hex_and_path() {
+    // Act
+    let output = imghash(&["hash", TEST_IMG, "--algo", "ahash"]);
+
+    // Assert
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), format!("ffffff0e00000301  {TEST_IMG}\n"));
+}
+
+#[test]
+fn test_hash_as_json_and_csv() {
+    // Act
+    let json = imghash(&["hash", TEST_IMG, "--format", "json"]);
+    let csv = imghash(&["hash", TEST_IMG, "-f", "csv"]);
+
+    // Assert
+    let value: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!([{ "path": TEST_IMG, "hash": "acdbe86135344e3a" }])
+    );
+    assert_eq!(
+        stdout(&csv),
+        format!("path,hash\n{TEST_IMG},acdbe86135344e3a\n")
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_json_with_non_utf8_file_name() {
+    use std::os::unix::ffi::OsStrExt;
+
+    // Arrange: a duplicate whose file name is valid on Linux, but not UTF-8
+    let dir = fixture_dir("non-utf8", &[(TEST_IMG, "test.png")]);
+    let path = dir.join(OsStr::from_bytes(b"bad\xff.png"));
+    fs::copy(TEST_IMG, &path).unwrap();
+
+    let json = |command: &str, paths: &[&Path]| {
+        let mut args = vec![OsStr::new(command), OsStr::new("-f"), OsStr::new("json")];
+        args.extend(paths.iter().map(|path| path.as_os_str()));
+
+        let output = imghash(&args);
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        (output.status.code(), value)
+    };
+
+    // Act
+    let hash = json("hash", &[&path]);
+    let compare = json("compare", &[&path, &path]);
+    let dupes = json("dupes", &[&dir]);
+
+    // Assert: the invalid byte is replaced instead of failing the output
+    let name = path.to_string_lossy();
+    assert!(name.ends_with("bad\u{FFFD}.png"));
+    assert_eq!(
+        hash,
+        (
+            Some(0),
+            serde_json::json!([{ "path": name, "hash": "acdbe86135344e3a" }])
+        )
+    );
+    assert_eq!(compare.0, Some(0));
+    assert_eq!(compare.1["a"]["path"], *name);
+    assert_eq!(dupes.0, Some(1));
+    assert_eq!(dupes.1[0][0]["path"], *name);
+}
+
+#[test]
+fn test_hash_with_spec_and_size() {
+    // Act
+    let spec = imghash(&["hash", TEST_IMG, "--spec", "dhash?size=16"]);
+    let size = imghash(&["hash", TEST_IMG, "--algo", "dhash", "--size", "16"]);
+
+    // Assert
+    assert_eq!(spec.status.code(), Some(0));
+    assert_eq!(stdout(&spec), stdout(&size));
+    assert_eq!(stdout(&spec).split_whitespace().next().unwrap().len(), 64);
+}
+
+#[test]
+fn test_hash_with_unreadable_file() {
+    // Act
+    let output = imghash(&["hash", TEST_IMG, TXT_FILE]);
+
+    // Assert: the readable image is still hashed
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output).lines().count(), 1);
+    assert!(String::from_utf8_lossy(&output.stderr).contains(TXT_FILE));
+}
+
+#[test]
+fn test_hash_with_invalid_arguments() {
+    // Act
+    let algo = imghash(&["hash", TEST_IMG, "--algo", "xhash"]);
+    let size = imghash(&["hash", TEST_IMG, "--size", "0"]);
+    let missing = imghash(&["hash"]);
+
+    // Assert
+    assert_eq!(algo.status.code(), Some(2));
+    assert_eq!(size.status.code(), Some(2));
+    assert_eq!(missing.status.code(), Some(2));
+}
+
+#[test]
+fn test_hash_applies_exif_orientation_on_request() {
+    // Act
+    let stored = imghash(&["hash", ROTATED_IMG]);
+    let oriented = imghash(&["hash", ROTATED_IMG, "--exif"]);
+
+    // Assert
+    assert_ne!(stdout(&stored), stdout(&oriented));
+}
+
+#[test]
+fn test_compare_prints_distance() {
+    // Act
+    let same = imghash(&["compare", TEST_IMG, TEST_IMG]);
+    let different = imghash(&["compare", TEST_IMG, LOGO_IMG]);
+    let loose = imghash(&["compare", TEST_IMG, LOGO_IMG, "--threshold", "64"]);
+
+    // Assert
+    assert_eq!(same.status.code(), Some(0));
+    assert_eq!(stdout(&same), "0\n");
+    assert_eq!(different.status.code(), Some(1));
+    assert!(stdout(&different).trim().parse::<usize>().unwrap() > 16);
+    assert_eq!(loose.status.code(), Some(0));
+}
+
+#[test]
+fn test_compare_as_json() {
+    // Act
+    let output = imghash(&["compare", TEST_IMG, TEST_IMG, "--format", "json"]);
+
+    // Assert
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["distance"], 0);
+    assert_eq!(value["similarity"], 1.0);
+    assert_eq!(value["threshold"], 16);
+    assert_eq!(value["a"]["hash"], "acdbe86135344e3a");
+}
+
+#[test]
+fn test_compare_with_unreadable_file() {
+    // Act
+    let output = imghash(&["compare", TEST_IMG, TXT_FILE]);
+
+    // Assert
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_dupes_groups_similar_images() {
+    // Act: the fixtures contain several variants of the test image and the logo
+    let output = imghash(&["dupes", "./data/img", "--format", "json"]);
+
+    // Assert
+    assert_eq!(output.status.code(), Some(1));
+
+    let groups: Vec<Vec<serde_json::Value>> = serde_json::from_slice(&output.stdout).unwrap();
+    let paths = groups
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|image| image["path"].as_str().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    assert!(paths
+        .iter()
+        .any(|group| group.contains(&"./data/img/test.png")
+            && group.contains(&"./data/img/test-dark.png")));
+    assert!(paths.iter().all(|group| group.len() > 1));
+}
+
+#[test]
+fn test_dupes_without_duplicates() {
+    // Arrange: two distinct images, one of them in a subdirectory
+    let dir = fixture_dir(
+        "dupes-distinct",
+        &[(TEST_IMG, "test.png"), (LOGO_IMG, "nested/logo.png")],
+    );
+
+    // Act
+    let output = imghash(&["dupes", dir.to_str().unwrap()]);
+    let csv = imghash(&[
+        "dupes",
+        dir.to_str().unwrap(),
+        "--threshold",
+        "64",
+        "-f",
+        "csv",
+    ]);
+
+    // Assert
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert_eq!(csv.status.code(), Some(1));
+    assert_eq!(stdout(&csv).lines().count(), 3);
+}
+
+#[test]
+fn test_dupes_with_missing_directory() {
+    // Act
+    let output = imghash(&["dupes", "./does/not/exist"]);
+
+    // Assert
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_dupes_skips_unreadable_subdirectory() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Arrange: a duplicate in a subdirectory and another one in a directory without permissions
+    let dir = fixture_dir(
+        "dupes-unreadable",
+        &[(TEST_IMG, "test.png"), (TEST_IMG, "nested/test.png")],
+    );
+    let locked = dir.join("locked");
+    fs::create_dir(&locked).unwrap();
+    fs::copy(TEST_IMG, locked.join("test.png")).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+    // root can read the directory anyway
+    let readable = fs::read_dir(&locked).is_ok();
+
+    // Act
+    let output = imghash(&["dupes", dir.to_str().unwrap()]);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+    if readable {
+        return;
+    }
+
+    // Assert: the directory is reported, while the other duplicates are still grouped
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("locked"));
+    assert_eq!(stdout(&output).lines().count(), 2);
+}
+
+#[test]
+fn test_index_build_and_query() {
+    // Arrange
+    let dir = fixture_dir(
+        "index-query",
+        &[
+            (TEST_IMG, "test.png"),
+            ("./data/img/test-dark.png", "nested/dark.png"),
+            (LOGO_IMG, "logo.png"),
+        ],
+    );
+    let index = dir.join("corpus.idx");
+    let index = index.to_str().unwrap();
+
+    // Act
+    let build = imghash(&["index", "build", dir.to_str().unwrap(), "-o", index]);
+    let query = imghash(&["index", "query", index, TEST_IMG, "--format", "json"]);
+    let top = imghash(&["index", "query", index, TEST_IMG, "-d", "64", "--top", "2"]);
+    let none = imghash(&[
+        "index",
+        "query",
+        index,
+        TEST_IMG,
+        "--max-distance",
+        "0",
+        "-n",
+        "0",
+    ]);
+
+    // Assert
+    assert_eq!(build.status.code(), Some(0));
+    assert!(stdout(&build).starts_with("indexed 3 images"));
+
+    let matches: Vec<serde_json::Value> = serde_json::from_slice(&query.stdout).unwrap();
+    assert_eq!(query.status.code(), Some(0));
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0]["distance"], 0);
+    assert_eq!(matches[0]["hash"], "acdbe86135344e3a");
+    assert!(matches[0]["path"].as_str().unwrap().ends_with("test.png"));
+    assert!(matches[1]["path"].as_str().unwrap().ends_with("dark.png"));
+
+    assert_eq!(stdout(&top).lines().count(), 2);
+    assert!(stdout(&top).starts_with("0  acdbe86135344e3a  "));
+    assert_eq!(none.status.code(), Some(1));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_index_build_skips_unsavable_and_unreadable_files() {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    // Arrange: a file name that is not UTF-8 and a link to a file that is gone
+    let dir = fixture_dir("index-unsavable", &[(TEST_IMG, "test.png")]);
+    fs::copy(TEST_IMG, dir.join(OsStr::from_bytes(b"bad\xff.png"))).unwrap();
+    symlink(dir.join("missing.png"), dir.join("gone.png")).unwrap();
+
+    let index = dir.join("corpus.idx");
+    let index = index.to_str().unwrap();
+
+    // Act
+    let build = imghash(&["index", "build", dir.to_str().unwrap(), "-o", index]);
+    let query = imghash(&["index", "query", index, TEST_IMG, "--format", "json"]);
+
+    // Assert: both files are reported, while the remaining image is indexed
+    let stderr = String::from_utf8_lossy(&build.stderr);
+    assert_eq!(build.status.code(), Some(3));
+    assert!(stdout(&build).starts_with("indexed 1 images"));
+    assert!(stderr.contains("bad\u{FFFD}.png: path is not valid UTF-8"));
+    assert!(stderr.contains("gone.png"));
+    assert!(!dir.join("corpus.idx.tmp").exists());
+
+    let matches: Vec<serde_json::Value> = serde_json::from_slice(&query.stdout).unwrap();
+    assert_eq!(query.status.code(), Some(0));
+    assert_eq!(matches.len(), 1);
+    assert!(matches[0]["path"].as_str().unwrap().ends_with("test.png"));
+}
+
+#[test]
+fn test_index_update_rehashes_changed_files() {
+    // Arrange
+    let dir = fixture_dir(
+        "index-update",
+        &[
+            (TEST_IMG, "a.png"),
+            (TEST_IMG, "b.png"),
+            (LOGO_IMG, "c.png"),
+        ],
+    );
+    let index = dir.join("corpus.idx");
+    let index = index.to_str().unwrap();
+    imghash(&[
+        "index",
+        "build",
+        dir.to_str().unwrap(),
+        "-o",
+        index,
+        "--algo",
+        "ahash",
+    ]);
+
+    fs::copy(LOGO_IMG, dir.join("b.png")).unwrap();
+    fs::remove_file(dir.join("c.png")).unwrap();
+    fs::copy(TEST_IMG, dir.join("nested/d.png")).unwrap();
+
+    // Act
+    let update = imghash(&["index", "update", index]);
+    let unchanged = imghash(&["index", "update", index]);
+    let query = imghash(&["index", "query", index, LOGO_IMG, "-d", "0"]);
+
+    // Assert: the index keeps the hasher it was built with
+    assert_eq!(update.status.code(), Some(0));
+    assert_eq!(
+        stdout(&update),
+        "1 added, 1 changed, 1 removed, 1 unchanged\n"
+    );
+    assert_eq!(
+        stdout(&unchanged),
+        "0 added, 0 changed, 0 removed, 3 unchanged\n"
+    );
+    assert_eq!(stdout(&query).lines().count(), 1);
+    assert!(stdout(&query).starts_with("0  ffc399818199c3ff  "));
+    assert!(stdout(&query).trim_end().ends_with("b.png"));
+}
+
+#[test]
+fn test_index_stats() {
+    // Arrange
+    let dir = fixture_dir(
+        "index-stats",
+        &[
+            (TEST_IMG, "a.png"),
+            (TEST_IMG, "nested/b.png"),
+            (LOGO_IMG, "c.png"),
+        ],
+    );
+    let index = dir.join("corpus.idx");
+    let index = index.to_str().unwrap();
+    imghash(&["index", "build", dir.to_str().unwrap(), "-o", index]);
+
+    // Act
+    let text = imghash(&["index", "stats", index]);
+    let json = imghash(&["index", "stats", index, "--format", "json"]);
+
+    // Assert
+    assert_eq!(text.status.code(), Some(0));
+    assert!(stdout(&text).contains("duplicates:  2 in 1 groups within 16 bits"));
+
+    let stats: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(stats["images"], 3);
+    assert_eq!(stats["distinct"], 2);
+    assert_eq!(stats["duplicates"], 2);
+    assert_eq!(stats["groups"], 1);
+    assert_eq!(
+        stats["nearest"][0],
+        serde_json::json!({ "distance": 0, "images": 2 })
+    );
+}
+
+#[test]
+fn test_index_query_with_invalid_index() {
+    // Act
+    let output = imghash(&["index", "query", TXT_FILE, TEST_IMG]);
+
+    // Assert
+    assert_eq!(output.status.code(), Some(3));
+}